@@ -0,0 +1,291 @@
+//! Format auto-detection for Home archives.
+//!
+//! BAR and SHARC archives share the same magic and only differ by the version
+//! field that follows it, so file extensions are not a reliable way to tell them
+//! apart. This module sniffs the header and opens the matching reader.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use hdk_archive::any::{ArchiveKeys, open_any};
+//! use hdk_archive::archive::ArchiveReader;
+//!
+//! let file = std::fs::File::open("path/to/archive.bar").unwrap();
+//! let keys = ArchiveKeys::new()
+//!     .with_bar_keys([0u8; 32], [0u8; 32])
+//!     .with_sharc_key([0u8; 32]);
+//!
+//! let archive = open_any(file, &keys).unwrap();
+//! println!("{:?} archive with {} entries", archive.version(), archive.entry_count());
+//! ```
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use enumflags2::BitFlags;
+use hdk_secure::hash::AfsHash;
+
+use crate::archive::{ArchiveReader, EntryMetadata, RawEntry, ReadSeek};
+use crate::bar::{BarEntryMetadata, BarReader};
+use crate::error::ArchiveError;
+use crate::sharc::reader::SharcReader;
+use crate::sharc::structs::SharcEntryMetadata;
+use crate::structs::{ARCHIVE_MAGIC, ArchiveFlags, ArchiveVersion, CompressionType, Endianness};
+
+#[cfg(test)]
+mod tests;
+
+/// Keys that may be needed to open an archive of unknown format.
+///
+/// Only the keys for the detected format are required; missing ones are
+/// reported as [`ArchiveError::KeysRequired`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ArchiveKeys {
+    /// The Blowfish key used for decrypting encrypted BAR file bodies.
+    pub bar_default_key: Option<[u8; 32]>,
+
+    /// The Blowfish key used for decrypting encrypted BAR file headers.
+    pub bar_signature_key: Option<[u8; 32]>,
+
+    /// The AES key used for decrypting the SHARC header and ToC.
+    pub sharc_key: Option<[u8; 32]>,
+}
+
+impl ArchiveKeys {
+    pub const fn new() -> Self {
+        Self {
+            bar_default_key: None,
+            bar_signature_key: None,
+            sharc_key: None,
+        }
+    }
+
+    /// Set the Blowfish keys used for BAR archives.
+    pub const fn with_bar_keys(mut self, default_key: [u8; 32], signature_key: [u8; 32]) -> Self {
+        self.bar_default_key = Some(default_key);
+        self.bar_signature_key = Some(signature_key);
+        self
+    }
+
+    /// Set the AES key used for SHARC archives.
+    pub const fn with_sharc_key(mut self, key: [u8; 32]) -> Self {
+        self.sharc_key = Some(key);
+        self
+    }
+}
+
+/// The format information sniffed from an archive's first 8 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveFormat {
+    pub version: ArchiveVersion,
+    pub endianness: Endianness,
+    pub flags: BitFlags<ArchiveFlags>,
+}
+
+/// Sniff the magic and version of an archive.
+///
+/// The reader is restored to its original position afterwards.
+pub fn detect_format<R: Read + Seek>(reader: &mut R) -> Result<ArchiveFormat, ArchiveError> {
+    let start = reader.stream_position()?;
+
+    let mut raw = [0u8; 8];
+    let read = reader.read_exact(&mut raw);
+    reader.seek(SeekFrom::Start(start))?;
+    read?;
+
+    let mut cursor = &raw[..];
+    let magic = cursor.read_u32::<LittleEndian>()?;
+
+    let (endianness, version_and_flags) = if magic == ARCHIVE_MAGIC {
+        (Endianness::Little, cursor.read_u32::<LittleEndian>()?)
+    } else if magic.swap_bytes() == ARCHIVE_MAGIC {
+        (Endianness::Big, cursor.read_u32::<BigEndian>()?)
+    } else {
+        return Err(ArchiveError::InvalidMagic(magic));
+    };
+
+    let version_raw = (version_and_flags >> 16) as u16;
+    let version = match ArchiveVersion::try_from(version_raw) {
+        Ok(ArchiveVersion::Unknown) | Err(_) => {
+            return Err(ArchiveError::UnknownVersion(version_raw));
+        }
+        Ok(version) => version,
+    };
+
+    Ok(ArchiveFormat {
+        version,
+        endianness,
+        flags: BitFlags::from_bits_truncate((version_and_flags & 0xFFFF) as u16),
+    })
+}
+
+/// Detect the format of an archive and open it with the matching reader.
+pub fn open_any<R: Read + Seek>(
+    mut reader: R,
+    keys: &ArchiveKeys,
+) -> Result<AnyArchive<R>, ArchiveError> {
+    let format = detect_format(&mut reader)?;
+
+    match format.version {
+        ArchiveVersion::BAR => {
+            // `BarReader` only understands little-endian archives.
+            if format.endianness != Endianness::Little {
+                return Err(ArchiveError::UnsupportedEndianness {
+                    version: format.version,
+                    endianness: format.endianness,
+                });
+            }
+
+            let (Some(default_key), Some(signature_key)) =
+                (keys.bar_default_key, keys.bar_signature_key)
+            else {
+                return Err(ArchiveError::KeysRequired(ArchiveVersion::BAR));
+            };
+
+            Ok(AnyArchive::Bar(BarReader::open(
+                reader,
+                default_key,
+                signature_key,
+            )?))
+        }
+        ArchiveVersion::SHARC => {
+            let key = keys
+                .sharc_key
+                .ok_or(ArchiveError::KeysRequired(ArchiveVersion::SHARC))?;
+
+            Ok(AnyArchive::Sharc(SharcReader::open(reader, key)?))
+        }
+        ArchiveVersion::Unknown => Err(ArchiveError::UnknownVersion(format.version.into())),
+    }
+}
+
+/// Open an archive, failing with [`ArchiveError::WrongFormat`] if it is not of the
+/// `expected` format.
+pub fn open_as<R: Read + Seek>(
+    mut reader: R,
+    expected: ArchiveVersion,
+    keys: &ArchiveKeys,
+) -> Result<AnyArchive<R>, ArchiveError> {
+    let format = detect_format(&mut reader)?;
+
+    if format.version != expected {
+        return Err(ArchiveError::WrongFormat {
+            expected,
+            found: format.version,
+        });
+    }
+
+    open_any(reader, keys)
+}
+
+/// An archive reader over any supported format.
+pub enum AnyArchive<R: Read + Seek> {
+    Bar(BarReader<R>),
+    Sharc(SharcReader<R>),
+}
+
+impl<R: Read + Seek> AnyArchive<R> {
+    pub const fn version(&self) -> ArchiveVersion {
+        match self {
+            Self::Bar(_) => ArchiveVersion::BAR,
+            Self::Sharc(_) => ArchiveVersion::SHARC,
+        }
+    }
+
+    pub const fn endianness(&self) -> Endianness {
+        match self {
+            Self::Bar(_) => Endianness::Little,
+            Self::Sharc(sharc) => sharc.endianness,
+        }
+    }
+}
+
+/// Metadata view for an entry of an [`AnyArchive`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnyEntryMetadata {
+    Bar(BarEntryMetadata),
+    Sharc(SharcEntryMetadata),
+}
+
+impl EntryMetadata for AnyEntryMetadata {
+    fn name_hash(&self) -> AfsHash {
+        match self {
+            Self::Bar(m) => m.name_hash(),
+            Self::Sharc(m) => m.name_hash(),
+        }
+    }
+
+    fn compression(&self) -> CompressionType {
+        match self {
+            Self::Bar(m) => m.compression(),
+            Self::Sharc(m) => m.compression(),
+        }
+    }
+
+    fn uncompressed_size(&self) -> u32 {
+        match self {
+            Self::Bar(m) => m.uncompressed_size(),
+            Self::Sharc(m) => m.uncompressed_size(),
+        }
+    }
+
+    fn compressed_size(&self) -> u32 {
+        match self {
+            Self::Bar(m) => m.compressed_size(),
+            Self::Sharc(m) => m.compressed_size(),
+        }
+    }
+}
+
+impl<R: Read + Seek> ArchiveReader for AnyArchive<R> {
+    type Metadata = AnyEntryMetadata;
+
+    fn entry_count(&self) -> usize {
+        match self {
+            Self::Bar(bar) => bar.entry_count(),
+            Self::Sharc(sharc) => sharc.entry_count(),
+        }
+    }
+
+    fn timestamp(&self) -> i32 {
+        match self {
+            Self::Bar(bar) => bar.timestamp(),
+            Self::Sharc(sharc) => sharc.timestamp(),
+        }
+    }
+
+    fn entry_metadata(&self, index: usize) -> io::Result<AnyEntryMetadata> {
+        match self {
+            Self::Bar(bar) => bar.entry_metadata(index).map(AnyEntryMetadata::Bar),
+            Self::Sharc(sharc) => sharc.entry_metadata(index).map(AnyEntryMetadata::Sharc),
+        }
+    }
+
+    fn find_by_hash(&self, name_hash: AfsHash) -> Result<Option<usize>, ArchiveError> {
+        match self {
+            Self::Bar(bar) => bar.find_by_hash(name_hash),
+            Self::Sharc(sharc) => sharc.find_by_hash(name_hash),
+        }
+    }
+
+    fn entry_reader<'a>(&'a mut self, index: usize) -> io::Result<Box<dyn Read + 'a>> {
+        match self {
+            Self::Bar(bar) => bar.entry_reader(index),
+            Self::Sharc(sharc) => sharc.entry_reader(index),
+        }
+    }
+
+    fn raw_entry_reader<'a>(&'a mut self, index: usize) -> io::Result<RawEntry<'a>> {
+        match self {
+            Self::Bar(bar) => bar.raw_entry_reader(index),
+            Self::Sharc(sharc) => sharc.raw_entry_reader(index),
+        }
+    }
+
+    fn entry_seekable_reader<'a>(&'a mut self, index: usize) -> io::Result<Box<dyn ReadSeek + 'a>> {
+        match self {
+            Self::Bar(bar) => bar.entry_seekable_reader(index),
+            Self::Sharc(sharc) => sharc.entry_seekable_reader(index),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::io::{Cursor, Read};
+
+use hdk_secure::hash::AfsHash;
+
+use crate::any::{AnyArchive, ArchiveKeys, detect_format, open_any, open_as};
+use crate::archive::{ArchiveReader, ArchiveWriter, EntryMetadata};
+use crate::bar::writer::BarWriter;
+use crate::error::ArchiveError;
+use crate::sharc::writer::SharcWriter;
+use crate::structs::{ArchiveVersion, CompressionType, Endianness};
+use crate::test_utils::{
+    TEST_DEFAULT_KEY, TEST_KEYS, TEST_SHARC_KEY, TEST_SIGNATURE_KEY, TestEntry, create_bar,
+    create_sharc,
+};
+
+const BAR_ENTRIES: [TestEntry; 1] = [("file_a", CompressionType::ZLib, b"Hello BAR")];
+const SHARC_ENTRIES: [TestEntry; 1] = [("file_a", CompressionType::Encrypted, b"Hello SHARC")];
+
+fn read_first(archive: &mut AnyArchive<Cursor<Vec<u8>>>) -> Vec<u8> {
+    let mut out = Vec::new();
+    archive
+        .entry_reader(0)
+        .unwrap()
+        .read_to_end(&mut out)
+        .unwrap();
+    out
+}
+
+#[test]
+fn detects_bar() {
+    let mut cursor = Cursor::new(create_bar(&BAR_ENTRIES));
+    let format = detect_format(&mut cursor).unwrap();
+
+    assert_eq!(format.version, ArchiveVersion::BAR);
+    assert_eq!(format.endianness, Endianness::Little);
+    assert_eq!(cursor.position(), 0);
+}
+
+#[test]
+fn detects_sharc_both_endianness() {
+    for endianness in [Endianness::Little, Endianness::Big] {
+        let mut cursor = Cursor::new(create_sharc(&SHARC_ENTRIES, endianness));
+        let format = detect_format(&mut cursor).unwrap();
+
+        assert_eq!(format.version, ArchiveVersion::SHARC);
+        assert_eq!(format.endianness, endianness);
+    }
+}
+
+#[test]
+fn open_any_reads_bar_and_sharc() {
+    let mut bar = open_any(Cursor::new(create_bar(&BAR_ENTRIES)), &TEST_KEYS).unwrap();
+    assert!(matches!(bar, AnyArchive::Bar(_)));
+    assert_eq!(
+        bar.entry_metadata(0).unwrap().name_hash(),
+        AfsHash::new_from_str("file_a")
+    );
+    assert_eq!(read_first(&mut bar), b"Hello BAR");
+
+    let mut sharc = open_any(
+        Cursor::new(create_sharc(&SHARC_ENTRIES, Endianness::Big)),
+        &TEST_KEYS,
+    )
+    .unwrap();
+    assert!(matches!(sharc, AnyArchive::Sharc(_)));
+    assert_eq!(sharc.endianness(), Endianness::Big);
+    assert_eq!(read_first(&mut sharc), b"Hello SHARC");
+}
+
+#[test]
+fn reports_missing_keys() {
+    let keys = ArchiveKeys::new().with_sharc_key(TEST_SHARC_KEY);
+    let err = open_any(Cursor::new(create_bar(&BAR_ENTRIES)), &keys)
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err,
+        ArchiveError::KeysRequired(ArchiveVersion::BAR)
+    ));
+
+    let keys = ArchiveKeys::new().with_bar_keys(TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY);
+    let err = open_any(
+        Cursor::new(create_sharc(&SHARC_ENTRIES, Endianness::Little)),
+        &keys,
+    )
+    .err()
+    .unwrap();
+    assert!(matches!(
+        err,
+        ArchiveError::KeysRequired(ArchiveVersion::SHARC)
+    ));
+}
+
+#[test]
+fn reports_wrong_format() {
+    let err = open_as(
+        Cursor::new(create_bar(&BAR_ENTRIES)),
+        ArchiveVersion::SHARC,
+        &TEST_KEYS,
+    )
+    .err()
+    .unwrap();
+    assert!(matches!(
+        err,
+        ArchiveError::WrongFormat {
+            expected: ArchiveVersion::SHARC,
+            found: ArchiveVersion::BAR
+        }
+    ));
+}
+
+#[test]
+fn reports_invalid_magic_and_version() {
+    let err = detect_format(&mut Cursor::new(vec![0u8; 8])).unwrap_err();
+    assert!(matches!(err, ArchiveError::InvalidMagic(0)));
+
+    let mut data = create_bar(&BAR_ENTRIES);
+    data[6..8].copy_from_slice(&0x0300u16.to_le_bytes());
+    let err = detect_format(&mut Cursor::new(data)).unwrap_err();
+    assert!(matches!(err, ArchiveError::UnknownVersion(0x0300)));
+}
+
+fn pack_generic<W: ArchiveWriter>(mut writer: W) -> W::Output {
+    writer
+        .add_entry_from_bytes(
+            AfsHash::new_from_str("a.txt"),
+            CompressionType::None,
+            b"plain",
+        )
+        .unwrap();
+    writer
+        .add_entry_from_reader(
+            AfsHash::new_from_str("b.xml"),
+            CompressionType::Encrypted,
+            &mut Cursor::new(b"<secret />"),
+        )
+        .unwrap();
+    writer.finish().unwrap()
+}
+
+#[test]
+fn generic_writer_targets_both_formats() {
+    let bar = pack_generic(BarWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    ))
+    .into_inner();
+    let sharc =
+        pack_generic(SharcWriter::new(Vec::new(), TEST_SHARC_KEY, Endianness::Big).unwrap());
+
+    for data in [bar, sharc] {
+        let mut archive = open_any(Cursor::new(data), &TEST_KEYS).unwrap();
+        assert_eq!(archive.entry_count(), 2);
+
+        let mut out = Vec::new();
+        archive
+            .entry_reader(1)
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, b"<secret />");
+    }
+}
@@ -1,6 +1,34 @@
-use std::io::Read;
+use std::collections::HashMap;
+use std::io::{Read, Seek};
 use std::ops::Range;
 
+use enumflags2::BitFlags;
+use hdk_secure::hash::AfsHash;
+
+use crate::error::ArchiveError;
+use crate::structs::{ArchiveFlags, CompressionType};
+
+/// Format-independent view over an entry's metadata.
+///
+/// Every archive format stores the same core fields for each entry, even if the
+/// concrete metadata types carry extra format-specific data (e.g. SHARC IVs).
+pub trait EntryMetadata {
+    fn name_hash(&self) -> AfsHash;
+
+    fn compression(&self) -> CompressionType;
+
+    fn uncompressed_size(&self) -> u32;
+
+    fn compressed_size(&self) -> u32;
+}
+
+/// A reader that can also seek, for random access into entry content.
+///
+/// This only exists so it can be used as a trait object (`Box<dyn ReadSeek>`).
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek + ?Sized> ReadSeek for T {}
+
 /// Bundles copyable entry metadata plus a reader for the entry content.
 ///
 /// The reader lifetime is tied to the archive reader borrow when the entry
@@ -10,12 +38,49 @@ pub struct EntryStream<'a, M> {
     pub reader: Box<dyn Read + 'a>,
 }
 
+/// An entry's stored payload: decrypted, but still compressed.
+///
+/// Returned by [`ArchiveReader::raw_entry_reader`] and accepted by
+/// [`ArchiveWriter::add_raw_entry`], this lets entries be copied between archives
+/// (of the same or different formats) without being decompressed and recompressed,
+/// so unchanged entries stay byte-identical when repacking.
+///
+/// Encryption is removed from the payload because it depends on where the entry is
+/// written: BAR IVs are forged from the entry's offset in the new archive, and
+/// SHARC entries are encrypted with the new archive's files key. Since the ciphers
+/// are stream ciphers, re-encrypting with the same key and IV reproduces the
+/// original bytes.
+pub struct RawEntry<'a> {
+    pub name_hash: AfsHash,
+
+    /// How `reader`'s content is compressed.
+    ///
+    /// For `Encrypted` entries, the content is the decrypted EdgeZLib stream. `ZLib`
+    /// content is always a complete zlib stream, even when read from a `LeanZLib` BAR.
+    pub compression: CompressionType,
+
+    pub uncompressed_size: u32,
+
+    /// The XTEA IV the payload was encrypted with in a SHARC archive.
+    ///
+    /// SHARC writers re-encrypt with it instead of a random IV. Uncompressed entries
+    /// with an IV are SHARC encrypted nested archives.
+    pub iv: Option<[u8; 8]>,
+
+    /// The SHA-1 of the uncompressed content, if the source format stores one.
+    ///
+    /// BAR writers compute it by decompressing the payload when it is missing.
+    pub checksum: Option<[u8; 20]>,
+
+    pub reader: Box<dyn Read + 'a>,
+}
+
 /// Common streaming read API shared by archive readers (e.g. BAR, SHARC).
 ///
 /// This trait intentionally does **not** include construction/opening, because
 /// formats may require different parameters (e.g. SHARC keys).
 pub trait ArchiveReader {
-    type Metadata: Copy;
+    type Metadata: EntryMetadata + Copy;
 
     fn is_empty(&self) -> bool {
         self.entry_count() == 0
@@ -23,6 +88,11 @@ pub trait ArchiveReader {
 
     fn entry_count(&self) -> usize;
 
+    /// The timestamp stored in the archive header.
+    ///
+    /// This should match the archive's `.time` file.
+    fn timestamp(&self) -> i32;
+
     fn entry_metadata(&self, index: usize) -> std::io::Result<Self::Metadata>;
 
     /// Iterate copyable metadata for all entries.
@@ -35,12 +105,75 @@ pub trait ArchiveReader {
         0..self.entry_count()
     }
 
+    /// Find the index of the entry with the given name hash.
+    ///
+    /// Fails with [`ArchiveError::DuplicateHash`] if several entries share the hash,
+    /// since there is no way to tell which one is meant.
+    ///
+    /// The default implementation scans every entry; archive readers override it
+    /// with an index built when the archive is opened.
+    fn find_by_hash(&self, name_hash: AfsHash) -> Result<Option<usize>, ArchiveError> {
+        let mut indices = Vec::new();
+        for (index, metadata) in self.entries().enumerate() {
+            if metadata?.name_hash() == name_hash {
+                indices.push(index);
+            }
+        }
+
+        match indices.as_slice() {
+            [] => Ok(None),
+            [index] => Ok(Some(*index)),
+            _ => Err(ArchiveError::DuplicateHash { name_hash, indices }),
+        }
+    }
+
+    /// Find the index of the entry with the given path.
+    ///
+    /// Paths are hashed with [`AfsHash::new_from_str`], so they are case-insensitive
+    /// and `\` and `/` are interchangeable.
+    fn find_by_path(&self, path: &str) -> Result<Option<usize>, ArchiveError> {
+        self.find_by_hash(AfsHash::new_from_str(path))
+    }
+
+    /// Open the entry with the given path, failing with [`ArchiveError::EntryNotFound`]
+    /// if there is none.
+    fn open_path<'a>(
+        &'a mut self,
+        path: &str,
+    ) -> Result<EntryStream<'a, Self::Metadata>, ArchiveError> {
+        let name_hash = AfsHash::new_from_str(path);
+        let index = self
+            .find_by_hash(name_hash)?
+            .ok_or(ArchiveError::EntryNotFound(name_hash))?;
+
+        Ok(self.entry(index)?)
+    }
+
     /// Stream an entry's content.
     ///
     /// This borrows `self` mutably because implementations typically `seek()` the
     /// underlying reader.
     fn entry_reader<'a>(&'a mut self, index: usize) -> std::io::Result<Box<dyn Read + 'a>>;
 
+    /// Open an entry's content for random access.
+    ///
+    /// Formats that can seek into an entry without decompressing everything before
+    /// the target position (e.g. uncompressed or EdgeZLib entries) override this.
+    /// The default implementation buffers the whole entry in memory.
+    fn entry_seekable_reader<'a>(
+        &'a mut self,
+        index: usize,
+    ) -> std::io::Result<Box<dyn ReadSeek + 'a>> {
+        let mut data = Vec::with_capacity(self.entry_metadata(index)?.uncompressed_size() as usize);
+        self.entry_reader(index)?.read_to_end(&mut data)?;
+        Ok(Box::new(std::io::Cursor::new(data)))
+    }
+
+    /// Open an entry's stored payload, decrypted but not decompressed.
+    ///
+    /// See [`RawEntry`] for how the payload can be passed to an [`ArchiveWriter`].
+    fn raw_entry_reader<'a>(&'a mut self, index: usize) -> std::io::Result<RawEntry<'a>>;
+
     fn entry<'a>(&'a mut self, index: usize) -> std::io::Result<EntryStream<'a, Self::Metadata>> {
         let metadata = self.entry_metadata(index)?;
         let reader = self.entry_reader(index)?;
@@ -63,3 +196,105 @@ pub trait ArchiveReader {
         Ok(())
     }
 }
+
+/// Common write API shared by archive writers (e.g. BAR, SHARC).
+///
+/// Like [`ArchiveReader`], construction is left to each format, since they take
+/// different keys and options.
+pub trait ArchiveWriter {
+    /// What `finish()` hands back once the archive is written (usually the inner writer).
+    type Output;
+
+    /// Add an entry, reading its content from `reader` and compressing it as requested.
+    fn add_entry_from_reader<R: Read + ?Sized>(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        reader: &mut R,
+    ) -> std::io::Result<()>;
+
+    /// Add an entry from a byte slice.
+    fn add_entry_from_bytes(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        bytes: &[u8],
+    ) -> std::io::Result<()> {
+        let mut cursor = std::io::Cursor::new(bytes);
+        self.add_entry_from_reader(name_hash, compression, &mut cursor)
+    }
+
+    /// Add an entry from a payload read with [`ArchiveReader::raw_entry_reader`], without
+    /// recompressing it.
+    ///
+    /// Encrypted payloads are re-encrypted as the target format requires.
+    fn add_raw_entry(&mut self, entry: RawEntry<'_>) -> std::io::Result<()>;
+
+    /// The archive flags to write in the header.
+    fn flags(&self) -> BitFlags<ArchiveFlags>;
+
+    /// Set the archive flags to write in the header.
+    ///
+    /// Fails if the writer cannot lay out archives with these flags.
+    fn set_flags(&mut self, flags: BitFlags<ArchiveFlags>) -> std::io::Result<()>;
+
+    /// Set the priority written in the header.
+    fn set_priority(&mut self, priority: i32);
+
+    /// Set the timestamp written in the header.
+    ///
+    /// This should match the archive's `.time` file.
+    fn set_timestamp(&mut self, timestamp: i32);
+
+    /// Write the archive and return the output.
+    fn finish(self) -> std::io::Result<Self::Output>;
+}
+
+/// Maps name hashes to entry indices, keeping track of hashes shared by several entries.
+#[derive(Debug, Default)]
+pub(crate) struct HashIndex {
+    indices: HashMap<AfsHash, usize>,
+    duplicates: HashMap<AfsHash, Vec<usize>>,
+}
+
+impl HashIndex {
+    pub(crate) fn new(hashes: impl IntoIterator<Item = AfsHash>) -> Self {
+        let mut index = Self::default();
+
+        for (i, name_hash) in hashes.into_iter().enumerate() {
+            if let Some(&first) = index.indices.get(&name_hash) {
+                index
+                    .duplicates
+                    .entry(name_hash)
+                    .or_insert_with(|| vec![first])
+                    .push(i);
+            } else {
+                index.indices.insert(name_hash, i);
+            }
+        }
+
+        index
+    }
+
+    pub(crate) fn find(&self, name_hash: AfsHash) -> Result<Option<usize>, ArchiveError> {
+        if let Some(indices) = self.duplicates.get(&name_hash) {
+            return Err(ArchiveError::DuplicateHash {
+                name_hash,
+                indices: indices.clone(),
+            });
+        }
+
+        Ok(self.indices.get(&name_hash).copied())
+    }
+
+    /// Fail with the duplicate hash found first, if any.
+    pub(crate) fn check_unique(&self) -> Result<(), ArchiveError> {
+        match self.duplicates.iter().min_by_key(|(_, indices)| indices[0]) {
+            Some((&name_hash, indices)) => Err(ArchiveError::DuplicateHash {
+                name_hash,
+                indices: indices.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
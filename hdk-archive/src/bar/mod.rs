@@ -1,8 +1,14 @@
+use ctr::Ctr64BE;
+use ctr::cipher::KeyIvInit;
+use hdk_secure::blowfish::Blowfish;
+
 pub use reader::BarReader;
+pub use stream::BarStreamWriter;
 pub use structs::{BarEntry, BarEntryMetadata, BarHeader};
 pub use writer::BarWriter;
 
 pub mod reader;
+pub mod stream;
 pub mod structs;
 pub mod writer;
 
@@ -24,3 +30,9 @@ pub const fn forge_iv(
         | (extended_timestamp & 0xFFFF);
     val.to_be_bytes()
 }
+
+/// Build the cipher for an encrypted entry's body, which uses the default key and IV + 3.
+pub(crate) fn body_cipher(default_key: &[u8; 32], iv: [u8; 8]) -> Ctr64BE<Blowfish> {
+    let iv_body = u64::from_be_bytes(iv).wrapping_add(3).to_be_bytes();
+    Ctr64BE::<Blowfish>::new(default_key.into(), &iv_body.into())
+}
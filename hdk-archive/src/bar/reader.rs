@@ -1,20 +1,33 @@
 use super::structs::{BarEntry, BarEntryMetadata, BarHeader};
 
-use crate::archive::ArchiveReader;
+use crate::archive::{ArchiveReader, HashIndex, RawEntry, ReadSeek};
+use crate::error::ArchiveError;
 use crate::structs::{ARCHIVE_MAGIC, ArchiveFlags, ArchiveVersion, CompressionType};
+use crate::utils::{Sha1Reader, Window, ZlibWrapReader, read_table, read_until_error};
 
 use binrw::BinReaderExt;
 use ctr::Ctr64BE;
 use ctr::cipher::{KeyIvInit, StreamCipher};
 use enumflags2::BitFlags;
-use hdk_comp::zlib::reader::SegmentedZlibReader;
+use flate2::read::{DeflateDecoder, ZlibDecoder};
+use hdk_comp::zlib::reader::{SeekableSegmentedZlibReader, SegmentedZlibReader};
 use hdk_secure::blowfish::Blowfish;
+use hdk_secure::hash::AfsHash;
+use hdk_secure::reader::{CryptoReader, SeekableCryptoReader};
 use std::io::{self, Cursor, Read, Seek, SeekFrom};
 
 pub struct BarReader<R: Read + Seek> {
     inner: R,
     header: BarHeader,
     entries: Vec<BarEntry>,
+
+    /// Entry indices by name hash, built when the archive is opened.
+    index: HashIndex,
+
+    /// Entries whose stored compression contradicted their sizes and was corrected
+    /// when opening, with the stored compression.
+    compression_fixups: Vec<(usize, CompressionType)>,
+
     toc_base: u64,
     flags: BitFlags<ArchiveFlags>,
 
@@ -27,6 +40,11 @@ pub struct BarReader<R: Read + Seek> {
     ///
     /// This is used in CTR mode with an IV derived from the entry metadata.
     signature_key: [u8; 32],
+
+    /// Whether encrypted entries are checked against their SHA-1 signature when read.
+    ///
+    /// Default is `false`.
+    verify: bool,
 }
 
 impl<R: Read + Seek> BarReader<R> {
@@ -37,7 +55,29 @@ impl<R: Read + Seek> BarReader<R> {
     /// * `reader` - The underlying reader to read the archive from.
     /// * `default_key` - The Blowfish key used for decrypting encrypted file bodies.
     /// * `signature_key` - The Blowfish key used for decrypting encrypted file headers.
-    pub fn open(mut reader: R, default_key: [u8; 32], signature_key: [u8; 32]) -> io::Result<Self> {
+    pub fn open(reader: R, default_key: [u8; 32], signature_key: [u8; 32]) -> io::Result<Self> {
+        Self::open_with(reader, default_key, signature_key, false)
+    }
+
+    /// Open a possibly truncated BAR archive, keeping the entries whose ToC record is
+    /// complete.
+    ///
+    /// Only the header must be intact. Entries may still point past the end of the
+    /// file; see [`crate::salvage`] to recover what they hold.
+    pub fn open_salvage(
+        reader: R,
+        default_key: [u8; 32],
+        signature_key: [u8; 32],
+    ) -> io::Result<Self> {
+        Self::open_with(reader, default_key, signature_key, true)
+    }
+
+    fn open_with(
+        mut reader: R,
+        default_key: [u8; 32],
+        signature_key: [u8; 32],
+        salvage: bool,
+    ) -> io::Result<Self> {
         let magic_val = reader
             .read_le::<u32>()
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
@@ -77,31 +117,39 @@ impl<R: Read + Seek> BarReader<R> {
             let compressed_size = reader
                 .read_le::<u32>()
                 .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-            let mut compressed_data = vec![0u8; compressed_size as usize];
-            reader.read_exact(&mut compressed_data)?;
-
-            // Decompress ZTOC
-            let mut d = flate2::Decompress::new(false);
-            let mut toc_data = Vec::with_capacity(entries_size as usize);
-            d.decompress_vec(
-                &compressed_data,
+
+            let compressed_data = read_table(&mut reader, u64::from(compressed_size), salvage)?;
+
+            // Decompress ZTOC, keeping whatever decompresses before the data runs out
+            // or goes bad when salvaging
+            let mut toc_data = Vec::new();
+            let decompressed = read_until_error(
+                &mut DeflateDecoder::new(&compressed_data[..]).take(entries_size),
                 &mut toc_data,
-                flate2::FlushDecompress::Finish,
-            )
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            );
+            if !salvage {
+                decompressed.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            }
 
             (toc_data, 24 + u64::from(compressed_size))
         } else {
-            let mut toc_data = vec![0u8; entries_size as usize];
-            reader.read_exact(&mut toc_data)?;
+            let toc_data = read_table(&mut reader, entries_size, salvage)?;
 
             (toc_data, 20 + entries_size)
         };
 
+        // A salvaged ToC may be cut short
+        let entries_count = if salvage {
+            entries_count.min(toc_data.len() / 16)
+        } else {
+            entries_count
+        };
+
         let mut cursor = Cursor::new(toc_data);
         let mut entries = Vec::with_capacity(entries_count);
+        let mut compression_fixups = Vec::new();
 
-        for _ in 0..entries_count {
+        for index in 0..entries_count {
             let mut entry: BarEntry = cursor
                 .read_le()
                 .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
@@ -110,28 +158,82 @@ impl<R: Read + Seek> BarReader<R> {
             let comp = entry.compression();
             if comp == CompressionType::None && entry.compressed_size < entry.uncompressed_size {
                 entry.offset_and_comp.1 = CompressionType::ZLib.into();
+                compression_fixups.push((index, comp));
             } else if comp == CompressionType::ZLib
                 && entry.compressed_size == entry.uncompressed_size
             {
                 entry.offset_and_comp.1 = CompressionType::None.into();
+                compression_fixups.push((index, comp));
             }
             entries.push(entry);
         }
 
+        let index = HashIndex::new(entries.iter().map(BarEntry::name_hash));
+
         Ok(Self {
             inner: reader,
             header,
             entries,
+            index,
+            compression_fixups,
             toc_base,
             flags,
             default_key,
             signature_key,
+            verify: false,
         })
     }
 
+    /// Enable or disable verified reads.
+    ///
+    /// When enabled, readers returned by [`ArchiveReader::entry_reader`] for encrypted
+    /// entries recompute the SHA-1 of the decompressed body and compare it with the
+    /// signature stored in the encrypted head. A mismatch is reported as an
+    /// [`io::ErrorKind::InvalidData`] error wrapping [`ArchiveError::SignatureMismatch`]
+    /// once the end of the entry is reached.
+    pub const fn with_verification(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Check an encrypted entry's SHA-1 signature against its decompressed content.
+    ///
+    /// A wrong `default_key` or `signature_key`, or a corrupted entry, will fail with
+    /// [`ArchiveError::SignatureMismatch`] (or an I/O error if the body cannot even
+    /// be decompressed).
+    ///
+    /// Entries that are not encrypted carry no signature and always pass.
+    pub fn verify_entry(&mut self, index: usize) -> Result<(), ArchiveError> {
+        let mut reader = self.open_entry(index, true)?;
+
+        io::copy(&mut reader, &mut io::sink())?;
+
+        Ok(())
+    }
+
     pub fn header(&self) -> BarHeader {
         self.header.clone()
     }
+
+    /// Where entry data starts; entry offsets are relative to it.
+    pub(crate) const fn data_start(&self) -> u64 {
+        self.toc_base
+    }
+
+    /// The compression bits of an entry's ToC record, after any correction.
+    pub(crate) fn raw_compression(&self, index: usize) -> u8 {
+        self.entries[index].offset_and_comp.1
+    }
+
+    /// Entries whose compression was corrected when opening, with the stored one.
+    pub(crate) fn compression_fixups(&self) -> &[(usize, CompressionType)] {
+        &self.compression_fixups
+    }
+
+    /// The size of the whole archive.
+    pub(crate) fn archive_len(&mut self) -> io::Result<u64> {
+        self.inner.seek(SeekFrom::End(0))
+    }
 }
 
 impl<R: Read + Seek> ArchiveReader for BarReader<R> {
@@ -141,6 +243,10 @@ impl<R: Read + Seek> ArchiveReader for BarReader<R> {
         self.entries.len()
     }
 
+    fn timestamp(&self) -> i32 {
+        self.header.timestamp
+    }
+
     fn entry_metadata(&self, index: usize) -> io::Result<BarEntryMetadata> {
         let entry = self
             .entries
@@ -149,98 +255,229 @@ impl<R: Read + Seek> ArchiveReader for BarReader<R> {
         Ok(entry.into())
     }
 
+    fn find_by_hash(&self, name_hash: AfsHash) -> Result<Option<usize>, ArchiveError> {
+        self.index.find(name_hash)
+    }
+
     fn entry_reader<'a>(&'a mut self, index: usize) -> io::Result<Box<dyn Read + 'a>> {
+        self.open_entry(index, self.verify)
+    }
+
+    /// Open an entry's stored payload, decrypted but not decompressed.
+    ///
+    /// Headerless `LeanZLib` entries are rewrapped into regular zlib streams (without
+    /// recompressing them), so ZLib payloads are always complete zlib streams.
+    fn raw_entry_reader<'a>(&'a mut self, index: usize) -> io::Result<RawEntry<'a>> {
+        let lean = self.flags.contains(ArchiveFlags::LeanZLib);
+        let mut raw = self.open_payload(index)?;
+
+        if lean && raw.compression == CompressionType::ZLib {
+            raw.reader = Box::new(ZlibWrapReader::new(raw.reader));
+        }
+
+        Ok(raw)
+    }
+
+    /// Open an entry for random access.
+    ///
+    /// Uncompressed entries are read through a window over the archive, and EdgeZLib
+    /// and encrypted entries through an index of their chunks, so only the chunk
+    /// containing the current position is decrypted and decompressed. ZLib entries
+    /// cannot be seeked into and are buffered in memory.
+    ///
+    /// Since only part of an entry may be read, encrypted entries are never verified
+    /// against their signature here, even if verification is enabled.
+    fn entry_seekable_reader<'a>(&'a mut self, index: usize) -> io::Result<Box<dyn ReadSeek + 'a>> {
         let entry = self
             .entries
             .get(index)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Index out of bounds"))?;
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Index out of bounds"))?
+            .clone();
 
         let offset = u64::from(entry.offset());
         let abs_offset = self.toc_base + offset;
+        let size = u64::from(entry.compressed_size);
 
-        self.inner.seek(SeekFrom::Start(abs_offset))?;
-        let mut raw_data = vec![0u8; entry.compressed_size as usize];
-        self.inner.read_exact(&mut raw_data)?;
-
-        let comp_type = entry.compression();
-        let use_zlib_header = !self.flags.contains(ArchiveFlags::LeanZLib);
-
-        match comp_type {
+        match entry.compression() {
             CompressionType::Encrypted => {
+                if size < 28 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Encrypted data too short",
+                    ));
+                }
+
                 let iv = super::forge_iv(
                     u64::from(self.header.file_count),
                     u64::from(entry.uncompressed_size),
-                    u64::from(entry.compressed_size),
-                    u64::from(entry.offset()),
+                    size,
+                    offset,
                     self.header.timestamp,
                 );
 
-                if raw_data.len() < 24 {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "Encrypted data too short",
-                    ));
-                }
+                // Skip the encrypted head and body fourcc
+                let body = Window::new(&mut self.inner, abs_offset + 28, size - 28)?;
+                let crypto =
+                    SeekableCryptoReader::new(body, super::body_cipher(&self.default_key, iv))?;
+                Ok(Box::new(SeekableSegmentedZlibReader::new(crypto)?))
+            }
+            CompressionType::EdgeZLib => {
+                let window = Window::new(&mut self.inner, abs_offset, size)?;
+                Ok(Box::new(SeekableSegmentedZlibReader::new(window)?))
+            }
+            CompressionType::ZLib => {
+                let mut data = Vec::with_capacity(entry.uncompressed_size as usize);
+                self.open_entry(index, false)?.read_to_end(&mut data)?;
+                Ok(Box::new(Cursor::new(data)))
+            }
+            CompressionType::None => Ok(Box::new(Window::new(&mut self.inner, abs_offset, size)?)),
+        }
+    }
+}
+
+// Private helpers for opening entry streams
+impl<R: Read + Seek> BarReader<R> {
+    /// Open an entry's stored payload, decrypting it if needed but not decompressing it.
+    ///
+    /// For encrypted entries, this also returns the SHA-1 signature stored in the
+    /// encrypted head, and the payload is the decrypted EdgeZLib body.
+    fn open_payload(&mut self, index: usize) -> io::Result<RawEntry<'_>> {
+        let entry = self
+            .entries
+            .get(index)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Index out of bounds"))?
+            .clone();
 
-                let (head, body_wrapper) = raw_data.split_at(24);
-                let mut head = head.to_vec();
-                let mut body_wrapper = body_wrapper.to_vec();
+        let offset = u64::from(entry.offset());
+        let abs_offset = self.toc_base + offset;
 
-                type BlowfishCtr = Ctr64BE<Blowfish>;
-                let mut bf = BlowfishCtr::new(&self.signature_key.into(), &iv.into());
-                bf.apply_keystream(&mut head);
+        self.inner.seek(SeekFrom::Start(abs_offset))?;
+        let mut raw_stream = (&mut self.inner).take(u64::from(entry.compressed_size));
+
+        // Encrypted: 24-byte head (fourcc + SHA-1) encrypted with the signature key,
+        // 4-byte raw body fourcc, then EdgeZLib body encrypted with the default key.
+        if entry.compression() != CompressionType::Encrypted {
+            return Ok(RawEntry {
+                name_hash: entry.name_hash(),
+                compression: entry.compression(),
+                uncompressed_size: entry.uncompressed_size,
+                iv: None,
+                checksum: None,
+                reader: Box::new(raw_stream),
+            });
+        }
 
-                let mut iv_val = u64::from_be_bytes(iv);
-                iv_val = iv_val.wrapping_add(3);
-                let iv_body = iv_val.to_be_bytes();
+        if entry.compressed_size < 28 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Encrypted data too short",
+            ));
+        }
+
+        let iv = super::forge_iv(
+            u64::from(self.header.file_count),
+            u64::from(entry.uncompressed_size),
+            u64::from(entry.compressed_size),
+            offset,
+            self.header.timestamp,
+        );
+
+        let mut head = [0u8; 24];
+        raw_stream.read_exact(&mut head)?;
+
+        type BlowfishCtr = Ctr64BE<Blowfish>;
+        let mut bf = BlowfishCtr::new(&self.signature_key.into(), &iv.into());
+        bf.apply_keystream(&mut head);
+
+        // Body fourcc (4 bytes) - kept raw
+        let mut body_fourcc = [0u8; 4];
+        raw_stream.read_exact(&mut body_fourcc)?;
+
+        let mut checksum = [0u8; 20];
+        checksum.copy_from_slice(&head[4..]);
+
+        let crypto = CryptoReader::new(raw_stream, super::body_cipher(&self.default_key, iv));
+        Ok(RawEntry {
+            name_hash: entry.name_hash(),
+            compression: entry.compression(),
+            uncompressed_size: entry.uncompressed_size,
+            iv: None,
+            checksum: Some(checksum),
+            reader: Box::new(crypto),
+        })
+    }
 
-                if body_wrapper.len() < 4 {
-                    return Err(io::Error::new(io::ErrorKind::InvalidData, "Body too short"));
+    pub(crate) fn open_entry(
+        &mut self,
+        index: usize,
+        verify: bool,
+    ) -> io::Result<Box<dyn Read + '_>> {
+        let lean = self.flags.contains(ArchiveFlags::LeanZLib);
+        let RawEntry {
+            name_hash,
+            compression,
+            checksum,
+            reader: payload,
+            ..
+        } = self.open_payload(index)?;
+
+        match compression {
+            CompressionType::Encrypted | CompressionType::EdgeZLib => {
+                let seg = SegmentedZlibReader::new(payload);
+
+                match checksum {
+                    Some(expected) if verify => {
+                        Ok(Box::new(VerifyingReader::new(seg, name_hash, expected)))
+                    }
+                    _ => Ok(Box::new(seg)),
                 }
+            }
+            // LeanZLib archives store ZLib entries without the zlib header
+            CompressionType::ZLib if lean => Ok(Box::new(DeflateDecoder::new(payload))),
+            CompressionType::ZLib => Ok(Box::new(ZlibDecoder::new(payload))),
+            CompressionType::None => Ok(payload),
+        }
+    }
+}
 
-                let (_fourcc, actual_body) = body_wrapper.split_at_mut(4);
-                let mut bf_body = BlowfishCtr::new(&self.default_key.into(), &iv_body.into());
-                bf_body.apply_keystream(actual_body);
+/// Checks the SHA-1 of an encrypted entry's decompressed body against its signature
+/// once the end of the stream is reached.
+struct VerifyingReader<R> {
+    inner: Sha1Reader<R>,
+    name_hash: AfsHash,
+    expected: [u8; 20],
+    verified: bool,
+}
 
-                let seg = SegmentedZlibReader::new(Cursor::new(actual_body.to_vec()));
-                Ok(Box::new(seg))
-            }
-            CompressionType::EdgeZLib => {
-                let seg = SegmentedZlibReader::new(Cursor::new(raw_data));
-                Ok(Box::new(seg))
-            }
-            _ => {
-                let data = decompress_data_helper(&raw_data, comp_type, use_zlib_header)?;
-                Ok(Box::new(Cursor::new(data)))
-            }
+impl<R: Read> VerifyingReader<R> {
+    fn new(inner: R, name_hash: AfsHash, expected: [u8; 20]) -> Self {
+        Self {
+            inner: Sha1Reader::new(inner),
+            name_hash,
+            expected,
+            verified: false,
         }
     }
 }
 
-fn decompress_data_helper(
-    data: &[u8],
-    ctype: CompressionType,
-    zlib_header: bool,
-) -> io::Result<Vec<u8>> {
-    match ctype {
-        CompressionType::None => Ok(data.to_vec()),
-        CompressionType::ZLib => {
-            if zlib_header {
-                let mut d = flate2::read::ZlibDecoder::new(data);
-                let mut out = Vec::new();
-                d.read_to_end(&mut out)?;
-                Ok(out)
-            } else {
-                let mut d = flate2::Decompress::new(zlib_header);
-                let mut out = Vec::new();
-                d.decompress_vec(data, &mut out, flate2::FlushDecompress::None)
-                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-                Ok(out)
+impl<R: Read> Read for VerifyingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+
+        if n == 0 && !buf.is_empty() && !self.verified {
+            let actual = self.inner.digest();
+            if actual != self.expected {
+                return Err(ArchiveError::SignatureMismatch {
+                    name_hash: self.name_hash,
+                    expected: self.expected,
+                    actual,
+                }
+                .into());
             }
+
+            self.verified = true;
         }
-        _ => Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Unsupported or Handled Elsewhere",
-        )),
+
+        Ok(n)
     }
 }
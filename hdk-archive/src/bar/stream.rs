@@ -0,0 +1,504 @@
+use enumflags2::BitFlags;
+use flate2::{
+    Compression,
+    write::{DeflateEncoder, ZlibEncoder},
+};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use hdk_comp::zlib::{reader::SegmentedZlibReader, writer::SegmentedZlibWriter};
+use hdk_secure::{hash::AfsHash, writer::CryptoWriter};
+
+use super::body_cipher;
+use super::structs::{BarEntryMetadata, BarHeader};
+use super::writer::{strip_zlib_wrapper, write_encrypted_head, write_header, write_toc};
+use crate::archive::{ArchiveWriter, RawEntry};
+use crate::structs::{ArchiveFlags, CompressionType};
+use crate::utils::{CountingWriter, Sha1Reader, padding, toc_u32};
+
+/// A BAR writer that streams entry data straight to the output.
+///
+/// Unlike [`super::BarWriter`], which keeps every compressed entry in memory until
+/// `finish()`, this writer reserves space for the header and ToC up front, writes
+/// each entry's payload as soon as it is added, then seeks back to patch the ToC.
+///
+/// Because the ToC size depends on the number of entries, that number must be
+/// known when the writer is created.
+///
+/// Encrypted entries are compressed into a temporary file first, since their IV
+/// depends on the compressed size and must be known before encrypting.
+pub struct BarStreamWriter<W: Write + Seek> {
+    /// The underlying writer.
+    inner: W,
+
+    /// Where the archive starts in the underlying writer.
+    start: u64,
+
+    /// The number of entries reserved in the ToC.
+    file_count: u32,
+
+    /// The archive flags to write in the header.
+    ///
+    /// Default is no flags.
+    flags: BitFlags<ArchiveFlags>,
+
+    /// Priority field in the header.
+    ///
+    /// Default is `0`.
+    priority: i32,
+
+    /// Timestamp field in the header.
+    ///
+    /// Default is `0`.
+    timestamp: i32,
+
+    /// The timestamp the IVs of already-written encrypted entries were forged with.
+    ///
+    /// The header timestamp cannot change after that, or those entries would no
+    /// longer decrypt.
+    iv_timestamp: Option<i32>,
+
+    /// Whether already-written ZLib entries were written headerless (`LeanZLib`).
+    ///
+    /// The flag cannot change after that, or those entries would no longer decompress.
+    zlib_lean: Option<bool>,
+
+    /// ToC entries written so far.
+    entries: Vec<BarEntryMetadata>,
+
+    /// Offset of the next entry, relative to the end of the ToC.
+    next_offset: u64,
+
+    /// The default Blowfish key used for encrypting file bodies.
+    default_key: [u8; 32],
+
+    /// The signature Blowfish key used for encrypting file headers.
+    signature_key: [u8; 32],
+}
+
+impl<W: Write + Seek> BarStreamWriter<W> {
+    /// Create a new streaming BAR archive writer.
+    ///
+    /// This immediately reserves space for the header and a ToC of `file_count` entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The underlying writer to write the archive to.
+    /// * `default_key` - The Blowfish key used for encrypting file bodies.
+    /// * `signature_key` - The Blowfish key used for encrypting file headers.
+    /// * `file_count` - The exact number of entries that will be added.
+    pub fn new(
+        mut inner: W,
+        default_key: [u8; 32],
+        signature_key: [u8; 32],
+        file_count: u32,
+    ) -> io::Result<Self> {
+        let start = inner.stream_position()?;
+
+        // Reserve header + ToC, patched in `finish()`
+        let reserved = 20 + u64::from(file_count) * 16;
+        io::copy(&mut io::repeat(0).take(reserved), &mut inner)?;
+
+        Ok(Self {
+            inner,
+            start,
+            file_count,
+            flags: BitFlags::empty(),
+            priority: 0,
+            timestamp: 0,
+            iv_timestamp: None,
+            zlib_lean: None,
+            entries: Vec::with_capacity(file_count as usize),
+            next_offset: 0,
+            default_key,
+            signature_key,
+        })
+    }
+
+    /// Create a new streaming BAR archive writer that keeps the flags, priority and
+    /// timestamp of an existing archive's header.
+    pub fn from_header(
+        inner: W,
+        header: &BarHeader,
+        default_key: [u8; 32],
+        signature_key: [u8; 32],
+    ) -> io::Result<Self> {
+        Ok(
+            Self::new(inner, default_key, signature_key, header.file_count)?
+                .with_flags(header.flags())?
+                .with_priority(header.priority)
+                .with_timestamp(header.timestamp),
+        )
+    }
+
+    /// Set the archive flags to write in the header.
+    ///
+    /// `ZTOC` is not supported, since the compressed ToC size cannot be reserved in
+    /// advance, so this fails if it is set.
+    ///
+    /// `LeanZLib` affects how ZLib entries are written, so it must be set before adding any.
+    pub fn with_flags(mut self, flags: BitFlags<ArchiveFlags>) -> io::Result<Self> {
+        check_flags(flags)?;
+        self.flags = flags;
+        Ok(self)
+    }
+
+    /// Set the priority written in the header.
+    pub const fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Set the timestamp written in the header and used to forge encrypted entries' IVs.
+    ///
+    /// Since encrypted entries are written as soon as they are added, this must be set
+    /// before adding any of them.
+    pub const fn with_timestamp(mut self, timestamp: i32) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn add_entry(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let mut cursor = io::Cursor::new(data);
+        self.add_entry_from_reader(name_hash, compression, &mut cursor)
+    }
+
+    /// Read data from `reader`, compress/encrypt as needed and write it to the output.
+    ///
+    /// If this fails, the writer is left as it was, and more entries can be added.
+    pub fn add_entry_from_reader<R: Read + ?Sized>(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        reader: &mut R,
+    ) -> io::Result<()> {
+        self.rollback_on_error(|this| this.write_entry(name_hash, compression, reader))
+    }
+
+    /// Add an entry from a payload read with [`ArchiveReader::raw_entry_reader`], without
+    /// recompressing it, and write it to the output.
+    ///
+    /// See [`super::BarWriter::add_raw_entry`]. If this fails, the writer is left as it
+    /// was, and more entries can be added.
+    ///
+    /// [`ArchiveReader::raw_entry_reader`]: crate::archive::ArchiveReader::raw_entry_reader
+    pub fn add_raw_entry(&mut self, entry: RawEntry<'_>) -> io::Result<()> {
+        self.rollback_on_error(|this| this.write_raw_entry(entry))
+    }
+
+    /// Add an entry with `write`, undoing what it wrote if it fails, so that the next
+    /// entry is written where the ToC expects it.
+    fn rollback_on_error(
+        &mut self,
+        write: impl FnOnce(&mut Self) -> io::Result<()>,
+    ) -> io::Result<()> {
+        self.check_capacity()?;
+
+        let (iv_timestamp, zlib_lean) = (self.iv_timestamp, self.zlib_lean);
+        let result = write(self);
+
+        if result.is_err() {
+            self.iv_timestamp = iv_timestamp;
+            self.zlib_lean = zlib_lean;
+
+            // Whatever the failed entry wrote is overwritten by the next one
+            let data_start = self.start + 20 + u64::from(self.file_count) * 16;
+            self.inner
+                .seek(SeekFrom::Start(data_start + self.next_offset))?;
+        }
+
+        result
+    }
+
+    fn write_entry<R: Read + ?Sized>(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        reader: &mut R,
+    ) -> io::Result<()> {
+        let offset = self.next_offset;
+
+        if compression == CompressionType::ZLib {
+            self.zlib_lean = Some(self.flags.contains(ArchiveFlags::LeanZLib));
+        }
+
+        let (uncompressed_size, compressed_size) = match compression {
+            CompressionType::Encrypted => self.write_encrypted(offset, reader)?,
+            _ => {
+                let mut out = CountingWriter::new(&mut self.inner);
+                let uncompressed_size = match compression {
+                    CompressionType::None => io::copy(reader, &mut out)?,
+                    // LeanZLib archives store ZLib entries without the zlib header
+                    CompressionType::ZLib if self.flags.contains(ArchiveFlags::LeanZLib) => {
+                        let mut enc = DeflateEncoder::new(&mut out, Compression::best());
+                        let n = io::copy(reader, &mut enc)?;
+                        enc.finish()?;
+                        n
+                    }
+                    CompressionType::ZLib => {
+                        let mut enc = ZlibEncoder::new(&mut out, Compression::best());
+                        let n = io::copy(reader, &mut enc)?;
+                        enc.finish()?;
+                        n
+                    }
+                    _ => {
+                        let mut seg = SegmentedZlibWriter::new(&mut out);
+                        let n = io::copy(reader, &mut seg)?;
+                        seg.finish()?;
+                        n
+                    }
+                };
+
+                (uncompressed_size, out.count())
+            }
+        };
+
+        self.push_entry(name_hash, compression, uncompressed_size, compressed_size)
+    }
+
+    fn write_raw_entry(&mut self, mut entry: RawEntry<'_>) -> io::Result<()> {
+        let offset = self.next_offset;
+        let uncompressed_size = u64::from(entry.uncompressed_size);
+
+        let compressed_size = match entry.compression {
+            CompressionType::Encrypted => {
+                let mut spill = tempfile::tempfile()?;
+                io::copy(&mut entry.reader, &mut spill)?;
+
+                let checksum = match entry.checksum {
+                    Some(checksum) => checksum,
+                    None => {
+                        spill.rewind()?;
+                        let mut hasher = Sha1Reader::new(SegmentedZlibReader::new(&mut spill));
+                        io::copy(&mut hasher, &mut io::sink())?;
+                        let checksum = hasher.digest();
+
+                        spill.seek(SeekFrom::End(0))?;
+                        checksum
+                    }
+                };
+
+                self.write_encrypted_body(offset, uncompressed_size, &checksum, spill)?
+            }
+            // LeanZLib archives store ZLib entries without the zlib header
+            CompressionType::ZLib if self.flags.contains(ArchiveFlags::LeanZLib) => {
+                self.zlib_lean = Some(true);
+
+                let mut data = Vec::new();
+                entry.reader.read_to_end(&mut data)?;
+                let deflate = strip_zlib_wrapper(&data)?;
+                self.inner.write_all(deflate)?;
+                deflate.len() as u64
+            }
+            compression => {
+                if compression == CompressionType::ZLib {
+                    self.zlib_lean = Some(false);
+                }
+
+                io::copy(&mut entry.reader, &mut self.inner)?
+            }
+        };
+
+        self.push_entry(
+            entry.name_hash,
+            entry.compression,
+            uncompressed_size,
+            compressed_size,
+        )
+    }
+
+    fn check_capacity(&self) -> io::Result<()> {
+        if self.entries.len() >= self.file_count as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "More entries added than reserved in the ToC",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Pad the entry just written and record it in the ToC.
+    fn push_entry(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        uncompressed_size: u64,
+        compressed_size: u64,
+    ) -> io::Result<()> {
+        let offset = self.next_offset;
+        let metadata = BarEntryMetadata {
+            name_hash,
+            offset: toc_u32(offset)?,
+            compression,
+            uncompressed_size: toc_u32(uncompressed_size)?,
+            compressed_size: toc_u32(compressed_size)?,
+        };
+
+        // Pad to 4 bytes if needed
+        let pad_len = padding(compressed_size);
+        io::copy(&mut io::repeat(0).take(pad_len), &mut self.inner)?;
+
+        self.next_offset = offset + compressed_size + pad_len;
+        self.entries.push(metadata);
+
+        Ok(())
+    }
+
+    /// Compress an entry into a temporary file, then encrypt it into the output.
+    ///
+    /// Returns the uncompressed and on-disk sizes.
+    fn write_encrypted<R: Read + ?Sized>(
+        &mut self,
+        offset: u64,
+        reader: &mut R,
+    ) -> io::Result<(u64, u64)> {
+        let mut hasher = Sha1Reader::new(reader);
+        let mut seg = SegmentedZlibWriter::new(tempfile::tempfile()?);
+        let uncompressed_size = io::copy(&mut hasher, &mut seg)?;
+        let spill = seg.finish()?;
+
+        let compressed_size =
+            self.write_encrypted_body(offset, uncompressed_size, &hasher.digest(), spill)?;
+
+        Ok((uncompressed_size, compressed_size))
+    }
+
+    /// Encrypt an EdgeZLib body spilled to a temporary file (positioned at its end)
+    /// into the output, preceded by its encrypted head.
+    ///
+    /// Returns the on-disk size.
+    fn write_encrypted_body(
+        &mut self,
+        offset: u64,
+        uncompressed_size: u64,
+        checksum: &[u8; 20],
+        mut spill: File,
+    ) -> io::Result<u64> {
+        // 24-byte encrypted head + 4 bytes body-fourcc in addition to the compressed body
+        let compressed_size = spill.stream_position()? + 28;
+        spill.rewind()?;
+
+        let iv = super::forge_iv(
+            u64::from(self.file_count),
+            uncompressed_size,
+            compressed_size,
+            offset,
+            self.timestamp,
+        );
+        self.iv_timestamp = Some(self.timestamp);
+
+        write_encrypted_head(&mut self.inner, &self.signature_key, iv, checksum)?;
+
+        let mut cw_body = CryptoWriter::new(&mut self.inner, body_cipher(&self.default_key, iv));
+        io::copy(&mut spill, &mut cw_body)?;
+
+        Ok(compressed_size)
+    }
+
+    /// Patch the header and ToC, and return the underlying writer positioned at the end
+    /// of the archive.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.entries.len() != self.file_count as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Expected {} entries, but {} were added",
+                    self.file_count,
+                    self.entries.len()
+                ),
+            ));
+        }
+
+        if self
+            .iv_timestamp
+            .is_some_and(|timestamp| timestamp != self.timestamp)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The timestamp changed after encrypted entries were written",
+            ));
+        }
+
+        if self
+            .zlib_lean
+            .is_some_and(|lean| lean != self.flags.contains(ArchiveFlags::LeanZLib))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The LeanZLib flag changed after ZLib entries were written",
+            ));
+        }
+
+        let end = self.inner.stream_position()?;
+        self.inner.seek(SeekFrom::Start(self.start))?;
+
+        write_header(
+            &mut self.inner,
+            self.flags,
+            self.priority,
+            self.timestamp,
+            self.file_count,
+        )?;
+        write_toc(&mut self.inner, self.entries.iter().copied())?;
+
+        self.inner.seek(SeekFrom::Start(end))?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write + Seek> ArchiveWriter for BarStreamWriter<W> {
+    type Output = W;
+
+    fn add_entry_from_reader<R: Read + ?Sized>(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        reader: &mut R,
+    ) -> io::Result<()> {
+        Self::add_entry_from_reader(self, name_hash, compression, reader)
+    }
+
+    fn add_raw_entry(&mut self, entry: RawEntry<'_>) -> io::Result<()> {
+        Self::add_raw_entry(self, entry)
+    }
+
+    fn flags(&self) -> BitFlags<ArchiveFlags> {
+        self.flags
+    }
+
+    fn set_flags(&mut self, flags: BitFlags<ArchiveFlags>) -> io::Result<()> {
+        check_flags(flags)?;
+        self.flags = flags;
+        Ok(())
+    }
+
+    fn set_priority(&mut self, priority: i32) {
+        self.priority = priority;
+    }
+
+    fn set_timestamp(&mut self, timestamp: i32) {
+        self.timestamp = timestamp;
+    }
+
+    fn finish(self) -> io::Result<W> {
+        Self::finish(self)
+    }
+}
+
+/// Reject flags the streaming writer cannot lay out.
+fn check_flags(flags: BitFlags<ArchiveFlags>) -> io::Result<()> {
+    if flags.contains(ArchiveFlags::ZTOC) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "ZTOC is not supported by the streaming BAR writer",
+        ));
+    }
+
+    Ok(())
+}
@@ -1,5 +1,7 @@
-use crate::structs::CompressionType;
+use crate::archive::EntryMetadata;
+use crate::structs::{ArchiveFlags, CompressionType};
 use binrw::prelude::*;
+use enumflags2::BitFlags;
 use hdk_secure::hash::AfsHash;
 
 #[derive(BinRead, Debug, Clone)]
@@ -9,10 +11,23 @@ pub struct BarHeader {
     pub version_and_flags: (u16, u16),
 
     pub priority: i32,
+
+    /// Kept in the `.time` file of extracted archives, see [`crate::time`].
     pub timestamp: i32,
+
     pub file_count: u32,
 }
 
+impl BarHeader {
+    pub const fn version(&self) -> u16 {
+        self.version_and_flags.0
+    }
+
+    pub fn flags(&self) -> BitFlags<ArchiveFlags> {
+        BitFlags::from_bits_truncate(self.version_and_flags.1)
+    }
+}
+
 #[derive(BinRead, Debug, Clone)]
 #[br(little)]
 pub struct BarEntry {
@@ -60,3 +75,21 @@ impl From<&BarEntry> for BarEntryMetadata {
         }
     }
 }
+
+impl EntryMetadata for BarEntryMetadata {
+    fn name_hash(&self) -> AfsHash {
+        self.name_hash
+    }
+
+    fn compression(&self) -> CompressionType {
+        self.compression
+    }
+
+    fn uncompressed_size(&self) -> u32 {
+        self.uncompressed_size
+    }
+
+    fn compressed_size(&self) -> u32 {
+        self.compressed_size
+    }
+}
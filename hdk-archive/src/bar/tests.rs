@@ -3,12 +3,7 @@ use sha1::Digest;
 use std::io::{Cursor, Read};
 
 use crate::archive::ArchiveReader;
-
-/// Test default Blowfish key (32 bytes) for encrypted file bodies.
-const TEST_DEFAULT_KEY: [u8; 32] = [0xAA; 32];
-
-/// Test signature Blowfish key (32 bytes) for encrypted file headers.
-const TEST_SIGNATURE_KEY: [u8; 32] = [0xBB; 32];
+use crate::test_utils::{TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY};
 
 // Helper to write a mock BAR file
 fn create_mock_bar() -> Vec<u8> {
@@ -237,3 +232,775 @@ fn test_encrypted_entry_write_and_read() {
 
     assert_eq!(got, content);
 }
+
+#[test]
+fn test_stream_writer_matches_in_memory_writer() {
+    use crate::bar::{BarStreamWriter, BarWriter};
+    use crate::structs::CompressionType;
+    use hdk_secure::hash::AfsHash;
+
+    let big: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    let files: [(&str, CompressionType, &[u8]); 4] = [
+        ("plain.txt", CompressionType::None, b"abc"),
+        ("zlib.xml", CompressionType::ZLib, b"<xml>zlib</xml>"),
+        ("edge.dds", CompressionType::EdgeZLib, &big),
+        ("secret.lua", CompressionType::Encrypted, b"print('hi')"),
+    ];
+
+    let mut memory = BarWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    );
+    let mut stream = BarStreamWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+        files.len() as u32,
+    )
+    .unwrap();
+
+    for (name, compression, data) in files {
+        let hash = AfsHash::new_from_str(name);
+        memory.add_entry(hash, compression, data).unwrap();
+        stream.add_entry(hash, compression, data).unwrap();
+    }
+
+    let memory = memory.finish().unwrap().into_inner();
+    let stream = stream.finish().unwrap().into_inner();
+    assert_eq!(memory, stream);
+
+    let mut archive = crate::bar::reader::BarReader::open(
+        Cursor::new(stream),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    )
+    .unwrap();
+    for (i, (_, _, data)) in files.iter().enumerate() {
+        let mut out = Vec::new();
+        archive
+            .entry_reader(i)
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(&out, data);
+    }
+}
+
+#[test]
+fn test_stream_writer_enforces_entry_count() {
+    use crate::bar::BarStreamWriter;
+    use crate::structs::CompressionType;
+    use hdk_secure::hash::AfsHash;
+
+    let mut writer = BarStreamWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+        1,
+    )
+    .unwrap();
+    writer
+        .add_entry(AfsHash::new_from_str("a"), CompressionType::None, b"a")
+        .unwrap();
+    assert!(
+        writer
+            .add_entry(AfsHash::new_from_str("b"), CompressionType::None, b"b")
+            .is_err()
+    );
+
+    let writer = BarStreamWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+        2,
+    )
+    .unwrap();
+    assert!(writer.finish().is_err());
+}
+
+/// A reader that fails once `data` is read.
+struct FailingReader<'a> {
+    data: &'a [u8],
+}
+
+impl Read for FailingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.data.is_empty() {
+            return Err(std::io::Error::other("source failed"));
+        }
+
+        self.data.read(buf)
+    }
+}
+
+#[test]
+fn test_stream_writer_recovers_from_failed_entry() {
+    use crate::bar::{BarReader, BarStreamWriter};
+    use crate::structs::CompressionType;
+    use hdk_secure::hash::AfsHash;
+
+    for compression in [
+        CompressionType::None,
+        CompressionType::ZLib,
+        CompressionType::EdgeZLib,
+        CompressionType::Encrypted,
+    ] {
+        let mut writer = BarStreamWriter::new(
+            Cursor::new(Vec::new()),
+            TEST_DEFAULT_KEY,
+            TEST_SIGNATURE_KEY,
+            2,
+        )
+        .unwrap();
+        writer
+            .add_entry(AfsHash::new_from_str("a"), compression, b"first")
+            .unwrap();
+
+        let mut broken = FailingReader {
+            data: &[0x55; 1000],
+        };
+        assert!(
+            writer
+                .add_entry_from_reader(AfsHash::new_from_str("b"), compression, &mut broken)
+                .is_err()
+        );
+
+        writer
+            .add_entry(AfsHash::new_from_str("c"), compression, b"second")
+            .unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        let mut archive =
+            BarReader::open(Cursor::new(data), TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY).unwrap();
+        assert_eq!(archive.entry_count(), 2);
+
+        for (index, expected) in [&b"first"[..], b"second"].into_iter().enumerate() {
+            let mut content = Vec::new();
+            archive
+                .entry_reader(index)
+                .unwrap()
+                .read_to_end(&mut content)
+                .unwrap();
+            assert_eq!(content, expected, "{compression:?}");
+        }
+    }
+}
+
+#[test]
+fn test_stream_writer_rejects_ztoc_up_front() {
+    use crate::archive::ArchiveWriter;
+    use crate::bar::BarStreamWriter;
+    use crate::structs::ArchiveFlags;
+
+    let new_writer = || {
+        BarStreamWriter::new(
+            Cursor::new(Vec::new()),
+            TEST_DEFAULT_KEY,
+            TEST_SIGNATURE_KEY,
+            1,
+        )
+        .unwrap()
+    };
+
+    let err = new_writer()
+        .with_flags(ArchiveFlags::ZTOC.into())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+
+    let mut writer = new_writer();
+    let err = writer.set_flags(ArchiveFlags::ZTOC.into()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+    writer.set_flags(ArchiveFlags::Protected.into()).unwrap();
+}
+
+#[test]
+fn test_repack_preserves_header() {
+    use crate::bar::{BarReader, BarWriter};
+    use crate::structs::{ArchiveFlags, CompressionType};
+    use hdk_secure::hash::AfsHash;
+
+    let content = b"Timestamped encrypted content";
+    let mut writer = BarWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    )
+    .with_flags(ArchiveFlags::Protected.into())
+    .with_priority(7)
+    .with_timestamp(0x5F3A_1C2B);
+    writer
+        .add_entry(
+            AfsHash::new_from_str("secret.xml"),
+            CompressionType::Encrypted,
+            content,
+        )
+        .unwrap();
+    let original = writer.finish().unwrap().into_inner();
+
+    let mut archive = BarReader::open(
+        Cursor::new(original.clone()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    )
+    .unwrap();
+    let header = archive.header();
+    assert_eq!(header.priority, 7);
+    assert_eq!(header.timestamp, 0x5F3A_1C2B);
+
+    let mut got = Vec::new();
+    archive
+        .entry_reader(0)
+        .unwrap()
+        .read_to_end(&mut got)
+        .unwrap();
+    assert_eq!(got, content);
+
+    // Unpack -> repack keeps the original header values
+    let mut repacked = BarWriter::from_header(
+        Cursor::new(Vec::new()),
+        &header,
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    );
+    repacked
+        .add_entry(
+            AfsHash::new_from_str("secret.xml"),
+            CompressionType::Encrypted,
+            &got,
+        )
+        .unwrap();
+    assert_eq!(repacked.finish().unwrap().into_inner(), original);
+}
+
+#[test]
+fn test_stream_writer_rejects_late_timestamp_change() {
+    use crate::archive::ArchiveWriter;
+    use crate::bar::BarStreamWriter;
+    use crate::structs::CompressionType;
+    use hdk_secure::hash::AfsHash;
+
+    let mut writer = BarStreamWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+        1,
+    )
+    .unwrap()
+    .with_timestamp(1234);
+    writer
+        .add_entry(AfsHash::new_from_str("a"), CompressionType::Encrypted, b"a")
+        .unwrap();
+    writer.set_timestamp(5678);
+
+    assert!(writer.finish().is_err());
+}
+
+fn write_flagged_bar(flags: enumflags2::BitFlags<crate::structs::ArchiveFlags>) -> Vec<u8> {
+    use crate::bar::BarWriter;
+    use crate::structs::CompressionType;
+    use hdk_secure::hash::AfsHash;
+
+    let mut writer = BarWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    )
+    .with_flags(flags)
+    .with_timestamp(42);
+    for i in 0..8u8 {
+        let compression = CompressionType::try_from(i % 4).unwrap();
+        let content = vec![i; 100 + usize::from(i)];
+        writer
+            .add_entry(
+                AfsHash::new_from_str(&format!("file{i}.bin")),
+                compression,
+                &content,
+            )
+            .unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+fn assert_flagged_bar_roundtrips(data: Vec<u8>) {
+    let mut archive =
+        crate::bar::BarReader::open(Cursor::new(data), TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY)
+            .unwrap();
+    assert_eq!(archive.entry_count(), 8);
+
+    for i in 0..8u8 {
+        let mut out = Vec::new();
+        archive
+            .entry_reader(usize::from(i))
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, vec![i; 100 + usize::from(i)]);
+    }
+}
+
+#[test]
+fn test_ztoc_roundtrip() {
+    use crate::structs::ArchiveFlags;
+
+    let data = write_flagged_bar(ArchiveFlags::ZTOC.into());
+
+    // The ToC size prefix follows the 20-byte header and is smaller than a plain ToC
+    let toc_size = u32::from_le_bytes(data[20..24].try_into().unwrap());
+    assert!(toc_size < 8 * 16);
+
+    assert_flagged_bar_roundtrips(data);
+}
+
+#[test]
+fn test_lean_zlib_roundtrip() {
+    use crate::structs::{ArchiveFlags, CompressionType};
+
+    let data = write_flagged_bar(ArchiveFlags::LeanZLib.into());
+
+    let archive = crate::bar::BarReader::open(
+        Cursor::new(data.clone()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    )
+    .unwrap();
+    let zlib = archive
+        .entries()
+        .map(Result::unwrap)
+        .find(|m| m.compression == CompressionType::ZLib)
+        .unwrap();
+
+    // Raw deflate: no `0x78` zlib header at the start of the entry
+    let start = 20 + 8 * 16 + zlib.offset as usize;
+    assert_ne!(data[start], 0x78);
+
+    assert_flagged_bar_roundtrips(data);
+    assert_flagged_bar_roundtrips(write_flagged_bar(
+        ArchiveFlags::ZTOC | ArchiveFlags::LeanZLib,
+    ));
+}
+
+#[test]
+fn test_stream_writer_lean_zlib_roundtrip() {
+    use crate::bar::BarStreamWriter;
+    use crate::structs::{ArchiveFlags, CompressionType};
+    use hdk_secure::hash::AfsHash;
+
+    let mut writer = BarStreamWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+        1,
+    )
+    .unwrap()
+    .with_flags(ArchiveFlags::LeanZLib.into())
+    .unwrap();
+    writer
+        .add_entry(
+            AfsHash::new_from_str("lean.xml"),
+            CompressionType::ZLib,
+            &[b'x'; 300],
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut archive =
+        crate::bar::BarReader::open(Cursor::new(data), TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY)
+            .unwrap();
+    let mut out = Vec::new();
+    archive
+        .entry_reader(0)
+        .unwrap()
+        .read_to_end(&mut out)
+        .unwrap();
+    assert_eq!(out, [b'x'; 300]);
+}
+
+#[test]
+fn test_verify_encrypted_entry() {
+    use crate::bar::{BarReader, BarWriter};
+    use crate::error::ArchiveError;
+    use crate::structs::CompressionType;
+    use hdk_secure::hash::AfsHash;
+
+    let mut writer = BarWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    );
+    writer
+        .add_entry(
+            AfsHash::new_from_str("plain.txt"),
+            CompressionType::ZLib,
+            b"Not signed",
+        )
+        .unwrap();
+    writer
+        .add_entry(
+            AfsHash::new_from_str("signed.xml"),
+            CompressionType::Encrypted,
+            b"Signed content",
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut archive = BarReader::open(
+        Cursor::new(data.clone()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    )
+    .unwrap();
+    archive.verify_entry(0).unwrap();
+    archive.verify_entry(1).unwrap();
+
+    // A wrong signature key decrypts the head to garbage, so the checksum no longer matches
+    let mut archive =
+        BarReader::open(Cursor::new(data.clone()), TEST_DEFAULT_KEY, [0x11; 32]).unwrap();
+    let err = archive.verify_entry(1).unwrap_err();
+    assert!(matches!(
+        err,
+        ArchiveError::SignatureMismatch { name_hash, .. } if name_hash == AfsHash::new_from_str("signed.xml")
+    ));
+
+    // Verified reads surface the mismatch through `Read`
+    let mut archive = BarReader::open(Cursor::new(data), TEST_DEFAULT_KEY, [0x11; 32])
+        .unwrap()
+        .with_verification(true);
+    let mut out = Vec::new();
+    let err = archive
+        .entry_reader(1)
+        .unwrap()
+        .read_to_end(&mut out)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(matches!(
+        ArchiveError::from(err),
+        ArchiveError::SignatureMismatch { .. }
+    ));
+}
+
+/// Wraps a reader and counts how many bytes were pulled from it.
+struct TrackingReader<R> {
+    inner: R,
+    pulled: std::rc::Rc<std::cell::Cell<u64>>,
+}
+
+impl<R: Read> Read for TrackingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.pulled.set(self.pulled.get() + n as u64);
+        Ok(n)
+    }
+}
+
+impl<R: std::io::Seek> std::io::Seek for TrackingReader<R> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[test]
+fn test_entry_readers_stream() {
+    use crate::bar::{BarReader, BarWriter};
+    use crate::structs::CompressionType;
+    use hdk_secure::hash::AfsHash;
+
+    // Pseudo-random data so that compressed entries stay large
+    let mut state = 0x1234_5678u32;
+    let content: Vec<u8> = (0..1_000_000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+
+    let compressions = [
+        CompressionType::None,
+        CompressionType::ZLib,
+        CompressionType::EdgeZLib,
+        CompressionType::Encrypted,
+    ];
+
+    let mut writer = BarWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    );
+    for (i, compression) in compressions.iter().enumerate() {
+        writer
+            .add_entry(
+                AfsHash::new_from_str(&format!("big{i}.bin")),
+                *compression,
+                &content,
+            )
+            .unwrap();
+    }
+    let data = writer.finish().unwrap().into_inner();
+
+    let pulled = std::rc::Rc::new(std::cell::Cell::new(0));
+    let tracked = TrackingReader {
+        inner: Cursor::new(data),
+        pulled: pulled.clone(),
+    };
+    let mut archive = BarReader::open(tracked, TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY).unwrap();
+
+    for i in 0..compressions.len() {
+        let compressed_size = u64::from(archive.entry_metadata(i).unwrap().compressed_size);
+
+        pulled.set(0);
+        let mut head = [0u8; 16];
+        archive
+            .entry_reader(i)
+            .unwrap()
+            .read_exact(&mut head)
+            .unwrap();
+        assert_eq!(head, content[..16]);
+        assert!(pulled.get() < compressed_size / 10);
+
+        let mut out = Vec::new();
+        archive
+            .entry_reader(i)
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, content);
+    }
+}
+
+#[test]
+fn test_entry_seekable_readers() {
+    use crate::bar::{BarReader, BarWriter};
+    use crate::structs::CompressionType;
+    use hdk_secure::hash::AfsHash;
+    use std::io::{Seek, SeekFrom};
+
+    let mut state = 0x9E37_79B9u32;
+    let content: Vec<u8> = (0..1_000_000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+
+    let compressions = [
+        CompressionType::None,
+        CompressionType::ZLib,
+        CompressionType::EdgeZLib,
+        CompressionType::Encrypted,
+    ];
+
+    let mut writer = BarWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    );
+    for (i, compression) in compressions.iter().enumerate() {
+        writer
+            .add_entry(
+                AfsHash::new_from_str(&format!("big{i}.bin")),
+                *compression,
+                &content,
+            )
+            .unwrap();
+    }
+    let data = writer.finish().unwrap().into_inner();
+
+    let pulled = std::rc::Rc::new(std::cell::Cell::new(0));
+    let tracked = TrackingReader {
+        inner: Cursor::new(data),
+        pulled: pulled.clone(),
+    };
+    let mut archive = BarReader::open(tracked, TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY).unwrap();
+
+    for (i, compression) in compressions.iter().enumerate() {
+        let compressed_size = u64::from(archive.entry_metadata(i).unwrap().compressed_size);
+
+        pulled.set(0);
+        let mut reader = archive.entry_seekable_reader(i).unwrap();
+
+        let mut middle = [0u8; 64];
+        reader.seek(SeekFrom::Start(700_000)).unwrap();
+        reader.read_exact(&mut middle).unwrap();
+        assert_eq!(middle, content[700_000..700_064]);
+
+        let mut head = [0u8; 16];
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        reader.read_exact(&mut head).unwrap();
+        assert_eq!(head, content[..16]);
+
+        let mut tail = Vec::new();
+        assert_eq!(
+            reader.seek(SeekFrom::End(-100)).unwrap(),
+            content.len() as u64 - 100
+        );
+        reader.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, content[content.len() - 100..]);
+
+        drop(reader);
+
+        // Everything but ZLib is read without going through the whole entry
+        if *compression != CompressionType::ZLib {
+            assert!(pulled.get() < compressed_size / 4);
+        }
+    }
+}
+
+#[test]
+fn test_raw_repack_is_byte_identical() {
+    use crate::bar::{BarReader, BarStreamWriter, BarWriter};
+    use crate::structs::ArchiveFlags;
+
+    for flags in [
+        enumflags2::BitFlags::empty(),
+        ArchiveFlags::LeanZLib.into(),
+        ArchiveFlags::LeanZLib | ArchiveFlags::ZTOC,
+    ] {
+        let data = write_flagged_bar(flags);
+        let mut archive = BarReader::open(
+            Cursor::new(data.clone()),
+            TEST_DEFAULT_KEY,
+            TEST_SIGNATURE_KEY,
+        )
+        .unwrap();
+        let header = archive.header();
+
+        let mut writer = BarWriter::from_header(
+            Cursor::new(Vec::new()),
+            &header,
+            TEST_DEFAULT_KEY,
+            TEST_SIGNATURE_KEY,
+        );
+        for i in archive.entry_indices() {
+            writer
+                .add_raw_entry(archive.raw_entry_reader(i).unwrap())
+                .unwrap();
+        }
+        assert_eq!(writer.finish().unwrap().into_inner(), data);
+
+        if flags.contains(ArchiveFlags::ZTOC) {
+            continue;
+        }
+
+        let mut stream = BarStreamWriter::from_header(
+            Cursor::new(Vec::new()),
+            &header,
+            TEST_DEFAULT_KEY,
+            TEST_SIGNATURE_KEY,
+        )
+        .unwrap();
+        for i in archive.entry_indices() {
+            stream
+                .add_raw_entry(archive.raw_entry_reader(i).unwrap())
+                .unwrap();
+        }
+        assert_eq!(stream.finish().unwrap().into_inner(), data);
+    }
+}
+
+#[test]
+fn test_raw_repack_reencrypts_moved_entries() {
+    use crate::bar::{BarReader, BarWriter};
+    use crate::structs::CompressionType;
+    use hdk_secure::hash::AfsHash;
+
+    let data = write_flagged_bar(enumflags2::BitFlags::empty());
+    let mut archive =
+        BarReader::open(Cursor::new(data), TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY).unwrap();
+
+    // Replacing the first entry with a bigger one moves every other entry
+    let mut writer = BarWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    )
+    .with_timestamp(42);
+    writer
+        .add_entry(
+            AfsHash::new_from_str("file0.bin"),
+            CompressionType::None,
+            &[0xEE; 1000],
+        )
+        .unwrap();
+    for i in 1..archive.entry_count() {
+        writer
+            .add_raw_entry(archive.raw_entry_reader(i).unwrap())
+            .unwrap();
+    }
+    let out = writer.finish().unwrap().into_inner();
+
+    let mut repacked =
+        BarReader::open(Cursor::new(out), TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY).unwrap();
+    for i in 1..8u8 {
+        let index = usize::from(i);
+        repacked.verify_entry(index).unwrap();
+
+        let mut out = Vec::new();
+        repacked
+            .entry_reader(index)
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, vec![i; 100 + index]);
+    }
+}
+
+#[test]
+fn test_lookup_by_hash_and_path() {
+    use crate::bar::{BarReader, BarWriter};
+    use crate::error::ArchiveError;
+    use crate::structs::CompressionType;
+    use hdk_secure::hash::AfsHash;
+
+    let mut writer = BarWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    );
+    for (path, content) in [
+        ("object.xml", &b"<object/>"[..]),
+        ("scripts/main.lua", b"print('a')"),
+        ("dupe.bin", b"first"),
+        ("DUPE.BIN", b"second"),
+    ] {
+        writer
+            .add_entry(AfsHash::new_from_str(path), CompressionType::ZLib, content)
+            .unwrap();
+    }
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut archive =
+        BarReader::open(Cursor::new(data), TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY).unwrap();
+
+    assert_eq!(
+        archive
+            .find_by_hash(AfsHash::new_from_str("object.xml"))
+            .unwrap(),
+        Some(0)
+    );
+    assert_eq!(archive.find_by_path("SCRIPTS\\Main.lua").unwrap(), Some(1));
+    assert_eq!(archive.find_by_path("missing.xml").unwrap(), None);
+
+    let mut stream = archive.open_path("scripts/main.lua").unwrap();
+    let mut out = Vec::new();
+    stream.reader.read_to_end(&mut out).unwrap();
+    assert_eq!(out, b"print('a')");
+    assert_eq!(
+        stream.metadata.name_hash,
+        AfsHash::new_from_str("scripts/main.lua")
+    );
+    drop(stream);
+
+    assert!(matches!(
+        archive.open_path("missing.xml"),
+        Err(ArchiveError::EntryNotFound(hash)) if hash == AfsHash::new_from_str("missing.xml")
+    ));
+    assert!(matches!(
+        archive.find_by_path("dupe.bin"),
+        Err(ArchiveError::DuplicateHash { indices, .. }) if indices == [2, 3]
+    ));
+}
@@ -1,14 +1,20 @@
 use byteorder::{LittleEndian, WriteBytesExt};
 use enumflags2::BitFlags;
-use flate2::{Compression, write::ZlibEncoder};
+use flate2::{
+    Compression,
+    write::{DeflateEncoder, ZlibEncoder},
+};
 use std::io::{self, Cursor, Read, Write};
 
 use ctr::Ctr64BE;
 use ctr::cipher::KeyIvInit;
-use hdk_comp::zlib::writer::SegmentedZlibWriter;
+use hdk_comp::zlib::{reader::SegmentedZlibReader, writer::SegmentedZlibWriter};
 use hdk_secure::{blowfish::Blowfish, hash::AfsHash, writer::CryptoWriter};
 
+use super::structs::{BarEntryMetadata, BarHeader};
+use crate::archive::{ArchiveWriter, RawEntry};
 use crate::structs::{ARCHIVE_MAGIC, ArchiveFlags, ArchiveVersion, CompressionType};
+use crate::utils::{Sha1Reader, toc_u32};
 
 pub struct BarWriter<W: Write> {
     /// The underlying writer.
@@ -21,6 +27,22 @@ pub struct BarWriter<W: Write> {
     /// Default is no flags.
     flags: BitFlags<ArchiveFlags>,
 
+    /// Priority field in the header.
+    ///
+    /// Home uses this to choose which archive has precedence when loading files
+    /// with conflicting name hashes.
+    ///
+    /// Default is `0`.
+    priority: i32,
+
+    /// Timestamp field in the header.
+    ///
+    /// This must match the archive's `.time` file for the client to mount it, and
+    /// it is also part of the IV of encrypted entries.
+    ///
+    /// Default is `0`.
+    timestamp: i32,
+
     /// The list of entries to write.
     ///
     /// Each entry holds its data in-memory until `finish()` is called.
@@ -63,6 +85,8 @@ impl<W: Write> BarWriter<W> {
         Self {
             inner,
             flags: BitFlags::empty(), // Default no flags
+            priority: 0,
+            timestamp: 0,
             entries: Vec::new(),
             default_key,
             signature_key,
@@ -85,11 +109,45 @@ impl<W: Write> BarWriter<W> {
         self
     }
 
+    /// Create a new BAR archive writer that keeps the flags, priority and timestamp
+    /// of an existing archive's header.
+    ///
+    /// This is useful to repack an archive read with [`super::BarReader`] while keeping
+    /// it consistent with its `.time` file.
+    pub fn from_header(
+        inner: W,
+        header: &BarHeader,
+        default_key: [u8; 32],
+        signature_key: [u8; 32],
+    ) -> Self {
+        Self::new(inner, default_key, signature_key)
+            .with_flags(header.flags())
+            .with_priority(header.priority)
+            .with_timestamp(header.timestamp)
+    }
+
     pub const fn with_flags(mut self, flags: BitFlags<ArchiveFlags>) -> Self {
         self.flags = flags;
         self
     }
 
+    /// The archive flags that will be written in the header.
+    pub const fn flags(&self) -> BitFlags<ArchiveFlags> {
+        self.flags
+    }
+
+    /// Set the priority written in the header.
+    pub const fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Set the timestamp written in the header and used to forge encrypted entries' IVs.
+    pub const fn with_timestamp(mut self, timestamp: i32) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
     pub fn add_entry(
         &mut self,
         name_hash: AfsHash,
@@ -173,7 +231,59 @@ impl<W: Write> BarWriter<W> {
         Ok(())
     }
 
+    /// Add an entry from a payload read with [`ArchiveReader::raw_entry_reader`], without
+    /// recompressing it.
+    ///
+    /// The payload is stored as-is, except for encrypted entries, which are encrypted
+    /// in `finish()` with an IV forged from their new offset. If the source did not
+    /// store a SHA-1 signature for an encrypted entry, it is computed by decompressing
+    /// the payload.
+    ///
+    /// [`ArchiveReader::raw_entry_reader`]: crate::archive::ArchiveReader::raw_entry_reader
+    pub fn add_raw_entry(&mut self, mut entry: RawEntry<'_>) -> io::Result<()> {
+        let mut data = Vec::new();
+        entry.reader.read_to_end(&mut data)?;
+
+        let (sha1, compressed_size) = if entry.compression == CompressionType::Encrypted {
+            let checksum = match entry.checksum {
+                Some(checksum) => checksum,
+                None => {
+                    let mut hasher = Sha1Reader::new(SegmentedZlibReader::new(&data[..]));
+                    io::copy(&mut hasher, &mut io::sink())?;
+                    hasher.digest()
+                }
+            };
+
+            (Some(checksum), toc_u32(data.len() as u64 + 28)?)
+        } else {
+            (None, toc_u32(data.len() as u64)?)
+        };
+
+        self.entries.push(BarEntryToWrite {
+            name_hash: entry.name_hash,
+            compression: entry.compression,
+            uncompressed_size: entry.uncompressed_size,
+            compressed_size,
+            data,
+            offset: 0,
+            sha1,
+        });
+
+        Ok(())
+    }
+
     pub fn finish(mut self) -> io::Result<W> {
+        // LeanZLib archives store ZLib entries without the zlib header and trailer,
+        // which are only known to be unwanted once the flags are final.
+        if self.flags.contains(ArchiveFlags::LeanZLib) {
+            for entry in &mut self.entries {
+                if entry.compression == CompressionType::ZLib {
+                    entry.data = strip_zlib_wrapper(&entry.data)?.to_vec();
+                    entry.compressed_size = entry.data.len() as u32;
+                }
+            }
+        }
+
         self.calculate_offsets();
 
         // Offsets are already calculated (including per-entry padding) by `calculate_offsets()`
@@ -181,37 +291,20 @@ impl<W: Write> BarWriter<W> {
         // Write Header
         let file_count = self.entries.len() as u32;
 
-        // Write Magic
-        self.inner.write_u32::<LittleEndian>(ARCHIVE_MAGIC)?;
-
-        // Version and Flags
-        let version = ArchiveVersion::BAR;
-        let version_u16: u16 = version.into();
-        let flags_u16 = self.flags.bits();
-
-        let ver_flags = (u32::from(version_u16) << 16) | u32::from(flags_u16);
-        self.inner.write_u32::<LittleEndian>(ver_flags)?;
-
-        // Priority (0 default)
-        self.inner.write_i32::<LittleEndian>(0)?;
-        // Timestamp (0 default)
-        // TODO: support custom timestamp
-        self.inner.write_i32::<LittleEndian>(0)?;
-        // File Count
-        self.inner.write_u32::<LittleEndian>(file_count)?;
+        write_header(
+            &mut self.inner,
+            self.flags,
+            self.priority,
+            self.timestamp,
+            file_count,
+        )?;
 
         // Write ToC
-        for entry in &self.entries {
-            self.inner.write_i32::<LittleEndian>(entry.name_hash.0)?;
-
-            let comp_val: u8 = entry.compression.into();
-            let val = (entry.offset & 0xFFFFFFFC) | u32::from(comp_val);
-            self.inner.write_u32::<LittleEndian>(val)?;
-
-            self.inner
-                .write_u32::<LittleEndian>(entry.uncompressed_size)?;
-            self.inner
-                .write_u32::<LittleEndian>(entry.compressed_size)?;
+        let entries = self.entries.iter().map(BarEntryToWrite::metadata);
+        if self.flags.contains(ArchiveFlags::ZTOC) {
+            write_compressed_toc(&mut self.inner, entries)?;
+        } else {
+            write_toc(&mut self.inner, entries)?;
         }
 
         // For encrypted entries we need to build the encrypted payload now that
@@ -226,44 +319,18 @@ impl<W: Write> BarWriter<W> {
                     u64::from(entry.uncompressed_size),
                     u64::from(entry.compressed_size),
                     u64::from(entry.offset),
-                    0, // timestamp currently 0
+                    self.timestamp,
                 );
 
-                // Build head: 4B fourcc (zeros) + 20B checksum
-                let mut head = Vec::new();
-                head.extend_from_slice(&[0u8; 4]);
-                head.extend_from_slice(&checksum);
+                // Encrypt head with signature_key and body with default_key using IV + 3
+                let mut final_data = Vec::with_capacity(entry.compressed_size as usize);
+                write_encrypted_head(&mut final_data, &self.signature_key, iv, &checksum)?;
 
-                // Encrypt head with signature_key using CryptoWriter
-                let mut cw_head = CryptoWriter::new(
-                    Vec::new(),
-                    Ctr64BE::<Blowfish>::new(&self.signature_key.into(), &iv.into()),
-                );
-                cw_head.write_all(&head)?;
-                let head_enc = cw_head.into_inner();
-
-                // Encrypt body with default_key using IV + 3 via CryptoWriter
-                let mut iv_as_u64 = u64::from_be_bytes(iv);
-                iv_as_u64 = iv_as_u64.wrapping_add(3);
-                let iv_body = iv_as_u64.to_be_bytes();
-
-                let mut cw_body = CryptoWriter::new(
-                    Vec::new(),
-                    Ctr64BE::<Blowfish>::new(&self.default_key.into(), &iv_body.into()),
-                );
+                let mut cw_body =
+                    CryptoWriter::new(final_data, super::body_cipher(&self.default_key, iv));
                 cw_body.write_all(&entry.data)?;
-                let body_enc = cw_body.into_inner();
-
-                // Body fourcc (4 bytes) - kept raw (zeros)
-                let body_fourcc = [0u8; 4];
-
-                // Compose final data: encrypted head, body_fourcc, encrypted body
-                let mut final_data = Vec::new();
-                final_data.extend_from_slice(&head_enc);
-                final_data.extend_from_slice(&body_fourcc);
-                final_data.extend_from_slice(&body_enc);
 
-                entry.data = final_data;
+                entry.data = cw_body.into_inner();
             }
 
             self.inner.write_all(&entry.data)?;
@@ -279,6 +346,150 @@ impl<W: Write> BarWriter<W> {
     }
 }
 
+impl<W: Write> ArchiveWriter for BarWriter<W> {
+    type Output = W;
+
+    fn add_entry_from_reader<R: Read + ?Sized>(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        reader: &mut R,
+    ) -> io::Result<()> {
+        Self::add_entry_from_reader(self, name_hash, compression, reader)
+    }
+
+    fn add_raw_entry(&mut self, entry: RawEntry<'_>) -> io::Result<()> {
+        Self::add_raw_entry(self, entry)
+    }
+
+    fn flags(&self) -> BitFlags<ArchiveFlags> {
+        Self::flags(self)
+    }
+
+    fn set_flags(&mut self, flags: BitFlags<ArchiveFlags>) -> io::Result<()> {
+        self.flags = flags;
+        Ok(())
+    }
+
+    fn set_priority(&mut self, priority: i32) {
+        self.priority = priority;
+    }
+
+    fn set_timestamp(&mut self, timestamp: i32) {
+        self.timestamp = timestamp;
+    }
+
+    fn finish(self) -> io::Result<W> {
+        Self::finish(self)
+    }
+}
+
+impl BarEntryToWrite {
+    const fn metadata(&self) -> BarEntryMetadata {
+        BarEntryMetadata {
+            name_hash: self.name_hash,
+            offset: self.offset,
+            compression: self.compression,
+            uncompressed_size: self.uncompressed_size,
+            compressed_size: self.compressed_size,
+        }
+    }
+}
+
+/// Write the fixed BAR header (magic, version and flags, priority, timestamp, file count).
+pub(super) fn write_header<W: Write>(
+    out: &mut W,
+    flags: BitFlags<ArchiveFlags>,
+    priority: i32,
+    timestamp: i32,
+    file_count: u32,
+) -> io::Result<()> {
+    // Write Magic
+    out.write_u32::<LittleEndian>(ARCHIVE_MAGIC)?;
+
+    // Version and Flags
+    let version_u16: u16 = ArchiveVersion::BAR.into();
+    let ver_flags = (u32::from(version_u16) << 16) | u32::from(flags.bits());
+    out.write_u32::<LittleEndian>(ver_flags)?;
+
+    out.write_i32::<LittleEndian>(priority)?;
+    out.write_i32::<LittleEndian>(timestamp)?;
+    out.write_u32::<LittleEndian>(file_count)
+}
+
+/// Write the (uncompressed) table of contents.
+pub(super) fn write_toc<W: Write>(
+    out: &mut W,
+    entries: impl IntoIterator<Item = BarEntryMetadata>,
+) -> io::Result<()> {
+    for entry in entries {
+        out.write_i32::<LittleEndian>(entry.name_hash.0)?;
+
+        let comp_val: u8 = entry.compression.into();
+        let val = (entry.offset & 0xFFFFFFFC) | u32::from(comp_val);
+        out.write_u32::<LittleEndian>(val)?;
+
+        out.write_u32::<LittleEndian>(entry.uncompressed_size)?;
+        out.write_u32::<LittleEndian>(entry.compressed_size)?;
+    }
+
+    Ok(())
+}
+
+/// Write the table of contents deflated (without zlib header), prefixed by its compressed size.
+///
+/// Entry offsets are relative to the end of the compressed ToC, which is what the
+/// reader uses as its base when `ZTOC` is set.
+pub(super) fn write_compressed_toc<W: Write>(
+    out: &mut W,
+    entries: impl IntoIterator<Item = BarEntryMetadata>,
+) -> io::Result<()> {
+    let mut enc = DeflateEncoder::new(Vec::new(), Compression::best());
+    write_toc(&mut enc, entries)?;
+    let compressed = enc.finish()?;
+
+    out.write_u32::<LittleEndian>(compressed.len() as u32)?;
+    out.write_all(&compressed)
+}
+
+/// Strip the 2-byte header and 4-byte Adler-32 trailer of a zlib stream, leaving the raw
+/// deflate data used by `LeanZLib` archives.
+pub(super) fn strip_zlib_wrapper(data: &[u8]) -> io::Result<&[u8]> {
+    if data.len() < 6 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "ZLib stream too short",
+        ));
+    }
+
+    Ok(&data[2..data.len() - 4])
+}
+
+/// Write the encrypted 24-byte head of an encrypted entry, followed by the raw body fourcc.
+///
+/// The head is a 4-byte fourcc (zeros) followed by the SHA-1 of the uncompressed body,
+/// encrypted with the signature key.
+pub(super) fn write_encrypted_head<W: Write>(
+    out: &mut W,
+    signature_key: &[u8; 32],
+    iv: [u8; 8],
+    checksum: &[u8; 20],
+) -> io::Result<()> {
+    // Build head: 4B fourcc (zeros) + 20B checksum
+    let mut head = [0u8; 24];
+    head[4..].copy_from_slice(checksum);
+
+    // Encrypt head with signature_key using CryptoWriter
+    let mut cw_head = CryptoWriter::new(
+        &mut *out,
+        Ctr64BE::<Blowfish>::new(signature_key.into(), &iv.into()),
+    );
+    cw_head.write_all(&head)?;
+
+    // Body fourcc (4 bytes) - kept raw (zeros)
+    out.write_all(&[0u8; 4])
+}
+
 // Private helper for recalculating offsets
 impl<W: Write> BarWriter<W> {
     fn calculate_offsets(&mut self) {
@@ -0,0 +1,89 @@
+//! Lossless conversion between BAR and SHARC archives.
+//!
+//! Entries keep their [`AfsHash`](hdk_secure::hash::AfsHash) and compression type,
+//! and their compressed payloads are moved as-is whenever both formats store them
+//! the same way, instead of being decompressed and recompressed:
+//!
+//! | Source                        | Target     | Payload                          |
+//! | :---------------------------- | :--------- | :------------------------------- |
+//! | `None`                        | `None`     | copied                           |
+//! | `ZLib`                        | `ZLib`     | copied                           |
+//! | `ZLib` (BAR, `LeanZLib`)      | `ZLib`     | rewrapped with a zlib header     |
+//! | `EdgeZLib`                    | `EdgeZLib` | copied                           |
+//! | `Encrypted`                   | `Encrypted`| decrypted, copied, re-encrypted  |
+//! | `EdgeZLib` (SHARC, nested)    | `None`     | decrypted, copied                |
+//!
+//! SHARC entries whose `EdgeZLib` sizes match are encrypted nested archives, which
+//! BAR has no equivalent for; they are stored uncompressed instead.
+//!
+//! The target can be any [`ArchiveWriter`], including the streaming writers. The
+//! source header's priority, timestamp and `Protected` flag are carried over.
+//! `ZTOC` and `LeanZLib` only describe how a BAR is laid out, so they are left to
+//! the target writer's configuration.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use hdk_archive::bar::BarReader;
+//! use hdk_archive::convert::bar_to_sharc;
+//! use hdk_archive::sharc::writer::SharcWriter;
+//! use hdk_archive::structs::Endianness;
+//!
+//! let file = std::fs::File::open("path/to/archive.bar").unwrap();
+//! let mut bar = BarReader::open(file, [0u8; 32], [0u8; 32]).unwrap();
+//!
+//! let writer = SharcWriter::new(Vec::new(), [0u8; 32], Endianness::Big).unwrap();
+//! let sharc = bar_to_sharc(&mut bar, writer).unwrap();
+//! ```
+
+use std::io::{self, Read, Seek};
+
+use crate::archive::{ArchiveReader, ArchiveWriter};
+use crate::bar::BarReader;
+use crate::sharc::reader::SharcReader;
+use crate::structs::ArchiveFlags;
+
+#[cfg(test)]
+mod tests;
+
+/// Convert a BAR archive to SHARC, writing every entry to `writer`.
+///
+/// `writer` decides the key, endianness and files key of the resulting archive.
+/// Returns the output of [`ArchiveWriter::finish`].
+pub fn bar_to_sharc<R: Read + Seek, W: ArchiveWriter>(
+    reader: &mut BarReader<R>,
+    mut writer: W,
+) -> io::Result<W::Output> {
+    let header = reader.header();
+
+    writer.set_flags(writer.flags() | (header.flags() & ArchiveFlags::Protected))?;
+    writer.set_priority(header.priority);
+    writer.set_timestamp(header.timestamp);
+
+    for index in reader.entry_indices() {
+        writer.add_raw_entry(reader.raw_entry_reader(index)?)?;
+    }
+
+    writer.finish()
+}
+
+/// Convert a SHARC archive to BAR, writing every entry to `writer`.
+///
+/// `writer` decides the keys and layout flags of the resulting archive.
+/// Returns the output of [`ArchiveWriter::finish`].
+pub fn sharc_to_bar<R: Read + Seek, W: ArchiveWriter>(
+    reader: &mut SharcReader<R>,
+    mut writer: W,
+) -> io::Result<W::Output> {
+    let header = reader.header();
+
+    writer.set_flags(writer.flags() | (header.flags & ArchiveFlags::Protected))?;
+    writer.set_priority(header.priority);
+    writer.set_timestamp(header.timestamp);
+
+    for index in reader.entry_indices() {
+        writer.add_raw_entry(reader.raw_entry_reader(index)?)?;
+    }
+
+    writer.finish()
+}
@@ -0,0 +1,208 @@
+use std::io::{Cursor, Read};
+
+use hdk_secure::hash::AfsHash;
+
+use crate::archive::{ArchiveReader, ArchiveWriter, EntryMetadata};
+use crate::bar::{BarStreamWriter, BarWriter};
+use crate::convert::{bar_to_sharc, sharc_to_bar};
+use crate::sharc::stream::SharcStreamWriter;
+use crate::sharc::writer::SharcWriter;
+use crate::structs::{ArchiveFlags, CompressionType, Endianness};
+use crate::test_utils::{
+    TEST_DEFAULT_KEY, TEST_SHARC_KEY, TEST_SIGNATURE_KEY, TestEntry, open_bar, open_sharc,
+    write_entries,
+};
+
+const COMPRESSIONS: [CompressionType; 4] = [
+    CompressionType::None,
+    CompressionType::ZLib,
+    CompressionType::EdgeZLib,
+    CompressionType::Encrypted,
+];
+
+fn content(i: usize) -> Vec<u8> {
+    (0..100_000 + i * 7)
+        .map(|j| (j * (i + 3) % 241) as u8)
+        .collect()
+}
+
+fn path(i: usize) -> String {
+    format!("entry{i}.bin")
+}
+
+fn name(i: usize) -> AfsHash {
+    AfsHash::new_from_str(&path(i))
+}
+
+/// Add an entry of each compression type to `writer` and finish it.
+fn write_contents<W: ArchiveWriter>(writer: W) -> W::Output {
+    let paths: Vec<_> = (0..COMPRESSIONS.len()).map(path).collect();
+    let contents: Vec<_> = (0..COMPRESSIONS.len()).map(content).collect();
+    let entries: Vec<TestEntry> = COMPRESSIONS
+        .iter()
+        .enumerate()
+        .map(|(i, compression)| (paths[i].as_str(), *compression, contents[i].as_slice()))
+        .collect();
+
+    write_entries(writer, &entries)
+}
+
+fn create_bar(flags: enumflags2::BitFlags<ArchiveFlags>) -> Vec<u8> {
+    let writer = BarWriter::new(Vec::new(), TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY)
+        .with_flags(flags)
+        .with_priority(-3)
+        .with_timestamp(0x1234_5678);
+
+    write_contents(writer)
+}
+
+fn assert_contents<A: ArchiveReader>(archive: &mut A) {
+    assert_eq!(archive.entry_count(), COMPRESSIONS.len());
+
+    for (i, compression) in COMPRESSIONS.iter().enumerate() {
+        let metadata = archive.entry_metadata(i).unwrap();
+        assert_eq!(metadata.name_hash(), name(i));
+        assert_eq!(metadata.compression(), *compression);
+
+        let mut out = Vec::new();
+        archive
+            .entry_reader(i)
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, content(i));
+    }
+}
+
+#[test]
+fn bar_to_sharc_keeps_entries_and_header() {
+    let data = create_bar(ArchiveFlags::Protected.into());
+    let mut bar = open_bar(data);
+
+    let writer = SharcWriter::new(Vec::new(), TEST_SHARC_KEY, Endianness::Big).unwrap();
+    let out = bar_to_sharc(&mut bar, writer).unwrap();
+
+    let mut sharc = open_sharc(out);
+    assert_contents(&mut sharc);
+
+    let header = sharc.header();
+    assert_eq!(header.priority, -3);
+    assert_eq!(header.timestamp, 0x1234_5678);
+    assert!(header.flags.contains(ArchiveFlags::Protected));
+
+    // Payloads are reused as-is (encrypted BAR entries also carry a 28-byte head)
+    for i in 0..3 {
+        assert_eq!(
+            sharc.entry_metadata(i).unwrap().compressed_size,
+            bar.entry_metadata(i).unwrap().compressed_size
+        );
+    }
+    assert_eq!(
+        sharc.entry_metadata(3).unwrap().compressed_size + 28,
+        bar.entry_metadata(3).unwrap().compressed_size
+    );
+}
+
+#[test]
+fn lean_zlib_bar_to_sharc() {
+    let data = create_bar(ArchiveFlags::LeanZLib | ArchiveFlags::ZTOC);
+    let mut bar = open_bar(data);
+
+    let writer = SharcWriter::new(Vec::new(), TEST_SHARC_KEY, Endianness::Little).unwrap();
+    let out = bar_to_sharc(&mut bar, writer).unwrap();
+
+    let mut sharc = open_sharc(out);
+    assert_contents(&mut sharc);
+    assert!(sharc.header().flags.is_empty());
+}
+
+#[test]
+fn sharc_to_bar_signs_encrypted_entries() {
+    let mut writer = SharcWriter::new(Vec::new(), TEST_SHARC_KEY, Endianness::Big).unwrap();
+    writer.priority = 7;
+    writer.timestamp = -42;
+    let mut sharc = open_sharc(write_contents(writer));
+    let writer = BarWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    );
+    let out = sharc_to_bar(&mut sharc, writer).unwrap().into_inner();
+
+    let mut bar = open_bar(out);
+    assert_contents(&mut bar);
+
+    // SHARC stores no signature, so the converted one must have been computed
+    bar.verify_entry(3).unwrap();
+
+    let header = bar.header();
+    assert_eq!(header.priority, 7);
+    assert_eq!(header.timestamp, -42);
+}
+
+#[test]
+fn bar_sharc_bar_roundtrip() {
+    let data = create_bar(enumflags2::BitFlags::empty());
+    let mut bar = open_bar(data.clone());
+
+    let writer = SharcWriter::new(Vec::new(), TEST_SHARC_KEY, Endianness::Big).unwrap();
+    let sharc = bar_to_sharc(&mut bar, writer).unwrap();
+
+    let mut sharc = open_sharc(sharc);
+    let writer = BarWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    );
+    let out = sharc_to_bar(&mut sharc, writer).unwrap().into_inner();
+
+    assert_eq!(out, data);
+}
+
+#[test]
+fn converts_into_stream_writers() {
+    let data = create_bar(ArchiveFlags::Protected.into());
+    let mut bar = open_bar(data);
+
+    let writer = SharcStreamWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_SHARC_KEY,
+        Endianness::Big,
+        COMPRESSIONS.len() as u32,
+    )
+    .unwrap();
+    let out = bar_to_sharc(&mut bar, writer).unwrap().into_inner();
+
+    let mut sharc = open_sharc(out);
+    assert_contents(&mut sharc);
+
+    let header = sharc.header();
+    assert_eq!(header.priority, -3);
+    assert_eq!(header.timestamp, 0x1234_5678);
+    assert!(header.flags.contains(ArchiveFlags::Protected));
+
+    // The target's own layout flags are kept alongside the carried-over ones
+    let writer = BarStreamWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+        COMPRESSIONS.len() as u32,
+    )
+    .unwrap()
+    .with_flags(ArchiveFlags::LeanZLib.into())
+    .unwrap();
+    let streamed = sharc_to_bar(&mut sharc, writer).unwrap().into_inner();
+
+    let writer = BarWriter::new(Vec::new(), TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY)
+        .with_flags(ArchiveFlags::LeanZLib.into());
+    let buffered = sharc_to_bar(&mut sharc, writer).unwrap();
+    assert_eq!(streamed, buffered);
+
+    let mut bar = open_bar(streamed);
+    assert_contents(&mut bar);
+    bar.verify_entry(3).unwrap();
+    assert_eq!(
+        bar.header().flags(),
+        ArchiveFlags::Protected | ArchiveFlags::LeanZLib
+    );
+}
@@ -0,0 +1,83 @@
+//! Error types for archive operations
+
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use hdk_secure::hash::AfsHash;
+
+use crate::structs::{ArchiveVersion, Endianness};
+
+/// Main error type for archive operations
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("I/O error: {0}")]
+    Io(io::Error),
+
+    #[error("invalid archive magic: {0:#010X}")]
+    InvalidMagic(u32),
+
+    #[error("unknown archive version: {0:#06X}")]
+    UnknownVersion(u16),
+
+    #[error("wrong archive format: expected {expected:?}, found {found:?}")]
+    WrongFormat {
+        expected: ArchiveVersion,
+        found: ArchiveVersion,
+    },
+
+    #[error("keys required to open {0:?} archive")]
+    KeysRequired(ArchiveVersion),
+
+    #[error("SHA-1 signature mismatch for entry {name_hash}")]
+    SignatureMismatch {
+        name_hash: AfsHash,
+        expected: [u8; 20],
+        actual: [u8; 20],
+    },
+
+    #[error("no entry found for {0}")]
+    EntryNotFound(AfsHash),
+
+    #[error("duplicate entries for {name_hash} at indices {indices:?}")]
+    DuplicateHash {
+        name_hash: AfsHash,
+        indices: Vec<usize>,
+    },
+
+    #[error("{} and {} both hash to {name_hash}", first.display(), second.display())]
+    HashCollision {
+        name_hash: AfsHash,
+        first: PathBuf,
+        second: PathBuf,
+    },
+
+    #[error("unsupported {endianness:?}-endian {version:?} archive")]
+    UnsupportedEndianness {
+        version: ArchiveVersion,
+        endianness: Endianness,
+    },
+}
+
+impl From<io::Error> for ArchiveError {
+    /// Typed errors surfaced through `io::Read` (e.g. signature mismatches) are
+    /// unwrapped back into their original variant.
+    fn from(err: io::Error) -> Self {
+        if err.get_ref().is_some_and(|inner| inner.is::<Self>()) {
+            let inner = err.into_inner().expect("checked above");
+            return *inner.downcast::<Self>().expect("checked above");
+        }
+
+        Self::Io(err)
+    }
+}
+
+impl From<ArchiveError> for io::Error {
+    fn from(err: ArchiveError) -> Self {
+        match err {
+            ArchiveError::Io(e) => e,
+            other => Self::new(io::ErrorKind::InvalidData, other),
+        }
+    }
+}
@@ -1,5 +1,22 @@
+pub mod any;
 pub mod archive;
 pub mod bar;
+pub mod convert;
+pub mod error;
 pub mod mapper;
+pub mod pack;
+pub mod salvage;
 pub mod sharc;
 pub mod structs;
+pub mod time;
+pub mod unpack;
+pub mod validate;
+pub mod walk;
+
+mod utils;
+
+#[cfg(test)]
+mod test_utils;
+
+pub use any::open_any;
+pub use error::ArchiveError;
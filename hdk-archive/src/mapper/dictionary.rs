@@ -0,0 +1,182 @@
+//! A persistent dictionary of known paths, keyed by their [`AfsHash`].
+//!
+//! Dictionaries are saved as plain text, one `HASH<TAB>path` line per entry, sorted
+//! by hash so that files from different runs diff and merge cleanly. Empty lines and
+//! lines starting with `#` are ignored, and lines without a hash (a bare path) are
+//! hashed on load, so a plain list of paths can be imported as-is.
+//!
+//! Paths of files inside an Object are stored with their `Objects/{uuid}/` prefix,
+//! since the hash depends on it. That prefix is the entry's UUID context: when a
+//! [`Mapper`](super::Mapper) with a different UUID is seeded with the dictionary,
+//! the same relative paths are also tried under its own prefix.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use hdk_secure::hash::AfsHash;
+
+/// Known paths keyed by their hash.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HashDictionary {
+    paths: HashMap<AfsHash, String>,
+}
+
+impl HashDictionary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Add a path, returning its hash.
+    ///
+    /// If another path with the same hash is already known, it is kept.
+    pub fn insert(&mut self, path: impl Into<String>) -> AfsHash {
+        let path = path.into();
+        let hash = AfsHash::new_from_str(&path);
+        self.paths.entry(hash).or_insert(path);
+        hash
+    }
+
+    pub fn get(&self, hash: AfsHash) -> Option<&str> {
+        self.paths.get(&hash).map(String::as_str)
+    }
+
+    pub fn contains(&self, hash: AfsHash) -> bool {
+        self.paths.contains_key(&hash)
+    }
+
+    /// Iterate over the entries, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (AfsHash, &str)> {
+        self.paths.iter().map(|(hash, path)| (*hash, path.as_str()))
+    }
+
+    /// Add every entry of `other` whose hash is not known yet, returning how many
+    /// were added.
+    pub fn merge(&mut self, other: &Self) -> usize {
+        let before = self.len();
+
+        for (hash, path) in &other.paths {
+            self.paths.entry(*hash).or_insert_with(|| path.clone());
+        }
+
+        self.len() - before
+    }
+
+    /// Parse a dictionary from its text format.
+    ///
+    /// Fails if a line's hash does not match its path.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut dictionary = Self::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((hash, path)) = line.split_once('\t') else {
+                dictionary.insert(line);
+                continue;
+            };
+
+            let hash = AfsHash::from_hash_str(hash.trim()).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Line {}: invalid hash {hash:?}", number + 1),
+                )
+            })?;
+
+            let path = path.trim();
+            if AfsHash::new_from_str(path) != hash {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Line {}: {path:?} does not hash to {hash}", number + 1),
+                ));
+            }
+
+            dictionary.insert(path);
+        }
+
+        Ok(dictionary)
+    }
+
+    /// Load a dictionary saved with [`HashDictionary::save`].
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Write the dictionary in its text format, sorted by hash.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_by_key(|(hash, _)| hash.0 as u32);
+
+        for (hash, path) in entries {
+            writeln!(writer, "{hash}\t{path}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Save the dictionary to a file, replacing it.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    /// The entries' paths, also placed under `Objects/{uuid}/` for those that have a
+    /// UUID context, keyed by hash.
+    pub(super) fn with_uuid(&self, uuid: Option<&str>) -> HashMap<AfsHash, String> {
+        let mut hashes = self.paths.clone();
+
+        if let Some(uuid) = uuid {
+            // Sorted, so that which of two paths with the same hash is kept is stable
+            let mut paths: Vec<_> = self.paths.values().collect();
+            paths.sort_unstable();
+
+            for path in paths {
+                if let Some((_, relative)) = split_object_path(path) {
+                    let path = format!("Objects/{uuid}/{relative}");
+                    hashes.entry(AfsHash::new_from_str(&path)).or_insert(path);
+                }
+            }
+        }
+
+        hashes
+    }
+}
+
+impl<S: Into<String>> FromIterator<S> for HashDictionary {
+    fn from_iter<I: IntoIterator<Item = S>>(paths: I) -> Self {
+        let mut dictionary = Self::new();
+        dictionary.extend(paths);
+        dictionary
+    }
+}
+
+impl<S: Into<String>> Extend<S> for HashDictionary {
+    fn extend<I: IntoIterator<Item = S>>(&mut self, paths: I) {
+        for path in paths {
+            self.insert(path);
+        }
+    }
+}
+
+/// Split a path of the form `Objects/{uuid}/{relative}` into its UUID and relative path.
+fn split_object_path(path: &str) -> Option<(&str, &str)> {
+    let normalized = path.trim_start_matches(['/', '\\']);
+    let (root, rest) = normalized.split_once(['/', '\\'])?;
+    let (uuid, relative) = rest.split_once(['/', '\\'])?;
+
+    (root.eq_ignore_ascii_case("objects") && !uuid.is_empty() && !relative.is_empty())
+        .then_some((uuid, relative))
+}
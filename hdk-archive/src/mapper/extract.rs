@@ -0,0 +1,253 @@
+//! Format-aware extraction of strings from binary files.
+//!
+//! The regex patterns only see a file's content as (lossy) UTF-8, which misses
+//! paths in UTF-16 text and can glue binary bytes (such as a length prefix that
+//! happens to be printable) onto the paths it finds. Extractors pull strings out of
+//! such formats first; the regex pass still runs over the raw content as a fallback.
+
+use std::io::{Cursor, Seek, SeekFrom};
+
+use binrw::BinRead;
+use hdk_mdl::Material;
+
+use super::luac;
+
+/// Strings pulled out of a file by a [`PathExtractor`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Extracted {
+    /// Text that may contain paths, searched with the regex patterns.
+    pub text: Vec<String>,
+
+    /// Strings known to be paths, used as-is.
+    pub paths: Vec<String>,
+}
+
+/// Extracts strings from a file's content before the regex pass.
+///
+/// Extractors are run on every file, so they should bail out quickly on data they
+/// do not understand.
+pub trait PathExtractor: Send + Sync {
+    fn extract(&self, data: &[u8], extracted: &mut Extracted);
+}
+
+/// The extractors used by a [`Mapper`](super::Mapper) unless told otherwise.
+pub fn default_extractors() -> Vec<Box<dyn PathExtractor>> {
+    vec![
+        Box::new(LuacExtractor),
+        Box::new(Utf16Extractor::default()),
+        Box::new(LengthPrefixedExtractor::default()),
+        Box::new(MdlExtractor),
+    ]
+}
+
+/// Whether a byte is printable ASCII, which all paths are made of.
+const fn is_printable(byte: u8) -> bool {
+    matches!(byte, 0x20..=0x7E)
+}
+
+/// Extracts runs of printable ASCII encoded as UTF-16, in both byte orders.
+#[derive(Debug, Clone, Copy)]
+pub struct Utf16Extractor {
+    /// Shortest run of characters worth extracting.
+    pub min_len: usize,
+}
+
+impl Default for Utf16Extractor {
+    fn default() -> Self {
+        Self { min_len: 4 }
+    }
+}
+
+impl PathExtractor for Utf16Extractor {
+    fn extract(&self, data: &[u8], extracted: &mut Extracted) {
+        for decode in [u16::from_le_bytes, u16::from_be_bytes] {
+            // Strings may start on either byte
+            for start in 0..2.min(data.len()) {
+                let mut run = String::new();
+
+                for unit in data[start..].chunks_exact(2) {
+                    match u8::try_from(decode([unit[0], unit[1]])) {
+                        Ok(byte) if is_printable(byte) => run.push(byte as char),
+                        _ => self.flush(&mut run, extracted),
+                    }
+                }
+
+                self.flush(&mut run, extracted);
+            }
+        }
+    }
+}
+
+impl Utf16Extractor {
+    fn flush(&self, run: &mut String, extracted: &mut Extracted) {
+        if run.len() >= self.min_len {
+            extracted.text.push(std::mem::take(run));
+        } else {
+            run.clear();
+        }
+    }
+}
+
+/// Extracts strings prefixed by their 32-bit length, in either byte order, as found in
+/// compiled Lua and many other binary formats.
+///
+/// A trailing NUL counted in the length (as Lua does) is dropped.
+#[derive(Debug, Clone, Copy)]
+pub struct LengthPrefixedExtractor {
+    /// Shortest string worth extracting.
+    pub min_len: usize,
+
+    /// Longest string worth extracting.
+    pub max_len: usize,
+}
+
+impl Default for LengthPrefixedExtractor {
+    fn default() -> Self {
+        Self {
+            min_len: 4,
+            max_len: 1024,
+        }
+    }
+}
+
+impl PathExtractor for LengthPrefixedExtractor {
+    fn extract(&self, data: &[u8], extracted: &mut Extracted) {
+        let mut offset = 0;
+
+        while offset + 4 <= data.len() {
+            let prefix = [
+                data[offset],
+                data[offset + 1],
+                data[offset + 2],
+                data[offset + 3],
+            ];
+
+            let string = [u32::from_le_bytes(prefix), u32::from_be_bytes(prefix)]
+                .into_iter()
+                .find_map(|len| self.string_at(data, offset + 4, len as usize));
+
+            match string {
+                Some((string, len)) => {
+                    extracted.text.push(string);
+                    offset += 4 + len;
+                }
+                None => offset += 1,
+            }
+        }
+    }
+}
+
+impl LengthPrefixedExtractor {
+    /// The string of `len` bytes at `start` and its length, if it is one.
+    fn string_at(&self, data: &[u8], start: usize, len: usize) -> Option<(String, usize)> {
+        if len > self.max_len {
+            return None;
+        }
+
+        let bytes = data.get(start..start + len)?;
+        let text = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+
+        (text.len() >= self.min_len && text.iter().all(|&byte| is_printable(byte)))
+            .then(|| (String::from_utf8_lossy(text).into_owned(), len))
+    }
+}
+
+/// Extracts the string constants of compiled Lua 5.1 scripts, with [`luac`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LuacExtractor;
+
+impl PathExtractor for LuacExtractor {
+    fn extract(&self, data: &[u8], extracted: &mut Extracted) {
+        if !luac::is_luac(data) {
+            return;
+        }
+
+        // A chunk that fails to read is left to the other extractors
+        if let Ok(strings) = luac::string_constants(data) {
+            extracted.text.extend(
+                strings
+                    .iter()
+                    .map(|string| String::from_utf8_lossy(string).into_owned()),
+            );
+        }
+    }
+}
+
+/// Extracts the material and texture filenames of MDL models, using `hdk-mdl`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MdlExtractor;
+
+/// Size of a material in an MDL file.
+const MDL_MATERIAL_SIZE: usize = 72;
+
+/// Most materials a plausible model has.
+const MDL_MAX_MATERIALS: u32 = 1024;
+
+impl PathExtractor for MdlExtractor {
+    fn extract(&self, data: &[u8], extracted: &mut Extracted) {
+        let Some(materials) = read_mdl_materials(data) else {
+            return;
+        };
+
+        for material in materials {
+            extracted.paths.push(material.filename);
+            extracted.paths.extend(
+                material
+                    .textures
+                    .into_iter()
+                    .map(|texture| texture.filename),
+            );
+        }
+    }
+}
+
+/// Read the materials of an MDL model, or `None` if `data` does not look like one.
+///
+/// `hdk-mdl` allocates as many items as the counts it reads say, so they are checked
+/// against the data size first: random data must not pass for a huge model.
+fn read_mdl_materials(data: &[u8]) -> Option<Vec<Material>> {
+    let u32_at = |offset: usize| -> Option<u32> {
+        let bytes = data.get(offset..offset + 4)?;
+        Some(u32::from_be_bytes(bytes.try_into().ok()?))
+    };
+    // Offsets are relative to where they are stored
+    let offset_at =
+        |offset: usize| -> Option<usize> { offset.checked_add(u32_at(offset)? as usize) };
+
+    let count = u32_at(20)?;
+    if count == 0 || count > MDL_MAX_MATERIALS {
+        return None;
+    }
+
+    let materials = offset_at(24)?;
+    let end = materials.checked_add(count as usize * MDL_MATERIAL_SIZE)?;
+    if end > data.len() {
+        return None;
+    }
+
+    // Each item of a material's lists takes at least 4 bytes
+    let max_items = (data.len() / 4) as u32;
+
+    for index in 0..count as usize {
+        let material = materials + index * MDL_MATERIAL_SIZE;
+
+        let constant_count = u32_at(material + 12)?;
+        let texture_count = u32_at(material + 24)?;
+        if constant_count > max_items / 4 || texture_count > max_items {
+            return None;
+        }
+
+        if let Some(attribute_count) = offset_at(material + 52).and_then(u32_at)
+            && attribute_count > max_items
+        {
+            return None;
+        }
+    }
+
+    let mut cursor = Cursor::new(data);
+    cursor.seek(SeekFrom::Start(materials as u64)).ok()?;
+
+    (0..count)
+        .map(|_| Material::read_be(&mut cursor).ok())
+        .collect()
+}
@@ -0,0 +1,224 @@
+//! Reader for the constant tables of compiled Lua 5.1 scripts (`.luac`).
+//!
+//! Scripts reference other files by strings in their constant tables, each stored
+//! behind a length prefix that regex patterns over lossy UTF-8 tend to glue onto
+//! the path or split it on. Reading the chunk instead yields every string constant
+//! exactly.
+//!
+//! Chunks are read as their header describes them. Home's PS3 scripts are
+//! big-endian, with 4-byte `int`, `size_t` and instructions and 8-byte numbers.
+
+use thiserror::Error;
+
+/// Signature at the start of every compiled Lua chunk.
+const SIGNATURE: &[u8; 4] = b"\x1BLua";
+
+/// Lua 5.1, the only version supported.
+const VERSION: u8 = 0x51;
+
+/// Deepest nesting of functions read, so that a crafted chunk cannot overflow the stack.
+const MAX_DEPTH: usize = 200;
+
+const TYPE_NIL: u8 = 0;
+const TYPE_BOOLEAN: u8 = 1;
+const TYPE_NUMBER: u8 = 3;
+const TYPE_STRING: u8 = 4;
+
+#[derive(Debug, Error)]
+pub enum LuacError {
+    #[error("Not a compiled Lua chunk")]
+    NotLuac,
+
+    #[error("Unsupported Lua bytecode version {0:#04X}")]
+    UnsupportedVersion(u8),
+
+    #[error("Unsupported Lua bytecode layout: {0}")]
+    UnsupportedLayout(&'static str),
+
+    #[error("Unknown constant type {0} at offset {1}")]
+    UnknownConstant(u8, usize),
+
+    #[error("Functions nested too deeply")]
+    TooDeep,
+
+    #[error("Unexpected end of chunk at offset {0}")]
+    Truncated(usize),
+}
+
+/// Whether `data` starts like a compiled Lua chunk.
+pub fn is_luac(data: &[u8]) -> bool {
+    data.starts_with(SIGNATURE)
+}
+
+/// Every string constant of a compiled Lua 5.1 chunk, in all its functions, in the
+/// order they are stored.
+///
+/// Constants are returned as raw bytes, without the trailing NUL Lua stores.
+pub fn string_constants(data: &[u8]) -> Result<Vec<Vec<u8>>, LuacError> {
+    let mut reader = ChunkReader::new(data)?;
+    let mut strings = Vec::new();
+    reader.function(0, &mut strings)?;
+
+    Ok(strings)
+}
+
+/// Reads a chunk with the layout given by its header.
+struct ChunkReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    big_endian: bool,
+    int_size: usize,
+    size_t_size: usize,
+    instruction_size: usize,
+    number_size: usize,
+}
+
+impl<'a> ChunkReader<'a> {
+    /// Read the chunk's header, leaving the reader at its main function.
+    fn new(data: &'a [u8]) -> Result<Self, LuacError> {
+        if !is_luac(data) {
+            return Err(LuacError::NotLuac);
+        }
+
+        let header = data.get(4..12).ok_or(LuacError::Truncated(data.len()))?;
+        let [
+            version,
+            format,
+            endianness,
+            int_size,
+            size_t_size,
+            instruction_size,
+            number_size,
+            _,
+        ] = header.try_into().unwrap();
+
+        if version != VERSION {
+            return Err(LuacError::UnsupportedVersion(version));
+        }
+        if format != 0 {
+            return Err(LuacError::UnsupportedLayout("non-official format"));
+        }
+
+        let size = |size: u8| match size {
+            1 | 2 | 4 | 8 => Ok(size as usize),
+            _ => Err(LuacError::UnsupportedLayout("type size")),
+        };
+
+        Ok(Self {
+            data,
+            offset: 12,
+            big_endian: endianness == 0,
+            int_size: size(int_size)?,
+            size_t_size: size(size_t_size)?,
+            instruction_size: size(instruction_size)?,
+            number_size: size(number_size)?,
+        })
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], LuacError> {
+        let bytes = self
+            .offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.offset..end))
+            .ok_or(LuacError::Truncated(self.offset))?;
+
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), LuacError> {
+        self.bytes(len).map(|_| ())
+    }
+
+    /// Skip `count` items of `size` bytes each.
+    fn skip_items(&mut self, count: usize, size: usize) -> Result<(), LuacError> {
+        let len = count
+            .checked_mul(size)
+            .ok_or(LuacError::Truncated(self.offset))?;
+        self.skip(len)
+    }
+
+    fn byte(&mut self) -> Result<u8, LuacError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// An unsigned integer of `size` bytes, in the chunk's byte order.
+    fn unsigned(&mut self, size: usize) -> Result<usize, LuacError> {
+        let bytes = self.bytes(size)?;
+        let fold = |value: u64, &byte: &u8| (value << 8) | byte as u64;
+
+        let value = if self.big_endian {
+            bytes.iter().fold(0, fold)
+        } else {
+            bytes.iter().rev().fold(0, fold)
+        };
+
+        usize::try_from(value).map_err(|_| LuacError::Truncated(self.offset))
+    }
+
+    fn int(&mut self) -> Result<usize, LuacError> {
+        self.unsigned(self.int_size)
+    }
+
+    /// A string, without its trailing NUL, or `None` for a null string.
+    fn string(&mut self) -> Result<Option<&'a [u8]>, LuacError> {
+        let len = self.unsigned(self.size_t_size)?;
+        if len == 0 {
+            return Ok(None);
+        }
+
+        let bytes = self.bytes(len)?;
+        Ok(Some(bytes.strip_suffix(&[0]).unwrap_or(bytes)))
+    }
+
+    /// Read a function and its nested functions, collecting their string constants.
+    fn function(&mut self, depth: usize, strings: &mut Vec<Vec<u8>>) -> Result<(), LuacError> {
+        if depth > MAX_DEPTH {
+            return Err(LuacError::TooDeep);
+        }
+
+        // Source name, line defined, last line defined
+        self.string()?;
+        self.skip_items(2, self.int_size)?;
+        // Upvalue count, parameter count, vararg flag, max stack size
+        self.skip(4)?;
+
+        let code = self.int()?;
+        self.skip_items(code, self.instruction_size)?;
+
+        let constants = self.int()?;
+        for _ in 0..constants {
+            let offset = self.offset;
+
+            match self.byte()? {
+                TYPE_NIL => {}
+                TYPE_BOOLEAN => self.skip(1)?,
+                TYPE_NUMBER => self.skip(self.number_size)?,
+                TYPE_STRING => strings.extend(self.string()?.map(<[u8]>::to_vec)),
+                kind => return Err(LuacError::UnknownConstant(kind, offset)),
+            }
+        }
+
+        let functions = self.int()?;
+        for _ in 0..functions {
+            self.function(depth + 1, strings)?;
+        }
+
+        // Debug info: line info, local variables, upvalue names
+        let lines = self.int()?;
+        self.skip_items(lines, self.int_size)?;
+
+        let locals = self.int()?;
+        for _ in 0..locals {
+            self.string()?;
+            self.skip_items(2, self.int_size)?;
+        }
+
+        let upvalues = self.int()?;
+        for _ in 0..upvalues {
+            self.string()?;
+        }
+
+        Ok(())
+    }
+}
@@ -12,9 +12,31 @@
 //! It uses regex patterns to scan file contents for potential original paths,
 //! compute their hashes, and match them against the files in the input folder.
 //!
+//! An archive can also be mapped directly with [`Mapper::run_archive`], scanning its
+//! entries in memory instead of extracting them first.
+//!
+//! References are resolved iteratively: once a file's path is recovered, the paths it
+//! references are also tried relative to its directory, and known file names are
+//! tried next to it, until no new file is recovered.
+//!
+//! Binary files are handled by [`PathExtractor`]s, which pull strings out of formats
+//! the regex patterns cannot read (compiled Lua, UTF-16 text, length-prefixed strings,
+//! MDL models)
+//! before the regex pass runs over the raw content as a fallback.
+//!
+//! Known paths can be kept across runs in a [`HashDictionary`], which seeds the
+//! mapper and receives the paths it discovers.
+//!
+//! Files left unmapped can be brute-forced afterwards with [`MappingResult::recover`],
+//! which runs a [`PreimageSearch`] over their hashes.
+//!
+//! Nothing is printed: progress is reported through a [`MapperObserver`], and
+//! errors on individual files are collected in the [`MappingResult`] rather than
+//! stopping the run.
+//!
 //! # Example
 //!
-//! ```rust
+//! ```rust,no_run
 //! use hdk_archive::mapper::Mapper;
 //! use std::path::PathBuf;
 //!
@@ -24,18 +46,39 @@
 //! let result = Mapper::new(input_folder)
 //!     .with_output_folder(output_folder) // optional
 //!     .with_full(true) // optional, enables slower patterns
-//!     .run();
+//!     .run()
+//!     .unwrap();
 //!
 //! println!("Mapped {} files, {} not found.", result.mapped, result.not_found.len());
 //! ```
 
-use std::{
-    collections::HashMap,
-    path::PathBuf,
-    sync::{RwLock, atomic::AtomicUsize},
-};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use hdk_secure::hash::AfsHash;
+use hdk_secure::hash::preimage::PreimageSearch;
+use thiserror::Error;
+
+use crate::archive::{ArchiveReader, EntryMetadata};
+use crate::time::{TIME_FILE_NAME, TimeFile};
+use crate::utils::relative_path;
+
+use self::extract::Extracted;
+use self::resolve::References;
+
+pub use dictionary::HashDictionary;
+pub use extract::PathExtractor;
+
+pub mod dictionary;
+pub mod extract;
+pub mod luac;
+mod resolve;
+
+#[cfg(test)]
+mod tests;
 
 /// Fast regex patterns always used for finding file paths in UTF-8 data.
 const FAST_PATTERNS: [&str; 4] = [
@@ -58,8 +101,13 @@ const SLOW_PATTERNS: [&str; 1] = [
     // r#"\w+\.(ani|atmos|bar|bin|bnk|cdata|dds|efx|fnt|hkx|lua|luac|mdl|mp3|png|probe|scene|schema|skn|sharc|sho|sql|txt|xml)\b"#,
 ];
 
+/// How many bytes of archive entries are read before scanning them in parallel.
+const SCAN_BATCH_SIZE: usize = 64 * 1024 * 1024;
+
 /// Common scene file extensions to help with mapping variations.
-const SCENE_EXTENSIONS: [&str; 24] = [
+///
+/// Also a good set of extensions for a [`PreimageSearch`] of the files left unmapped.
+pub const SCENE_EXTENSIONS: [&str; 24] = [
     "ani", "atmos", "bar", "bin", "bnk", "cdata", "dds", "efx", "fnt", "hkx", "lua", "luac", "mdl",
     "mp3", "png", "probe", "scene", "schema", "skn", "sharc", "sho", "sql", "txt", "xml",
 ];
@@ -79,10 +127,86 @@ const COMMON_FILES: [&str; 11] = [
     "files.txt",
 ];
 
+/// Errors returned by [`Mapper::run`], or collected in [`MappingResult::errors`]
+/// for errors on individual files.
+#[derive(Debug, Error)]
+pub enum MapperError {
+    #[error("failed to read folder {}: {source}", path.display())]
+    ReadFolder {
+        path: PathBuf,
+        source: walkdir::Error,
+    },
+
+    #[error("invalid pattern {pattern:?}: {source}")]
+    InvalidPattern {
+        pattern: String,
+        source: Box<fancy_regex::Error>,
+    },
+
+    #[error("no input folder to map, use `Mapper::run_archive` to map an archive")]
+    NoInputFolder,
+
+    #[error("cannot derive an output folder from {}", .0.display())]
+    NoOutputFolder(PathBuf),
+
+    #[error("failed to read entry {index}: {source}")]
+    Entry { index: usize, source: io::Error },
+
+    #[error("I/O error on {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+}
+
+/// Receives per-file events while a [`Mapper`] runs.
+///
+/// Every method does nothing by default, so only the interesting ones need to be implemented.
+///
+/// `scanned` and `mapped` are called from the worker threads, in no particular order.
+pub trait MapperObserver: Send + Sync {
+    /// A file was scanned, and `found` candidate paths were extracted from it.
+    fn scanned(&self, _path: &Path, _found: usize) {}
+
+    /// A hashed file was copied to its recovered path.
+    fn mapped(&self, _source: &Path, _destination: &Path) {}
+
+    /// No path was found for a hashed file.
+    fn not_found(&self, _path: &Path, _hash: AfsHash) {}
+
+    /// An error occurred on a single file. The run carries on.
+    fn error(&self, _error: &MapperError) {}
+}
+
+impl MapperObserver for () {}
+
 /// Result returned by `Mapper::run` containing summary information.
+#[derive(Debug, Default)]
 pub struct MappingResult {
+    /// Number of files copied to their recovered path.
     pub mapped: usize,
+
+    /// Hashed files no path was found for.
     pub not_found: Vec<PathBuf>,
+
+    /// Every hash→path mapping known at the end of the run, including paths that do
+    /// not match any input file.
+    pub hashes: HashMap<AfsHash, String>,
+
+    /// Errors on individual files, which were skipped.
+    pub errors: Vec<MapperError>,
+
+    /// The `.time` file written to the output folder.
+    ///
+    /// If `None` after mapping a folder, the input folder had none and the repacked
+    /// archive may fail to mount (SEC error -6 in logs).
+    pub time_file: Option<TimeFile>,
+
+    /// The paths of mapped files that were not in the seed dictionary.
+    ///
+    /// Merge them into a shared dictionary to skip rediscovering them next time.
+    pub discovered: HashDictionary,
+
+    /// Number of rounds spent resolving references against the directories of
+    /// recovered files.
+    pub rounds: usize,
 }
 
 /// Builder for mapping hashed files back to their original paths.
@@ -92,7 +216,7 @@ pub struct MappingResult {
 ///
 /// # Example
 ///
-/// ```rust
+/// ```rust,no_run
 /// use hdk_archive::mapper::Mapper;
 /// use std::path::PathBuf;
 ///
@@ -102,16 +226,21 @@ pub struct MappingResult {
 /// let result = Mapper::new(input_folder)
 ///     .with_output_folder(output_folder) // optional
 ///     .with_full(true) // optional, enables slower patterns
-///     .run();
+///     .run()
+///     .unwrap();
 ///
 /// println!("Mapped {} files, {} not found.", result.mapped, result.not_found.len());
 /// ```
 pub struct Mapper {
-    input_folder: PathBuf,
+    input_folder: Option<PathBuf>,
     output_folder: Option<PathBuf>,
     uuid: Option<String>,
     full: bool,
     extra_files: Vec<PathBuf>,
+    observer: Option<Box<dyn MapperObserver>>,
+    dictionary: Option<HashDictionary>,
+    extractors: Option<Vec<Box<dyn PathExtractor>>>,
+    threads: usize,
 }
 
 impl Mapper {
@@ -126,11 +255,32 @@ impl Mapper {
     /// If not set, defaults to a sibling folder named `{input_folder}_mapped`.
     pub const fn new(input_folder: PathBuf) -> Self {
         Self {
-            input_folder,
+            input_folder: Some(input_folder),
             output_folder: None,
             uuid: None,
             full: false,
             extra_files: Vec::new(),
+            observer: None,
+            dictionary: None,
+            extractors: None,
+            threads: 0,
+        }
+    }
+
+    /// Create a new `Mapper` for an archive, to be run with [`Mapper::run_archive`].
+    ///
+    /// No output folder is needed: without one, only the hash→path mapping is returned.
+    pub const fn for_archive() -> Self {
+        Self {
+            input_folder: None,
+            output_folder: None,
+            uuid: None,
+            full: false,
+            extra_files: Vec::new(),
+            observer: None,
+            dictionary: None,
+            extractors: None,
+            threads: 0,
         }
     }
 
@@ -173,239 +323,675 @@ impl Mapper {
         self
     }
 
+    /// Report per-file events to `observer` while running.
+    pub fn with_observer(mut self, observer: impl MapperObserver + 'static) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Seed the mapping with known paths, in addition to the built-in common files.
+    ///
+    /// Paths under `Objects/{uuid}/` are also tried under this mapper's UUID, if set.
+    /// Calling this multiple times merges the dictionaries.
+    pub fn with_dictionary(mut self, dictionary: HashDictionary) -> Self {
+        match &mut self.dictionary {
+            Some(seed) => {
+                seed.merge(&dictionary);
+            }
+            None => self.dictionary = Some(dictionary),
+        }
+        self
+    }
+
+    /// Run `extractor` on every file, in addition to the ones already set (the
+    /// [default extractors](extract::default_extractors) if none were).
+    pub fn with_extractor(mut self, extractor: impl PathExtractor + 'static) -> Self {
+        self.extractors
+            .get_or_insert_with(extract::default_extractors)
+            .push(Box::new(extractor));
+        self
+    }
+
+    /// Replace the extractors run on every file.
+    ///
+    /// An empty list leaves only the regex patterns.
+    pub fn with_extractors(mut self, extractors: Vec<Box<dyn PathExtractor>>) -> Self {
+        self.extractors = Some(extractors);
+        self
+    }
+
+    /// Set the number of threads used to scan and copy files.
+    ///
+    /// Defaults to `0`, which uses one thread per available CPU. The result does not
+    /// depend on the thread count.
+    pub const fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
     /// Run the mapping process and return a summary `MappingResult`.
-    pub fn run(self) -> MappingResult {
+    ///
+    /// Only failing to list the input folder or to pick an output folder stops the run;
+    /// errors on individual files are collected in [`MappingResult::errors`].
+    ///
+    /// Fails with [`MapperError::NoInputFolder`] if the mapper was created with
+    /// [`Mapper::for_archive`].
+    pub fn run(self) -> Result<MappingResult, MapperError> {
         let Self {
             input_folder,
             output_folder: builder_output,
             uuid,
             full,
             extra_files,
+            observer,
+            dictionary,
+            extractors,
+            threads,
         } = self;
 
+        let input_folder = input_folder.ok_or(MapperError::NoInputFolder)?;
+        let threads = thread_count(threads);
+
+        let observer: &dyn MapperObserver = observer.as_deref().unwrap_or(&());
+        let mut result = MappingResult::default();
+
         // Load all files in the input folder
         let mut paths: Vec<PathBuf> = Vec::new();
 
-        let result = (|| -> Result<(), walkdir::Error> {
-            for entry in walkdir::WalkDir::new(&input_folder).into_iter() {
-                let entry = entry?;
+        for entry in walkdir::WalkDir::new(&input_folder) {
+            let entry = entry.map_err(|source| MapperError::ReadFolder {
+                path: input_folder.clone(),
+                source,
+            })?;
 
-                if !entry.path().is_file() {
-                    continue;
-                }
-
-                paths.push(entry.path().to_path_buf());
+            if entry.path().is_file() {
+                paths.push(entry.into_path());
             }
-
-            Ok(())
-        })();
-
-        if result.is_err() {
-            println!("Failed to read folder {}!", input_folder.display());
-            return MappingResult {
-                mapped: 0,
-                not_found: Vec::new(),
-            };
         }
 
         // Add any extra files provided via builder
         for path in extra_files {
-            if path.exists() && path.is_file() {
-                println!("Reading additional file {}", path.display());
+            if path.is_file() {
                 paths.push(path);
             } else {
-                println!("Could not read additional file {}", path.display());
+                let source = io::Error::new(io::ErrorKind::NotFound, "extra file not found");
+                result.report(observer, MapperError::Io { path, source });
             }
         }
 
-        let file_count = paths.len() as u64;
+        if paths.is_empty() {
+            return Ok(result);
+        }
 
-        if file_count == 0 {
-            println!("No files found in the folder!");
-            return MappingResult {
-                mapped: 0,
-                not_found: Vec::new(),
-            };
+        result.hashes = seed_hashes(dictionary.as_ref(), uuid.as_deref());
+        let compiled_regexes = compile_patterns(full)?;
+        let extractors = extractors.unwrap_or_else(extract::default_extractors);
+        let scanner = Scanner {
+            regexes: &compiled_regexes,
+            extractors: &extractors,
+            uuid: uuid.as_deref(),
+            observer,
+            threads,
+        };
+        let mut references = References::new();
+
+        let scans = parallel_map(&paths, scanner.threads, |path| {
+            let buf = std::fs::read(path)?;
+            Ok(scanner.scan(path, &buf))
+        });
+
+        // Merge in file order, so that the result does not depend on scheduling
+        for (path, scanned) in paths.iter().zip(scans) {
+            match scanned {
+                Ok((found, local_matches)) => {
+                    result.hashes.extend(local_matches);
+                    references.add(file_hash(path), found);
+                }
+                Err(source) => {
+                    let path = path.clone();
+                    result.report(observer, MapperError::Io { path, source });
+                }
+            }
         }
 
-        let hashes = RwLock::new(HashMap::new());
+        let targets: HashSet<AfsHash> = paths.iter().filter_map(|path| file_hash(path)).collect();
+        result.rounds = references.resolve(&mut result.hashes, &targets);
+
+        // Prepare output folder
+        let output_folder = match builder_output {
+            Some(output) => output,
+            None => default_output_folder(&input_folder)
+                .ok_or_else(|| MapperError::NoOutputFolder(input_folder.clone()))?,
+        };
 
-        // insert static hashes for files that do not get referenced (therefore can't be detected)
-        {
-            for file_path in COMMON_FILES.iter() {
-                let path = PathBuf::from(file_path);
-                let hash = AfsHash::new_from_path(&path);
+        // Work out where each hashed file goes
+        let mut copies = Vec::new();
 
-                hashes.write().unwrap().insert(hash, path);
-            }
+        for path in &paths {
+            let Some(hash) = file_hash(path) else {
+                continue;
+            };
 
-            if let Some(uuid) = &uuid {
-                for file_path in COMMON_FILES.iter() {
-                    let path = PathBuf::from("Objects").join(uuid).join(file_path);
-                    let hash = AfsHash::new_from_path(&path);
+            let Some(recovered_path) = result.hashes.get(&hash).cloned() else {
+                observer.not_found(path, hash);
+                result.not_found.push(path.clone());
+                continue;
+            };
 
-                    hashes.write().unwrap().insert(hash, path);
+            match relative_path(&recovered_path) {
+                Ok(relative) => {
+                    copies.push((path, hash, recovered_path, output_folder.join(relative)))
+                }
+                Err(source) => {
+                    let path = path.clone();
+                    result.report(observer, MapperError::Io { path, source });
                 }
             }
         }
 
-        // Always scan fast patterns, optionally scan slow patterns
-        let mut patterns = FAST_PATTERNS
-            .iter()
-            .map(|&x| x.to_string())
-            .collect::<Vec<String>>();
+        // Actually write the mapped files
+        let copied = parallel_map(&copies, scanner.threads, |(path, _, _, output_path)| {
+            // Note: apparently moving a file DOES NOT take less time than copying it..?
+            copy_file(path, output_path)?;
+            observer.mapped(path, output_path);
+            Ok(())
+        });
+
+        for ((_, hash, recovered_path, output_path), copied) in copies.into_iter().zip(copied) {
+            if let Err(source) = copied {
+                let path = output_path;
+                result.report(observer, MapperError::Io { path, source });
+                continue;
+            }
 
-        if full {
-            patterns.extend(SLOW_PATTERNS.iter().map(|&x| x.to_string()));
+            result.mapped += 1;
+
+            if !dictionary.as_ref().is_some_and(|seed| seed.contains(hash)) {
+                result.discovered.insert(recovered_path);
+            }
         }
 
-        // Precompile regexes for better performance
-        let compiled_regexes: Vec<fancy_regex::Regex> = patterns
-            .iter()
-            .map(|pattern| {
-                fancy_regex::RegexBuilder::new(pattern)
-                    .backtrack_limit(usize::MAX)
-                    .build()
-                    .unwrap()
-            })
-            .collect();
+        // Copy .time file
+        match TimeFile::read(&input_folder) {
+            Ok(Some(time_file)) => {
+                let written = std::fs::create_dir_all(&output_folder)
+                    .and_then(|()| time_file.write(&output_folder));
+
+                match written {
+                    Ok(()) => result.time_file = Some(time_file),
+                    Err(source) => {
+                        let path = output_folder.join(TIME_FILE_NAME);
+                        result.report(observer, MapperError::Io { path, source });
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(source) => {
+                let path = input_folder.join(TIME_FILE_NAME);
+                result.report(observer, MapperError::Io { path, source });
+            }
+        }
 
-        // Use a shared map to accumulate found mappings
-        let result_hashes: RwLock<_> = RwLock::new(HashMap::new());
+        Ok(result)
+    }
 
-        for path in &paths {
-            let buf: Vec<u8> = match std::fs::read(path) {
-                Ok(content) => content,
-                Err(_) => continue,
+    /// Map the entries of `archive` without extracting it first.
+    ///
+    /// Entries (and extra files) are scanned in memory. If an output folder is set,
+    /// entries whose path was recovered are written straight to it along with the
+    /// `.time` file; otherwise only the hash→path mapping is returned.
+    ///
+    /// Entries are reported to the observer and in [`MappingResult::not_found`] by their
+    /// `{HASH}` file name. The input folder, if any, is ignored.
+    pub fn run_archive<A: ArchiveReader>(
+        self,
+        archive: &mut A,
+    ) -> Result<MappingResult, MapperError> {
+        let Self {
+            output_folder,
+            uuid,
+            full,
+            extra_files,
+            observer,
+            dictionary,
+            extractors,
+            threads,
+            ..
+        } = self;
+
+        let threads = thread_count(threads);
+        let observer: &dyn MapperObserver = observer.as_deref().unwrap_or(&());
+        let mut result = MappingResult {
+            hashes: seed_hashes(dictionary.as_ref(), uuid.as_deref()),
+            ..Default::default()
+        };
+        let compiled_regexes = compile_patterns(full)?;
+        let extractors = extractors.unwrap_or_else(extract::default_extractors);
+        let scanner = Scanner {
+            regexes: &compiled_regexes,
+            extractors: &extractors,
+            uuid: uuid.as_deref(),
+            observer,
+            threads,
+        };
+        let mut references = References::new();
+
+        // Scan every entry in memory, reading them in batches that are scanned in parallel
+        let mut entries = Vec::with_capacity(archive.entry_count());
+        let mut batch = Vec::new();
+        let mut batch_size = 0;
+
+        for index in 0..archive.entry_count() {
+            let mut buf = Vec::new();
+            let name_hash = match read_entry(archive, index, &mut buf) {
+                Ok(name_hash) => name_hash,
+                Err(source) => {
+                    result.report(observer, MapperError::Entry { index, source });
+                    continue;
+                }
             };
 
-            let data_str = String::from_utf8_lossy(&buf);
+            batch_size += buf.len();
+            batch.push((index, name_hash, buf));
 
-            let mut local_matches = HashMap::new();
+            if batch_size >= SCAN_BATCH_SIZE {
+                let batch = std::mem::take(&mut batch);
+                scanner.scan_entries(batch, &mut result, &mut references, &mut entries);
+                batch_size = 0;
+            }
+        }
 
-            for regex in &compiled_regexes {
-                let matches: Vec<_> = regex.find_iter(&data_str).filter_map(|m| m.ok()).collect();
+        scanner.scan_entries(batch, &mut result, &mut references, &mut entries);
 
-                for m in matches {
-                    let mut path_str = m
-                        .as_str()
-                        .to_lowercase()
-                        .replace("\\", "/")
-                        .replace("file:///resource_root/build/", "")
-                        .replace("file://resource_root/build/", "");
+        for path in extra_files {
+            match std::fs::read(&path) {
+                Ok(buf) => {
+                    let (found, local_matches) = scanner.scan(&path, &buf);
+                    result.hashes.extend(local_matches);
+                    references.add(None, found);
+                }
+                Err(source) => result.report(observer, MapperError::Io { path, source }),
+            }
+        }
 
-                    if let Some(ext) = SCENE_EXTENSIONS.iter().find(|&ext| path_str.ends_with(ext))
-                    {
-                        for extension in SCENE_EXTENSIONS.iter() {
-                            let scene_val = path_str.replace(ext, extension);
-                            let hashed_val = AfsHash::new_from_str(&scene_val);
-                            local_matches.insert(hashed_val, scene_val);
-                        }
-                    }
+        let targets: HashSet<AfsHash> = entries.iter().map(|&(_, hash)| hash).collect();
+        result.rounds = references.resolve(&mut result.hashes, &targets);
+
+        if let Some(output_folder) = &output_folder {
+            std::fs::create_dir_all(output_folder).map_err(|source| MapperError::Io {
+                path: output_folder.clone(),
+                source,
+            })?;
+
+            let time_file = TimeFile::from_archive(archive);
+            match time_file.write(output_folder) {
+                Ok(()) => result.time_file = Some(time_file),
+                Err(source) => {
+                    let path = output_folder.join(TIME_FILE_NAME);
+                    result.report(observer, MapperError::Io { path, source });
+                }
+            }
+        }
+
+        // Write the mapped entries, or just count them
+        for (index, name_hash) in entries {
+            let source_path = PathBuf::from(name_hash.to_string());
 
-                    if let Some(uuid) = &uuid {
-                        path_str = format!("Objects/{uuid}/{path_str}");
+            let Some(recovered_path) = result.hashes.get(&name_hash).cloned() else {
+                observer.not_found(&source_path, name_hash);
+                result.not_found.push(source_path);
+                continue;
+            };
+
+            let relative = match relative_path(&recovered_path) {
+                Ok(relative) => relative,
+                Err(source) => {
+                    result.report(observer, MapperError::Entry { index, source });
+                    continue;
+                }
+            };
+
+            let output_path = match &output_folder {
+                Some(output_folder) => {
+                    let output_path = output_folder.join(relative);
+
+                    if let Err(source) = extract_entry(archive, index, &output_path) {
+                        let path = output_path;
+                        result.report(observer, MapperError::Io { path, source });
+                        continue;
                     }
 
-                    let hash = AfsHash::new_from_str(&path_str);
-                    local_matches.insert(hash, path_str);
+                    output_path
                 }
-            }
+                None => relative,
+            };
 
+            observer.mapped(&source_path, &output_path);
+            result.mapped += 1;
+
+            if !dictionary
+                .as_ref()
+                .is_some_and(|seed| seed.contains(name_hash))
             {
-                let mut shared_hashes = result_hashes.write().unwrap();
-                for (key, value) in local_matches {
-                    shared_hashes.insert(key, value);
-                }
+                result.discovered.insert(recovered_path);
             }
         }
 
-        // Merge the results into the main hashes map
-        {
-            let mut main_hashes = hashes.write().unwrap();
-            let shared_results = result_hashes.read().unwrap();
+        Ok(result)
+    }
+}
+
+impl MappingResult {
+    /// Search for the paths of the files left in [`not_found`](Self::not_found).
+    ///
+    /// Files whose path is found are moved out of `not_found`, and their path is added
+    /// to [`hashes`](Self::hashes) and [`discovered`](Self::discovered). If a hash has
+    /// several matches, the first in the search's order is kept.
+    ///
+    /// Returns the recovered files with their path. They are not copied: copy them to
+    /// the output folder, or run the mapper again seeded with `discovered`.
+    pub fn recover(&mut self, search: &PreimageSearch) -> Vec<(PathBuf, String)> {
+        let targets: Vec<AfsHash> = self
+            .not_found
+            .iter()
+            .filter_map(|path| file_hash(path))
+            .collect();
+
+        let mut paths: HashMap<AfsHash, String> = HashMap::new();
+        for (hash, path) in search.search(&targets) {
+            paths.entry(hash).or_insert(path);
+        }
+
+        let mut recovered = Vec::new();
+        self.not_found.retain(|file| {
+            file_hash(file)
+                .and_then(|hash| paths.get(&hash))
+                .is_none_or(|path| {
+                    recovered.push((file.clone(), path.clone()));
+                    false
+                })
+        });
 
-            for (k, v) in shared_results.iter() {
-                main_hashes.insert(*k, v.into());
-            }
+        for (_, path) in &recovered {
+            self.hashes
+                .insert(AfsHash::new_from_str(path), path.clone());
+            self.discovered.insert(path.clone());
         }
 
-        // Check if we found any hashes
-        if hashes.read().unwrap().is_empty() {
-            println!("No mappings found!");
-            return MappingResult {
-                mapped: 0,
-                not_found: Vec::new(),
-            };
+        recovered
+    }
+
+    /// Record an error on a single file.
+    fn report(&mut self, observer: &dyn MapperObserver, error: MapperError) {
+        observer.error(&error);
+        self.errors.push(error);
+    }
+}
+
+/// Static hashes for files that do not get referenced (therefore can't be detected),
+/// and those of the seed dictionary.
+fn seed_hashes(
+    dictionary: Option<&HashDictionary>,
+    uuid: Option<&str>,
+) -> HashMap<AfsHash, String> {
+    let mut hashes = dictionary
+        .map(|dictionary| dictionary.with_uuid(uuid))
+        .unwrap_or_default();
+
+    for file_path in COMMON_FILES.iter() {
+        hashes.insert(AfsHash::new_from_str(file_path), file_path.to_string());
+
+        if let Some(uuid) = uuid {
+            let path = format!("Objects/{uuid}/{file_path}");
+            hashes.insert(AfsHash::new_from_str(&path), path);
         }
+    }
 
-        // Prepare output folder
-        let output_folder = builder_output.unwrap_or_else(|| {
-            let mut output = input_folder.clone();
-            let folder_name = input_folder.file_name().unwrap().to_str().unwrap();
+    hashes
+}
+
+/// Compile the fast patterns, and the slow ones in full mode.
+fn compile_patterns(full: bool) -> Result<Vec<fancy_regex::Regex>, MapperError> {
+    // Always scan fast patterns, optionally scan slow patterns
+    let mut patterns = FAST_PATTERNS.to_vec();
+
+    if full {
+        patterns.extend(SLOW_PATTERNS);
+    }
+
+    // Precompile regexes for better performance
+    patterns
+        .iter()
+        .map(|&pattern| {
+            fancy_regex::RegexBuilder::new(pattern)
+                .backtrack_limit(usize::MAX)
+                .build()
+                .map_err(|source| MapperError::InvalidPattern {
+                    pattern: pattern.to_string(),
+                    source: Box::new(source),
+                })
+        })
+        .collect()
+}
 
-            output = output
-                .parent()
-                .unwrap()
-                .join(format!("{folder_name}_mapped"));
+/// Scans file contents for path references.
+struct Scanner<'a> {
+    regexes: &'a [fancy_regex::Regex],
+    extractors: &'a [Box<dyn PathExtractor>],
+    uuid: Option<&'a str>,
+    observer: &'a dyn MapperObserver,
+    threads: usize,
+}
+
+impl Scanner<'_> {
+    /// Scan a file's content, returning its references and their candidate paths.
+    fn scan(&self, path: &Path, buf: &[u8]) -> (Vec<String>, HashMap<AfsHash, String>) {
+        let found = scan(buf, self.regexes, self.extractors);
+        let local_matches = candidates(&found, self.uuid);
+        self.observer.scanned(path, local_matches.len());
+
+        (found, local_matches)
+    }
 
-            output
+    /// Scan a batch of archive entries in parallel, merging the results in entry order.
+    fn scan_entries(
+        &self,
+        batch: Vec<(usize, AfsHash, Vec<u8>)>,
+        result: &mut MappingResult,
+        references: &mut References,
+        entries: &mut Vec<(usize, AfsHash)>,
+    ) {
+        let scans = parallel_map(&batch, self.threads, |(_, name_hash, buf)| {
+            self.scan(Path::new(&name_hash.to_string()), buf)
         });
 
-        // Actually write the mapped files
-        let found: AtomicUsize = AtomicUsize::new(0);
-        let not_found = RwLock::new(Vec::new());
+        for ((index, name_hash, _), (found, local_matches)) in batch.into_iter().zip(scans) {
+            result.hashes.extend(local_matches);
+            references.add(Some(name_hash), found);
+            entries.push((index, name_hash));
+        }
+    }
+}
 
-        for path in &paths {
-            let hash_str = path.file_name().unwrap().to_str().unwrap().to_owned();
-            if !AfsHash::is_valid_hash_str(&hash_str) {
-                continue;
-            }
+/// Run `f` on every item across `threads` threads, returning the results in item order.
+fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    threads: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let threads = threads.min(items.len());
+    if threads <= 1 {
+        return items.iter().map(f).collect();
+    }
 
-            let hash = AfsHash::new_from_str(&hash_str);
-            let recovered_path = {
-                let rw = hashes.read().unwrap();
-                match rw.get(&hash) {
-                    Some(p) => p.clone(),
-                    None => {
-                        println!("[!] Could not find mapping for file {}", path.display());
-                        not_found.write().unwrap().push(path.clone());
-                        continue;
+    let next = AtomicUsize::new(0);
+
+    let mut results: Vec<(usize, R)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(index) else {
+                            break;
+                        };
+
+                        results.push((index, f(item)));
                     }
-                }
-            };
 
-            let relative_path = input_folder.join(hash.to_string());
+                    results
+                })
+            })
+            .collect();
 
-            let output_path = output_folder.join(recovered_path);
-            std::fs::create_dir_all(output_path.parent().unwrap()).unwrap();
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
 
-            // Note: apparently moving a file DOES NOT take less time than copying it..?
-            std::fs::copy(&relative_path, &output_path).unwrap();
+    results.sort_unstable_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
 
-            found.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        }
+/// The number of threads to use for a requested count of `0` (automatic) or more.
+fn thread_count(threads: usize) -> usize {
+    match threads {
+        0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+        threads => threads,
+    }
+}
 
-        let found = found.load(std::sync::atomic::Ordering::Relaxed);
-        println!("Mapped {} files!", found);
+/// Extract the path references in a file's content, normalised to lowercase and
+/// forward slashes.
+///
+/// The extractors run first; the regex patterns then search the text they extracted
+/// as well as the raw content.
+fn scan(
+    buf: &[u8],
+    regexes: &[fancy_regex::Regex],
+    extractors: &[Box<dyn PathExtractor>],
+) -> Vec<String> {
+    let mut extracted = Extracted::default();
+    for extractor in extractors {
+        extractor.extract(buf, &mut extracted);
+    }
 
-        let not_found_len = not_found.read().unwrap().len();
-        if not_found_len > 0 {
-            println!("Could not find mappings for {} files!", not_found_len);
-        }
+    let mut references: Vec<String> = extracted.paths.iter().map(|path| normalize(path)).collect();
 
-        // Copy .time file
-        let time_file = input_folder.join(".time");
-        if time_file.is_file() {
-            let output_time_file = output_folder.join(".time");
-            std::fs::copy(time_file, output_time_file).unwrap();
-        } else {
-            println!("No .time file found. Archive may fail to mount! (SEC error -6 in logs)");
+    for text in extracted.text.iter().map(String::as_str) {
+        find_references(text, regexes, &mut references);
+    }
+    find_references(&String::from_utf8_lossy(buf), regexes, &mut references);
+
+    references.sort_unstable();
+    references.dedup();
+    references
+}
+
+/// Add the matches of the regex patterns in `text` to `references`.
+fn find_references(text: &str, regexes: &[fancy_regex::Regex], references: &mut Vec<String>) {
+    for regex in regexes {
+        for m in regex.find_iter(text).filter_map(|m| m.ok()) {
+            references.push(normalize(m.as_str()));
         }
+    }
+}
 
-        MappingResult {
-            mapped: found,
-            not_found: not_found.into_inner().unwrap(),
+/// Normalise a path reference to lowercase and forward slashes, without resource
+/// root prefix.
+fn normalize(reference: &str) -> String {
+    reference
+        .to_lowercase()
+        .replace("\\", "/")
+        .replace("file:///resource_root/build/", "")
+        .replace("file://resource_root/build/", "")
+}
+
+/// The candidate paths for references found in a file whose location is unknown,
+/// keyed by their hash.
+fn candidates(references: &[String], uuid: Option<&str>) -> HashMap<AfsHash, String> {
+    let mut local_matches = HashMap::new();
+
+    for path_str in references {
+        for scene_val in scene_variants(path_str) {
+            local_matches.insert(AfsHash::new_from_str(&scene_val), scene_val);
         }
+
+        let path_str = uuid.map_or_else(
+            || path_str.clone(),
+            |uuid| format!("Objects/{uuid}/{path_str}"),
+        );
+
+        local_matches.insert(AfsHash::new_from_str(&path_str), path_str);
+    }
+
+    local_matches
+}
+
+/// The path with each of the scene extensions, if it ends with one.
+fn scene_variants(path: &str) -> Vec<String> {
+    SCENE_EXTENSIONS
+        .iter()
+        .find(|&ext| path.ends_with(ext))
+        .map_or_else(Vec::new, |ext| {
+            SCENE_EXTENSIONS
+                .iter()
+                .map(|extension| path.replace(ext, extension))
+                .collect()
+        })
+}
+
+/// The hash a file is named by, if any.
+fn file_hash(path: &Path) -> Option<AfsHash> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(AfsHash::from_hash_str)
+}
+
+/// The sibling folder named `{input_folder}_mapped`.
+fn default_output_folder(input_folder: &Path) -> Option<PathBuf> {
+    let folder_name = input_folder.file_name()?.to_str()?;
+
+    Some(input_folder.parent()?.join(format!("{folder_name}_mapped")))
+}
+
+/// Read an entry's content into `buf`, returning its name hash.
+fn read_entry<A: ArchiveReader>(
+    archive: &mut A,
+    index: usize,
+    buf: &mut Vec<u8>,
+) -> io::Result<AfsHash> {
+    let name_hash = archive.entry_metadata(index)?.name_hash();
+    archive.entry_reader(index)?.read_to_end(buf)?;
+
+    Ok(name_hash)
+}
+
+/// Write an entry to `to`, creating its parent folders.
+fn extract_entry<A: ArchiveReader>(archive: &mut A, index: usize, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
     }
+
+    let mut writer = BufWriter::new(File::create(to)?);
+    io::copy(&mut archive.entry_reader(index)?, &mut writer)?;
+    writer.into_inner().map_err(|e| e.into_error())?;
+
+    Ok(())
+}
+
+/// Copy a file, creating the destination's parent folders.
+fn copy_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::copy(from, to).map(|_| ())
 }
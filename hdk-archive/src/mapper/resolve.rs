@@ -0,0 +1,128 @@
+//! Iterative resolution of path references against the directories of mapped files.
+//!
+//! A single scan hashes references as they appear, but many are relative to the
+//! file they were found in (e.g. textures referenced by a `.scene`). Once a file's
+//! path is recovered, its references are joined to its directory, and every known
+//! file name is tried in that directory. Files recovered that way are processed in
+//! turn, until a round recovers nothing new.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use hdk_secure::hash::AfsHash;
+
+use super::{COMMON_FILES, scene_variants};
+
+/// The path references found in scanned files.
+#[derive(Debug, Default)]
+pub(super) struct References {
+    /// References found in hashed files, by the file's hash.
+    by_file: HashMap<AfsHash, Vec<String>>,
+
+    /// Every file name referenced anywhere, tried in each recovered directory.
+    ///
+    /// Ordered, like the frontier, so that which of two paths with the same hash is
+    /// kept does not depend on hashing.
+    names: BTreeSet<String>,
+}
+
+impl References {
+    pub(super) fn new() -> Self {
+        Self {
+            by_file: HashMap::new(),
+            names: COMMON_FILES.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    /// Record the references found in a file, which has a hash if it is one of the
+    /// files being mapped.
+    pub(super) fn add(&mut self, file: Option<AfsHash>, references: Vec<String>) {
+        for reference in &references {
+            if let Some(name) = reference.rsplit('/').next().filter(|name| !name.is_empty()) {
+                self.names.insert(name.to_string());
+            }
+        }
+
+        if let Some(file) = file {
+            self.by_file.entry(file).or_default().extend(references);
+        }
+    }
+
+    /// Resolve references against the directories of recovered `targets` until no new
+    /// target is recovered, adding every candidate path to `hashes`.
+    ///
+    /// Returns the number of rounds run.
+    pub(super) fn resolve(
+        &self,
+        hashes: &mut HashMap<AfsHash, String>,
+        targets: &HashSet<AfsHash>,
+    ) -> usize {
+        let mut resolved: HashSet<AfsHash> = targets
+            .iter()
+            .filter(|hash| hashes.contains_key(hash))
+            .copied()
+            .collect();
+        let mut frontier: Vec<AfsHash> = resolved.iter().copied().collect();
+        frontier.sort_unstable_by_key(|hash| hash.0);
+        let mut visited_dirs: HashSet<String> = HashSet::new();
+        let mut rounds = 0;
+
+        while !frontier.is_empty() {
+            rounds += 1;
+
+            let mut candidates = Vec::new();
+
+            for hash in std::mem::take(&mut frontier) {
+                let dir = parent_dir(&hashes[&hash]).to_string();
+
+                for reference in self.by_file.get(&hash).into_iter().flatten() {
+                    if let Some(joined) = join(&dir, reference) {
+                        candidates.extend(scene_variants(&joined));
+                        candidates.push(joined);
+                    }
+                }
+
+                // Siblings only need to be tried once per directory
+                if visited_dirs.insert(dir.to_lowercase()) {
+                    candidates.extend(self.names.iter().filter_map(|name| join(&dir, name)));
+                }
+            }
+
+            for candidate in candidates {
+                let hash = AfsHash::new_from_str(&candidate);
+                hashes.entry(hash).or_insert(candidate);
+
+                if targets.contains(&hash) && resolved.insert(hash) {
+                    frontier.push(hash);
+                }
+            }
+
+            frontier.sort_unstable_by_key(|hash| hash.0);
+        }
+
+        rounds
+    }
+}
+
+/// The directory part of a path, without trailing separator.
+fn parent_dir(path: &str) -> &str {
+    path.rfind(['/', '\\']).map_or("", |end| &path[..end])
+}
+
+/// Join a relative reference to a directory, resolving `.` and `..` segments.
+///
+/// Returns `None` if the reference climbs above the root.
+fn join(dir: &str, reference: &str) -> Option<String> {
+    let mut parts: Vec<&str> = dir.split(['/', '\\']).filter(|p| !p.is_empty()).collect();
+
+    for part in reference.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
@@ -0,0 +1,746 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use hdk_secure::hash::AfsHash;
+use hdk_secure::hash::preimage::PreimageSearch;
+
+use crate::bar::BarReader;
+use crate::mapper::extract::{
+    Extracted, LengthPrefixedExtractor, LuacExtractor, MdlExtractor, PathExtractor, Utf16Extractor,
+};
+use crate::mapper::luac::{self, LuacError};
+use crate::mapper::{HashDictionary, Mapper, MapperError, MapperObserver, SCENE_EXTENSIONS};
+use crate::structs::CompressionType;
+use crate::test_utils::{TestEntry, create_bar, open_bar};
+use crate::time::TimeFile;
+use crate::unpack::unpack_to_directory;
+
+const UUID: &str = "0A1B2C3D-11111111-22222222-33333333";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Event {
+    Scanned(PathBuf),
+    Mapped(PathBuf, PathBuf),
+    NotFound(AfsHash),
+    Error,
+}
+
+#[derive(Default, Clone)]
+struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+impl Recorder {
+    fn events(&self) -> Vec<Event> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl MapperObserver for Recorder {
+    fn scanned(&self, path: &Path, _found: usize) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(Event::Scanned(path.to_path_buf()));
+    }
+
+    fn mapped(&self, source: &Path, destination: &Path) {
+        self.0.lock().unwrap().push(Event::Mapped(
+            source.to_path_buf(),
+            destination.to_path_buf(),
+        ));
+    }
+
+    fn not_found(&self, _path: &Path, hash: AfsHash) {
+        self.0.lock().unwrap().push(Event::NotFound(hash));
+    }
+
+    fn error(&self, _error: &MapperError) {
+        self.0.lock().unwrap().push(Event::Error);
+    }
+}
+
+fn write_hashed(dir: &Path, path: &str, content: &[u8]) -> PathBuf {
+    let file = dir.join(AfsHash::new_from_str(path).to_string());
+    std::fs::write(&file, content).unwrap();
+    file
+}
+
+#[test]
+fn maps_referenced_and_common_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("hashed");
+    let output = dir.path().join("mapped");
+    std::fs::create_dir(&input).unwrap();
+
+    let prefix = format!("Objects/{UUID}");
+    let object = write_hashed(
+        &input,
+        &format!("{prefix}/object.xml"),
+        b"<script file=\"scripts/main.lua\"/>",
+    );
+    let script = write_hashed(&input, &format!("{prefix}/scripts/main.lua"), b"print()");
+    let unknown = write_hashed(&input, "nowhere/unknown.bin", b"???");
+    TimeFile::new(0x4F2A_1B3C).write(&input).unwrap();
+
+    let recorder = Recorder::default();
+    let result = Mapper::new(input)
+        .with_output_folder(output.clone())
+        .with_uuid(UUID)
+        .with_observer(recorder.clone())
+        .run()
+        .unwrap();
+
+    assert_eq!(result.mapped, 2);
+    assert_eq!(result.not_found, [unknown]);
+    assert!(result.errors.is_empty());
+    assert_eq!(result.time_file, Some(TimeFile::new(0x4F2A_1B3C)));
+
+    let script_path = format!("{prefix}/scripts/main.lua");
+    assert_eq!(
+        result.hashes.get(&AfsHash::new_from_str(&script_path)),
+        Some(&script_path)
+    );
+
+    assert_eq!(
+        std::fs::read(output.join(&script_path)).unwrap(),
+        b"print()"
+    );
+    assert!(output.join(&prefix).join("object.xml").is_file());
+    assert!(output.join(".time").is_file());
+
+    let events = recorder.events();
+    assert!(events.contains(&Event::Scanned(object)));
+    assert!(events.contains(&Event::Mapped(script, output.join(&script_path))));
+    assert!(events.contains(&Event::NotFound(AfsHash::new_from_str(
+        "nowhere/unknown.bin"
+    ))));
+    assert!(!events.contains(&Event::Error));
+}
+
+#[test]
+fn collects_per_file_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("hashed");
+    std::fs::create_dir(&input).unwrap();
+    write_hashed(&input, "object.xml", b"<object/>");
+
+    let recorder = Recorder::default();
+    let result = Mapper::new(input)
+        .with_extra_file(dir.path().join("missing.bin"))
+        .with_observer(recorder.clone())
+        .run()
+        .unwrap();
+
+    // The missing extra file is reported, the rest of the run goes on
+    assert_eq!(result.mapped, 1);
+    assert!(matches!(
+        result.errors.as_slice(),
+        [MapperError::Io { path, .. }] if path == &dir.path().join("missing.bin")
+    ));
+    assert_eq!(
+        recorder
+            .events()
+            .iter()
+            .filter(|&event| event == &Event::Error)
+            .count(),
+        1
+    );
+    assert_eq!(result.time_file, None);
+
+    // Without an output folder, a sibling folder is used
+    assert!(dir.path().join("hashed_mapped/object.xml").is_file());
+}
+
+#[test]
+fn fails_on_missing_input_folder() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let result = Mapper::new(dir.path().join("missing")).run();
+
+    assert!(matches!(result, Err(MapperError::ReadFolder { .. })));
+}
+
+/// A BAR holding `entries`, all encrypted.
+fn open_encrypted_bar(entries: &[(&str, &[u8])]) -> BarReader<Cursor<Vec<u8>>> {
+    let entries: Vec<TestEntry> = entries
+        .iter()
+        .map(|&(path, content)| (path, CompressionType::Encrypted, content))
+        .collect();
+
+    open_bar(create_bar(&entries))
+}
+
+#[test]
+fn maps_archive_in_memory() {
+    let mut archive = open_encrypted_bar(&[
+        ("object.xml", b"<script file=\"scripts/main.lua\"/>"),
+        ("scripts/main.lua", b"print()"),
+        ("nowhere/unknown.bin", b"???"),
+    ]);
+
+    let recorder = Recorder::default();
+    let result = Mapper::for_archive()
+        .with_observer(recorder.clone())
+        .run_archive(&mut archive)
+        .unwrap();
+
+    assert_eq!(result.mapped, 2);
+    let unknown = AfsHash::new_from_str("nowhere/unknown.bin");
+    assert_eq!(result.not_found, [PathBuf::from(unknown.to_string())]);
+    assert_eq!(
+        result
+            .hashes
+            .get(&AfsHash::new_from_str("scripts/main.lua")),
+        Some(&"scripts/main.lua".to_string())
+    );
+    // Nothing is written without an output folder
+    assert_eq!(result.time_file, None);
+
+    let script = AfsHash::new_from_str("scripts/main.lua");
+    let events = recorder.events();
+    assert!(events.contains(&Event::Scanned(PathBuf::from(script.to_string()))));
+    assert!(events.contains(&Event::Mapped(
+        PathBuf::from(script.to_string()),
+        Path::new("scripts").join("main.lua")
+    )));
+    assert!(events.contains(&Event::NotFound(unknown)));
+}
+
+#[test]
+fn maps_archive_straight_to_output_folder() {
+    let mut archive = open_encrypted_bar(&[
+        ("object.xml", b"<script file=\"scripts/main.lua\"/>"),
+        ("scripts/main.lua", b"print()"),
+        ("nowhere/unknown.bin", b"???"),
+    ]);
+    let dir = tempfile::tempdir().unwrap();
+
+    let result = Mapper::for_archive()
+        .with_output_folder(dir.path().to_path_buf())
+        .run_archive(&mut archive)
+        .unwrap();
+
+    assert_eq!(result.mapped, 2);
+    assert!(result.errors.is_empty());
+    assert_eq!(result.time_file, Some(TimeFile::new(0x4F2A_1B3C)));
+
+    assert_eq!(
+        std::fs::read(dir.path().join("scripts/main.lua")).unwrap(),
+        b"print()"
+    );
+    assert!(dir.path().join("object.xml").is_file());
+    assert_eq!(
+        TimeFile::read(dir.path()).unwrap(),
+        Some(TimeFile::new(0x4F2A_1B3C))
+    );
+
+    // Unmapped entries are not written
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+}
+
+#[test]
+fn run_requires_an_input_folder() {
+    assert!(matches!(
+        Mapper::for_archive().run(),
+        Err(MapperError::NoInputFolder)
+    ));
+}
+
+#[test]
+fn dictionary_roundtrip() {
+    let mut dictionary: HashDictionary = [
+        "scripts/main.lua",
+        "Objects/0A1B2C3D-11111111-22222222-33333333/object.xml",
+    ]
+    .into_iter()
+    .collect();
+    let hash = dictionary.insert("Textures/Wall.dds");
+
+    assert_eq!(dictionary.len(), 3);
+    assert_eq!(dictionary.get(hash), Some("Textures/Wall.dds"));
+    // Paths differing only by case or separators have the same hash
+    assert_eq!(dictionary.insert("textures\\wall.DDS"), hash);
+    assert_eq!(dictionary.get(hash), Some("Textures/Wall.dds"));
+
+    let mut text = Vec::new();
+    dictionary.write_to(&mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines.is_sorted());
+    assert!(lines.contains(&format!("{hash}\tTextures/Wall.dds").as_str()));
+
+    assert_eq!(HashDictionary::parse(&text).unwrap(), dictionary);
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("paths.txt");
+    dictionary.save(&file).unwrap();
+    assert_eq!(HashDictionary::load(&file).unwrap(), dictionary);
+}
+
+#[test]
+fn dictionary_parse() {
+    let hash = AfsHash::new_from_str("object.xml");
+    let dictionary = HashDictionary::parse(&format!(
+        "# known paths\n\n{hash}\tobject.xml\nscripts/main.lua\n"
+    ))
+    .unwrap();
+
+    assert_eq!(dictionary.len(), 2);
+    assert_eq!(dictionary.get(hash), Some("object.xml"));
+    assert!(dictionary.contains(AfsHash::new_from_str("scripts/main.lua")));
+
+    assert!(HashDictionary::parse("F00DCAFE\tobject.xml").is_err());
+    assert!(HashDictionary::parse("nothex\tobject.xml").is_err());
+}
+
+#[test]
+fn dictionary_merge() {
+    let mut first: HashDictionary = ["object.xml", "scripts/main.lua"].into_iter().collect();
+    let second: HashDictionary = ["OBJECT.XML", "textures/wall.dds"].into_iter().collect();
+
+    assert_eq!(first.merge(&second), 1);
+    assert_eq!(first.len(), 3);
+    // Known entries are kept as they were
+    assert_eq!(
+        first.get(AfsHash::new_from_str("object.xml")),
+        Some("object.xml")
+    );
+}
+
+#[test]
+fn mapper_uses_and_extends_dictionary() {
+    let mut archive = open_encrypted_bar(&[
+        (
+            "Objects/FFFFFFFF-11111111-22222222-33333333/data/level.bin",
+            b"\x00\x01",
+        ),
+        (
+            "Objects/FFFFFFFF-11111111-22222222-33333333/object.xml",
+            b"<object/>",
+        ),
+        ("unreferenced.bin", b"???"),
+    ]);
+
+    // Known from another object, with a different UUID
+    let seed =
+        HashDictionary::from_iter(["Objects/0A1B2C3D-11111111-22222222-33333333/data/level.bin"]);
+
+    let result = Mapper::for_archive()
+        .with_uuid("FFFFFFFF-11111111-22222222-33333333")
+        .with_dictionary(seed.clone())
+        .with_dictionary(HashDictionary::from_iter(["unreferenced.bin"]))
+        .run_archive(&mut archive)
+        .unwrap();
+
+    assert_eq!(result.mapped, 3);
+    assert!(result.not_found.is_empty());
+
+    // Seeded paths are not reported as discovered, others are
+    let discovered: Vec<_> = result.discovered.iter().map(|(_, path)| path).collect();
+    assert_eq!(discovered.len(), 2);
+    assert!(discovered.contains(&"Objects/FFFFFFFF-11111111-22222222-33333333/data/level.bin"));
+    assert!(discovered.contains(&"Objects/FFFFFFFF-11111111-22222222-33333333/object.xml"));
+
+    let mut shared = seed;
+    assert_eq!(shared.merge(&result.discovered), 2);
+}
+
+fn create_scene_bar() -> BarReader<Cursor<Vec<u8>>> {
+    open_encrypted_bar(&[
+        ("object.xml", b"<scene file=\"scenes/home.scene\"/>"),
+        // References relative to the scene's own folder
+        (
+            "scenes/home.scene",
+            b"texture = \"textures/floor.dds\"\nshader = \"wall.dds\"",
+        ),
+        ("scenes/textures/floor.dds", b"DDS floor"),
+        // Only found as a sibling of floor.dds
+        ("scenes/textures/wall.dds", b"DDS wall"),
+    ])
+}
+
+#[test]
+fn resolves_references_until_fixpoint() {
+    let mut archive = create_scene_bar();
+
+    let result = Mapper::for_archive().run_archive(&mut archive).unwrap();
+
+    assert_eq!(result.mapped, 4);
+    assert!(result.not_found.is_empty());
+    assert!(result.rounds >= 2);
+    assert_eq!(
+        result
+            .hashes
+            .get(&AfsHash::new_from_str("scenes/textures/wall.dds"))
+            .map(String::as_str),
+        Some("scenes/textures/wall.dds")
+    );
+}
+
+#[test]
+fn resolves_references_in_folders() {
+    let mut archive = create_scene_bar();
+    let dir = tempfile::tempdir().unwrap();
+    let hashed = dir.path().join("hashed");
+    let mapped = dir.path().join("mapped");
+    unpack_to_directory(&hashed).unpack(&mut archive).unwrap();
+
+    let result = Mapper::new(hashed)
+        .with_output_folder(mapped.clone())
+        .run()
+        .unwrap();
+
+    assert_eq!(result.mapped, 4);
+    assert_eq!(
+        std::fs::read(mapped.join("scenes/textures/wall.dds")).unwrap(),
+        b"DDS wall"
+    );
+}
+
+#[test]
+fn results_do_not_depend_on_thread_count() {
+    let mut paths = vec!["object.xml".to_string(), "scenes/home.scene".to_string()];
+    paths.extend((0..64).map(|i| format!("scenes/textures/tex{i}.dds")));
+    paths.extend((0..16).map(|i| format!("unknown/{i}.bin")));
+
+    let scene = (0..64)
+        .map(|i| format!("texture = \"textures/tex{i}.dds\""))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let entries: Vec<(&str, &[u8])> = paths
+        .iter()
+        .map(|path| {
+            let content: &[u8] = match path.as_str() {
+                "object.xml" => b"<scene file=\"scenes/home.scene\"/>",
+                "scenes/home.scene" => scene.as_bytes(),
+                _ => b"\x00\x01",
+            };
+            (path.as_str(), content)
+        })
+        .collect();
+
+    let dir = tempfile::tempdir().unwrap();
+    let hashed = dir.path().join("hashed");
+    unpack_to_directory(&hashed)
+        .unpack(&mut open_encrypted_bar(&entries))
+        .unwrap();
+
+    let run = |threads: usize| {
+        let mapped = dir.path().join(format!("mapped_{threads}"));
+        let folder = Mapper::new(hashed.clone())
+            .with_output_folder(mapped)
+            .with_threads(threads)
+            .run()
+            .unwrap();
+        let archive = Mapper::for_archive()
+            .with_threads(threads)
+            .run_archive(&mut open_encrypted_bar(&entries))
+            .unwrap();
+        (folder, archive)
+    };
+
+    let (single_folder, single_archive) = run(1);
+    assert_eq!(single_folder.mapped, 66);
+    assert_eq!(single_archive.mapped, 66);
+
+    for threads in [2, 8] {
+        let (folder, archive) = run(threads);
+
+        for (single, parallel) in [(&single_folder, &folder), (&single_archive, &archive)] {
+            assert_eq!(parallel.mapped, single.mapped);
+            assert_eq!(parallel.hashes, single.hashes);
+            assert_eq!(parallel.discovered, single.discovered);
+            assert_eq!(parallel.rounds, single.rounds);
+            assert_eq!(
+                parallel
+                    .not_found
+                    .iter()
+                    .map(|path| path.file_name().unwrap())
+                    .collect::<Vec<_>>(),
+                single
+                    .not_found
+                    .iter()
+                    .map(|path| path.file_name().unwrap())
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+}
+
+fn utf16le(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+#[test]
+fn extracts_utf16_paths() {
+    let mut extracted = Extracted::default();
+    let mut data = vec![0xFF, 0xFE];
+    data.extend(utf16le("levels/lobby/floor.dds"));
+    Utf16Extractor::default().extract(&data, &mut extracted);
+
+    assert!(
+        extracted
+            .text
+            .contains(&"levels/lobby/floor.dds".to_string())
+    );
+
+    let be: Vec<u8> = "x\u{1}sounds/door.bnk"
+        .encode_utf16()
+        .flat_map(u16::to_be_bytes)
+        .collect();
+    let mut extracted = Extracted::default();
+    Utf16Extractor::default().extract(&be, &mut extracted);
+
+    assert!(extracted.text.contains(&"sounds/door.bnk".to_string()));
+}
+
+#[test]
+fn extracts_length_prefixed_strings() {
+    // A big-endian length of 0x41 reads as "A" in front of the string
+    let path = format!("levels/maze/{}.lua", "a".repeat(48));
+    let mut data = vec![0x1B, b'L', b'u', b'a', 0x00, 0x00, 0x00, 0x41];
+    data.extend(path.as_bytes());
+    data.push(0);
+
+    let mut extracted = Extracted::default();
+    LengthPrefixedExtractor::default().extract(&data, &mut extracted);
+
+    assert_eq!(extracted.text, [path]);
+}
+
+/// A model with one material using one texture.
+fn create_mdl() -> Vec<u8> {
+    let mut data = Vec::new();
+    let u32_be = |data: &mut Vec<u8>, value: u32| data.extend(value.to_be_bytes());
+
+    // Header: no elements, 1 material at 32
+    for value in [0, 0, 0, 0, 0, 1, 8, 0] {
+        u32_be(&mut data, value);
+    }
+
+    // Material at 32: name at 132, 1 texture (hash at 104, texture at 108)
+    for value in [100, 0, 0, 0, 0, 0, 1, 44, 44, 0] {
+        u32_be(&mut data, value);
+    }
+    data.extend([0; 32]);
+
+    // Texture hash at 104, texture at 108 with its name at 152
+    u32_be(&mut data, 0);
+    for value in [44, 0, 0, 0, 0, 0] {
+        u32_be(&mut data, value);
+    }
+
+    data.extend(b"materials/floor.mat\0");
+    data.extend(b"textures\\floor.dds\0");
+    data
+}
+
+#[test]
+fn extracts_mdl_filenames() {
+    let mut extracted = Extracted::default();
+    MdlExtractor.extract(&create_mdl(), &mut extracted);
+
+    assert_eq!(
+        extracted.paths,
+        ["materials/floor.mat", "textures\\floor.dds"]
+    );
+}
+
+#[test]
+fn rejects_implausible_mdl() {
+    let mut data = create_mdl();
+    // A texture count no file this size can hold
+    data[56..60].copy_from_slice(&u32::MAX.to_be_bytes());
+
+    let mut extracted = Extracted::default();
+    MdlExtractor.extract(&data, &mut extracted);
+    MdlExtractor.extract(&[0xFF; 64], &mut extracted);
+
+    assert_eq!(extracted, Extracted::default());
+}
+
+#[test]
+fn maps_paths_found_by_extractors() {
+    let lua_path = format!("levels/maze/{}.lua", "a".repeat(48));
+    let mut lua = vec![0x1B, b'L', b'u', b'a', 0x00, 0x00, 0x00, 0x41];
+    lua.extend(lua_path.as_bytes());
+    lua.push(0);
+
+    let object = utf16le("<model file=\"models/chair.mdl\" script=\"scripts/main.luac\"/>");
+    let mdl = create_mdl();
+    let entries: [(&str, &[u8]); 5] = [
+        ("object.xml", &object),
+        ("models/chair.mdl", &mdl),
+        ("textures/floor.dds", b"DDS floor"),
+        ("scripts/main.luac", &lua),
+        (&lua_path, b"print()"),
+    ];
+
+    // The regex patterns alone only find the model's texture, stored as plain text
+    let result = Mapper::for_archive()
+        .with_extractors(Vec::new())
+        .run_archive(&mut open_encrypted_bar(&entries))
+        .unwrap();
+    assert_eq!(result.mapped, 2);
+
+    let result = Mapper::for_archive()
+        .run_archive(&mut open_encrypted_bar(&entries))
+        .unwrap();
+    assert_eq!(result.mapped, 5);
+    assert!(result.not_found.is_empty());
+}
+
+#[test]
+fn recovers_unmapped_files_by_preimage() {
+    let mut archive = open_encrypted_bar(&[
+        ("object.xml", b"<object/>"),
+        ("textures/k9.dds", b"DDS"),
+        ("sounds/door_open.bnk", b"BKHD"),
+    ]);
+
+    let mut result = Mapper::for_archive().run_archive(&mut archive).unwrap();
+    assert_eq!(result.not_found.len(), 2);
+
+    let recovered = result.recover(
+        &PreimageSearch::new("textures/")
+            .with_charset("abcdefghijklmnopqrstuvwxyz0123456789", 3)
+            .with_extensions(SCENE_EXTENSIONS),
+    );
+
+    let texture = AfsHash::new_from_str("textures/k9.dds");
+    assert_eq!(
+        recovered,
+        [(
+            PathBuf::from(texture.to_string()),
+            "textures/k9.dds".to_string()
+        )]
+    );
+    assert_eq!(
+        result.not_found,
+        [PathBuf::from(
+            AfsHash::new_from_str("sounds/door_open.bnk").to_string()
+        )]
+    );
+    assert_eq!(
+        result.hashes.get(&texture).map(String::as_str),
+        Some("textures/k9.dds")
+    );
+    assert_eq!(result.discovered.get(texture), Some("textures/k9.dds"));
+}
+
+/// A big-endian Lua 5.1 chunk with `strings` as constants of its main function and
+/// `nested` as constants of a function nested in it.
+fn create_luac(strings: &[&str], nested: &[&str]) -> Vec<u8> {
+    fn string(data: &mut Vec<u8>, string: &str) {
+        data.extend((string.len() as u32 + 1).to_be_bytes());
+        data.extend(string.as_bytes());
+        data.push(0);
+    }
+
+    fn function(data: &mut Vec<u8>, strings: &[&str], nested: Option<&[&str]>) {
+        string(data, "@main.lua");
+        data.extend([0; 8]);
+        data.extend([0, 0, 2, 2]);
+
+        // A single RETURN instruction
+        data.extend(1u32.to_be_bytes());
+        data.extend(0x0080_001Eu32.to_be_bytes());
+
+        // A nil, a boolean and a number before the strings
+        data.extend((strings.len() as u32 + 3).to_be_bytes());
+        data.extend([0, 1, 1, 3]);
+        data.extend(1.5f64.to_be_bytes());
+        for constant in strings {
+            data.push(4);
+            string(data, constant);
+        }
+
+        match nested {
+            Some(nested) => {
+                data.extend(1u32.to_be_bytes());
+                function(data, nested, None);
+            }
+            None => data.extend(0u32.to_be_bytes()),
+        }
+
+        // One line, one local variable and one upvalue name
+        data.extend(1u32.to_be_bytes());
+        data.extend(1u32.to_be_bytes());
+        data.extend(1u32.to_be_bytes());
+        string(data, "self");
+        data.extend([0; 8]);
+        data.extend(1u32.to_be_bytes());
+        string(data, "env");
+    }
+
+    let mut data = b"\x1BLua\x51\x00\x00\x04\x04\x04\x08\x00".to_vec();
+    function(&mut data, strings, Some(nested));
+    data
+}
+
+#[test]
+fn reads_luac_string_constants() {
+    let data = create_luac(&["print", "scripts/door.lua"], &["textures/sky.dds"]);
+
+    assert!(luac::is_luac(&data));
+    assert_eq!(
+        luac::string_constants(&data).unwrap(),
+        [&b"print"[..], b"scripts/door.lua", b"textures/sky.dds"]
+    );
+}
+
+#[test]
+fn rejects_invalid_luac() {
+    let data = create_luac(&["print"], &[]);
+
+    assert!(matches!(
+        luac::string_constants(b"print()"),
+        Err(LuacError::NotLuac)
+    ));
+    assert!(matches!(
+        luac::string_constants(&data[..data.len() - 2]),
+        Err(LuacError::Truncated(_))
+    ));
+
+    let mut newer = data.clone();
+    newer[4] = 0x52;
+    assert!(matches!(
+        luac::string_constants(&newer),
+        Err(LuacError::UnsupportedVersion(0x52))
+    ));
+
+    let mut unknown = data;
+    // The nil constant of the main function
+    unknown[50] = 9;
+    assert!(matches!(
+        luac::string_constants(&unknown),
+        Err(LuacError::UnknownConstant(9, 50))
+    ));
+}
+
+#[test]
+fn maps_luac_string_constants() {
+    // 64 characters: its length prefix, NUL included, ends in an "A"
+    let lua_path = format!("levels/maze/{}.lua", "a".repeat(48));
+    let luac = create_luac(&["require", &lua_path], &[]);
+
+    let entries: [(&str, &[u8]); 3] = [
+        ("object.xml", b"<script file=\"scripts/main.luac\"/>"),
+        ("scripts/main.luac", &luac),
+        (&lua_path, b"print()"),
+    ];
+
+    let result = Mapper::for_archive()
+        .with_extractors(Vec::new())
+        .run_archive(&mut open_encrypted_bar(&entries))
+        .unwrap();
+    assert_eq!(result.mapped, 2);
+
+    let result = Mapper::for_archive()
+        .with_extractors(vec![Box::new(LuacExtractor)])
+        .run_archive(&mut open_encrypted_bar(&entries))
+        .unwrap();
+    assert_eq!(result.mapped, 3);
+}
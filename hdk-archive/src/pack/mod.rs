@@ -0,0 +1,238 @@
+//! Packing a directory tree into an archive.
+//!
+//! Every file under the root becomes an entry, named by the [`AfsHash`] of its
+//! path relative to the root (normalised to forward slashes and lowercase), and
+//! optionally prefixed by `Objects/{uuid}/` for object archives. Files that are
+//! already named by an uppercase-hex hash (as extracted from an archive whose paths
+//! were not recovered) keep that hash.
+//!
+//! If the root contains a `.time` file, its timestamp is written in the header
+//! instead of being packed as an entry.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use hdk_archive::bar::BarWriter;
+//! use hdk_archive::pack::pack_directory;
+//! use hdk_archive::structs::CompressionType;
+//!
+//! let writer = BarWriter::new(Vec::new(), [0u8; 32], [0u8; 32]);
+//!
+//! let archive = pack_directory("path/to/object")
+//!     .with_uuid("00000000-00000000-00000000-00000000")
+//!     .with_compression("dds", CompressionType::EdgeZLib)
+//!     .with_compression("xml", CompressionType::Encrypted)
+//!     .pack(writer)
+//!     .unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use hdk_secure::hash::AfsHash;
+
+use crate::archive::ArchiveWriter;
+use crate::error::ArchiveError;
+use crate::structs::CompressionType;
+use crate::time::{TIME_FILE_NAME, TimeFile};
+
+#[cfg(test)]
+mod tests;
+
+/// Chooses the compression of each packed file from its extension.
+#[derive(Debug, Clone)]
+pub struct CompressionPolicy {
+    /// Compression by lowercase extension, without the leading dot.
+    by_extension: HashMap<String, CompressionType>,
+
+    /// Compression for files whose extension has no explicit policy.
+    default: CompressionType,
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        Self::new(CompressionType::ZLib)
+    }
+}
+
+impl CompressionPolicy {
+    /// Create a policy compressing every file with `default`.
+    pub fn new(default: CompressionType) -> Self {
+        Self {
+            by_extension: HashMap::new(),
+            default,
+        }
+    }
+
+    /// Compress files with the given extension (case-insensitive, with or without
+    /// the leading dot) with `compression`.
+    pub fn with(mut self, extension: &str, compression: CompressionType) -> Self {
+        let extension = extension.trim_start_matches('.').to_lowercase();
+        self.by_extension.insert(extension, compression);
+        self
+    }
+
+    /// The compression to use for the file at `path`.
+    pub fn compression_for(&self, path: &Path) -> CompressionType {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.by_extension.get(&ext.to_lowercase()))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// A file that will be packed, as listed by [`DirectoryPacker::files`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedFile {
+    /// Where the file is on disk.
+    pub source: PathBuf,
+
+    /// The normalised path the entry's hash is computed from.
+    pub archive_path: String,
+
+    pub name_hash: AfsHash,
+
+    pub compression: CompressionType,
+}
+
+/// Builder for packing a directory tree into an archive.
+///
+/// See the [module documentation](self) for how files are named.
+#[derive(Debug, Clone)]
+pub struct DirectoryPacker {
+    root: PathBuf,
+    uuid: Option<String>,
+    policy: CompressionPolicy,
+}
+
+/// Start packing the directory tree at `root`.
+pub fn pack_directory(root: impl Into<PathBuf>) -> DirectoryPacker {
+    DirectoryPacker::new(root)
+}
+
+impl DirectoryPacker {
+    /// Create a new `DirectoryPacker` for the given root folder.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            uuid: None,
+            policy: CompressionPolicy::default(),
+        }
+    }
+
+    /// Prefix every path with `Objects/{uuid}/` before hashing it.
+    ///
+    /// This is required for Objects, whose original hashes take that prefix into account.
+    pub fn with_uuid(mut self, uuid: impl Into<String>) -> Self {
+        self.uuid = Some(uuid.into());
+        self
+    }
+
+    /// Replace the whole compression policy.
+    ///
+    /// Default is [`CompressionType::ZLib`] for every file.
+    pub fn with_policy(mut self, policy: CompressionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Compress files with the given extension with `compression`.
+    ///
+    /// See [`CompressionPolicy::with`].
+    pub fn with_compression(mut self, extension: &str, compression: CompressionType) -> Self {
+        self.policy = self.policy.with(extension, compression);
+        self
+    }
+
+    /// List the files that will be packed, sorted by archive path.
+    ///
+    /// This is useful to know the entry count up front, e.g. for the streaming writers.
+    ///
+    /// Fails with [`ArchiveError::HashCollision`] if two files would get the same name hash.
+    pub fn files(&self) -> Result<Vec<PackedFile>, ArchiveError> {
+        let mut files = Vec::new();
+
+        for entry in walkdir::WalkDir::new(&self.root) {
+            let entry = entry.map_err(std::io::Error::from)?;
+
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let relative = entry
+                .path()
+                .strip_prefix(&self.root)
+                .expect("walked paths are under the root");
+
+            // The sidecar holds the header timestamp, it is not an entry
+            if relative == Path::new(TIME_FILE_NAME) {
+                continue;
+            }
+
+            let archive_path = self.archive_path(relative);
+
+            // Files named by their hash keep it, since their path is unknown
+            let name_hash = relative
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(AfsHash::from_hash_str)
+                .unwrap_or_else(|| AfsHash::new_from_str(&archive_path));
+
+            files.push(PackedFile {
+                source: entry.path().to_path_buf(),
+                archive_path,
+                name_hash,
+                compression: self.policy.compression_for(relative),
+            });
+        }
+
+        files.sort_by(|a, b| a.archive_path.cmp(&b.archive_path));
+
+        let mut seen: HashMap<AfsHash, &PackedFile> = HashMap::new();
+        for file in &files {
+            if let Some(first) = seen.insert(file.name_hash, file) {
+                return Err(ArchiveError::HashCollision {
+                    name_hash: file.name_hash,
+                    first: first.source.clone(),
+                    second: file.source.clone(),
+                });
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Pack every file into `writer`, then finish it.
+    ///
+    /// If the root contains a `.time` file, its timestamp is set on the writer first.
+    pub fn pack<W: ArchiveWriter>(&self, mut writer: W) -> Result<W::Output, ArchiveError> {
+        if let Some(time_file) = TimeFile::read(&self.root)? {
+            writer.set_timestamp(time_file.timestamp);
+        }
+
+        for file in self.files()? {
+            let mut reader = File::open(&file.source)?;
+            writer.add_entry_from_reader(file.name_hash, file.compression, &mut reader)?;
+        }
+
+        Ok(writer.finish()?)
+    }
+
+    /// Normalise a path relative to the root to forward slashes and lowercase, with
+    /// the UUID prefix if any.
+    fn archive_path(&self, relative: &Path) -> String {
+        let path = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+            .to_lowercase();
+
+        match &self.uuid {
+            Some(uuid) => format!("objects/{}/{path}", uuid.to_lowercase()),
+            None => path,
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use hdk_secure::hash::AfsHash;
+
+use crate::archive::{ArchiveReader, ArchiveWriter, EntryMetadata};
+use crate::bar::BarWriter;
+use crate::error::ArchiveError;
+use crate::pack::{CompressionPolicy, pack_directory};
+use crate::sharc::stream::SharcStreamWriter;
+use crate::structs::{CompressionType, Endianness};
+use crate::test_utils::{
+    TEST_DEFAULT_KEY, TEST_SHARC_KEY, TEST_SIGNATURE_KEY, open_bar, open_sharc,
+};
+
+const UUID: &str = "0A1B2C3D-11111111-22222222-33333333";
+
+fn write_file(root: &Path, relative: &str, content: &[u8]) {
+    let path = root.join(relative);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
+
+fn create_tree() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+
+    write_file(root, "object.xml", b"<object/>");
+    write_file(root, "Textures/Wall.DDS", &[0x44; 5000]);
+    write_file(root, "scripts/main.lua", b"print('hello')");
+    write_file(root, "F00DCAFE", b"unmapped file");
+    write_file(root, ".time", b"4F2A1B3C\n");
+
+    dir
+}
+
+fn read_entry<A: ArchiveReader>(archive: &mut A, path: &str) -> (CompressionType, Vec<u8>) {
+    let mut stream = archive.open_path(path).unwrap();
+    let mut content = Vec::new();
+    stream.reader.read_to_end(&mut content).unwrap();
+    (stream.metadata.compression(), content)
+}
+
+#[test]
+fn pack_into_bar() {
+    let dir = create_tree();
+
+    let writer = BarWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    );
+    let out = pack_directory(dir.path())
+        .with_uuid(UUID)
+        .with_compression("dds", CompressionType::EdgeZLib)
+        .with_compression(".XML", CompressionType::Encrypted)
+        .pack(writer)
+        .unwrap()
+        .into_inner();
+
+    let mut archive = open_bar(out);
+
+    // The .time file is not an entry, but sets the header timestamp
+    assert_eq!(archive.entry_count(), 4);
+    assert_eq!(archive.header().timestamp, 0x4F2A_1B3C);
+
+    let prefix = format!("Objects/{UUID}");
+    assert_eq!(
+        read_entry(&mut archive, &format!("{prefix}/object.xml")),
+        (CompressionType::Encrypted, b"<object/>".to_vec())
+    );
+    assert_eq!(
+        read_entry(&mut archive, &format!("{prefix}/textures/wall.dds")),
+        (CompressionType::EdgeZLib, vec![0x44; 5000])
+    );
+    assert_eq!(
+        read_entry(&mut archive, &format!("{prefix}/scripts/main.lua")),
+        (CompressionType::ZLib, b"print('hello')".to_vec())
+    );
+
+    // Files named by a hash keep it
+    let index = archive
+        .find_by_hash(AfsHash(0xF00D_CAFEu32 as i32))
+        .unwrap()
+        .unwrap();
+    let mut content = Vec::new();
+    archive
+        .entry_reader(index)
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap();
+    assert_eq!(content, b"unmapped file");
+}
+
+#[test]
+fn pack_into_stream_writer() {
+    let dir = create_tree();
+    std::fs::remove_file(dir.path().join(".time")).unwrap();
+
+    let packer =
+        pack_directory(dir.path()).with_policy(CompressionPolicy::new(CompressionType::None));
+    let files = packer.files().unwrap();
+    assert_eq!(
+        files
+            .iter()
+            .map(|f| f.archive_path.as_str())
+            .collect::<Vec<_>>(),
+        [
+            "f00dcafe",
+            "object.xml",
+            "scripts/main.lua",
+            "textures/wall.dds"
+        ]
+    );
+
+    let mut writer = SharcStreamWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_SHARC_KEY,
+        Endianness::Big,
+        files.len() as u32,
+    )
+    .unwrap();
+    writer.set_timestamp(1234);
+    let out = packer.pack(writer).unwrap().into_inner();
+
+    let mut archive = open_sharc(out);
+    assert_eq!(archive.entry_count(), 4);
+    // Without a .time file, the writer's timestamp is kept
+    assert_eq!(archive.header().timestamp, 1234);
+    assert_eq!(
+        read_entry(&mut archive, "Scripts\\Main.lua"),
+        (CompressionType::None, b"print('hello')".to_vec())
+    );
+}
+
+#[test]
+fn pack_rejects_hash_collisions() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a/file.txt", b"one");
+    write_file(dir.path(), "A/FILE.TXT", b"two");
+
+    let result = pack_directory(dir.path()).files();
+
+    // Case-insensitive file systems cannot hold both files
+    if std::fs::read_dir(dir.path()).unwrap().count() == 2 {
+        assert!(matches!(result, Err(ArchiveError::HashCollision { .. })));
+    }
+}
+
+#[test]
+fn pack_rejects_invalid_time_file() {
+    let dir = create_tree();
+    write_file(dir.path(), ".time", b"not a timestamp");
+
+    let writer = BarWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    );
+    assert!(pack_directory(dir.path()).pack(writer).is_err());
+}
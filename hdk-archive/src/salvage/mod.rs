@@ -0,0 +1,237 @@
+//! Recovery of what is left in truncated or corrupted archives.
+//!
+//! A download cut short leaves an archive whose later entries run past the end of
+//! the file, and often a table of contents cut short too, so that
+//! [`BarReader::open`] and [`SharcReader::open`] refuse it. Opening it with
+//! [`BarReader::open_salvage`] or [`SharcReader::open_salvage`] keeps every complete
+//! ToC record, and [`Salvage::salvage`] then reads each entry as far as it goes:
+//! intact entries come out whole, and compressed entries cut short are decoded up
+//! to their last good chunk. Each entry is reported with a [`EntryStatus`].
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use hdk_archive::bar::BarReader;
+//! use hdk_archive::salvage::{EntryStatus, Salvage};
+//!
+//! let file = std::fs::File::open("path/to/truncated.bar").unwrap();
+//! let mut archive = BarReader::open_salvage(file, [0u8; 32], [0u8; 32]).unwrap();
+//!
+//! let report = archive
+//!     .salvage(|entry, data| {
+//!         if entry.status == EntryStatus::Intact {
+//!             std::fs::write(format!("{}.bin", entry.name_hash), data)?;
+//!         }
+//!         Ok(())
+//!     })
+//!     .unwrap();
+//!
+//! println!("{} of {} entries intact", report.intact().count(), report.declared);
+//! ```
+
+use std::io::{self, Read, Seek};
+
+use hdk_secure::hash::AfsHash;
+
+use crate::any::AnyArchive;
+use crate::archive::{ArchiveReader, EntryMetadata};
+use crate::bar::BarReader;
+use crate::error::ArchiveError;
+use crate::sharc::reader::SharcReader;
+use crate::structs::CompressionType;
+use crate::utils::read_until_error;
+
+#[cfg(test)]
+mod tests;
+
+/// How much of an entry was recovered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryStatus {
+    /// All of the content was recovered.
+    Intact,
+
+    /// The data runs past the end of the file; the content before the cut was
+    /// recovered.
+    Truncated,
+
+    /// The data is in the file but failed to read; the content before the failure
+    /// was recovered.
+    Corrupt(String),
+}
+
+/// A listed entry and what was recovered of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SalvagedEntry {
+    pub index: usize,
+    pub name_hash: AfsHash,
+    pub compression: CompressionType,
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+
+    /// Bytes of stored data present in the file.
+    pub available: u32,
+
+    /// Bytes of content recovered.
+    pub recovered: u64,
+
+    pub status: EntryStatus,
+}
+
+/// What was recovered of an archive.
+#[derive(Debug, Clone, Default)]
+pub struct SalvageReport {
+    /// Number of entries the header declares.
+    pub declared: u32,
+
+    /// Every entry whose ToC record survived, in ToC order.
+    pub entries: Vec<SalvagedEntry>,
+}
+
+impl SalvageReport {
+    /// Whether every declared entry was listed and recovered intact.
+    pub fn is_complete(&self) -> bool {
+        self.unlisted() == 0 && self.intact().count() == self.entries.len()
+    }
+
+    /// Entries recovered whole.
+    pub fn intact(&self) -> impl Iterator<Item = &SalvagedEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.status == EntryStatus::Intact)
+    }
+
+    /// Number of declared entries whose ToC record was lost.
+    pub const fn unlisted(&self) -> usize {
+        (self.declared as usize).saturating_sub(self.entries.len())
+    }
+}
+
+/// Archives whose entries can be recovered as far as they go.
+pub trait Salvage {
+    /// Read every listed entry, passing each one and its recovered content to `sink`.
+    ///
+    /// Only I/O errors on the archive itself, or from `sink`, fail; damaged entries
+    /// are reported.
+    fn salvage<F>(&mut self, sink: F) -> io::Result<SalvageReport>
+    where
+        F: FnMut(&SalvagedEntry, &[u8]) -> io::Result<()>;
+}
+
+impl<R: Read + Seek> Salvage for BarReader<R> {
+    fn salvage<F>(&mut self, mut sink: F) -> io::Result<SalvageReport>
+    where
+        F: FnMut(&SalvagedEntry, &[u8]) -> io::Result<()>,
+    {
+        let archive_len = self.archive_len()?;
+        let data_start = self.data_start();
+        let mut report = SalvageReport {
+            declared: self.header().file_count,
+            entries: Vec::with_capacity(self.entry_count()),
+        };
+
+        let mut data = Vec::new();
+        for index in 0..self.entry_count() {
+            let metadata = self.entry_metadata(index)?;
+            let start = data_start + u64::from(metadata.offset);
+
+            data.clear();
+            let entry = salvage_entry(index, &metadata, start, archive_len, &mut data, || {
+                self.open_entry(index, true)
+            });
+
+            sink(&entry, &data)?;
+            report.entries.push(entry);
+        }
+
+        Ok(report)
+    }
+}
+
+impl<R: Read + Seek> Salvage for SharcReader<R> {
+    fn salvage<F>(&mut self, mut sink: F) -> io::Result<SalvageReport>
+    where
+        F: FnMut(&SalvagedEntry, &[u8]) -> io::Result<()>,
+    {
+        let archive_len = self.archive_len()?;
+        let data_start = self.data_start();
+        let mut report = SalvageReport {
+            declared: self.header().file_count,
+            entries: Vec::with_capacity(self.entry_count()),
+        };
+
+        let mut data = Vec::new();
+        for index in 0..self.entry_count() {
+            let metadata = self.entry_metadata(index)?;
+            let start = data_start + metadata.offset;
+
+            data.clear();
+            let entry = salvage_entry(index, &metadata, start, archive_len, &mut data, || {
+                self.entry_reader(index)
+            });
+
+            sink(&entry, &data)?;
+            report.entries.push(entry);
+        }
+
+        Ok(report)
+    }
+}
+
+impl<R: Read + Seek> Salvage for AnyArchive<R> {
+    fn salvage<F>(&mut self, sink: F) -> io::Result<SalvageReport>
+    where
+        F: FnMut(&SalvagedEntry, &[u8]) -> io::Result<()>,
+    {
+        match self {
+            Self::Bar(bar) => bar.salvage(sink),
+            Self::Sharc(sharc) => sharc.salvage(sink),
+        }
+    }
+}
+
+/// Read an entry into `data` as far as it goes, and tell how far that was.
+fn salvage_entry<'a, M: EntryMetadata>(
+    index: usize,
+    metadata: &M,
+    start: u64,
+    archive_len: u64,
+    data: &mut Vec<u8>,
+    open: impl FnOnce() -> io::Result<Box<dyn Read + 'a>>,
+) -> SalvagedEntry {
+    let compressed_size = metadata.compressed_size();
+    let uncompressed_size = metadata.uncompressed_size();
+
+    // Fits, as it is at most `compressed_size`
+    let available = archive_len
+        .saturating_sub(start)
+        .min(u64::from(compressed_size)) as u32;
+    let cut = available < compressed_size;
+
+    // Nothing of the entry is in the file
+    let result = if cut && available == 0 {
+        Ok(())
+    } else {
+        open().and_then(|mut reader| read_until_error(&mut reader, data))
+    };
+
+    let status = match result {
+        Ok(()) if data.len() as u64 == u64::from(uncompressed_size) => EntryStatus::Intact,
+        _ if cut => EntryStatus::Truncated,
+        Ok(()) => EntryStatus::Corrupt(format!(
+            "content is {} bytes, expected {uncompressed_size}",
+            data.len()
+        )),
+        Err(e) => EntryStatus::Corrupt(ArchiveError::from(e).to_string()),
+    };
+
+    SalvagedEntry {
+        index,
+        name_hash: metadata.name_hash(),
+        compression: metadata.compression(),
+        compressed_size,
+        uncompressed_size,
+        available,
+        recovered: data.len() as u64,
+        status,
+    }
+}
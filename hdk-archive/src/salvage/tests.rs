@@ -0,0 +1,173 @@
+use std::io::Cursor;
+
+use hdk_secure::hash::AfsHash;
+
+use super::{EntryStatus, Salvage, SalvageReport, SalvagedEntry};
+use crate::any::open_any;
+use crate::archive::ArchiveReader;
+use crate::bar::BarReader;
+use crate::sharc::reader::SharcReader;
+use crate::structs::{CompressionType, Endianness};
+use crate::test_utils::{
+    TEST_DEFAULT_KEY, TEST_KEYS, TEST_SHARC_KEY, TEST_SIGNATURE_KEY, TestEntry, create_bar,
+    create_sharc, open_bar, open_sharc,
+};
+
+/// Content spanning several EdgeZLib chunks, compressible but not trivially.
+fn large_content() -> Vec<u8> {
+    let mut state = 0x1234_5678u32;
+    (0..300_000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            b'a' + (state >> 28) as u8
+        })
+        .collect()
+}
+
+fn entries(large: &[u8]) -> [TestEntry<'_>; 3] {
+    [
+        ("first.txt", CompressionType::None, b"first entry"),
+        ("large.bin", CompressionType::EdgeZLib, large),
+        ("secret.lua", CompressionType::Encrypted, b"print('secret')"),
+    ]
+}
+
+/// Salvage an archive, collecting the recovered content of each entry.
+fn salvage(archive: &mut impl Salvage) -> (SalvageReport, Vec<Vec<u8>>) {
+    let mut contents = Vec::new();
+    let report = archive
+        .salvage(|_, data| {
+            contents.push(data.to_vec());
+            Ok(())
+        })
+        .unwrap();
+
+    (report, contents)
+}
+
+fn entry<'a>(report: &'a SalvageReport, path: &str) -> &'a SalvagedEntry {
+    let name_hash = AfsHash::new_from_str(path);
+    report
+        .entries
+        .iter()
+        .find(|entry| entry.name_hash == name_hash)
+        .unwrap()
+}
+
+#[test]
+fn salvages_intact_archives() {
+    let large = large_content();
+    let entries = entries(&large);
+
+    for data in [
+        create_bar(&entries),
+        create_sharc(&entries, Endianness::Little),
+    ] {
+        let mut archive = open_any(Cursor::new(data), &TEST_KEYS).unwrap();
+        let (report, contents) = salvage(&mut archive);
+
+        assert!(report.is_complete(), "{:?}", report.entries);
+        assert_eq!(report.declared, 3);
+
+        for (path, _, content) in entries {
+            let entry = entry(&report, path);
+            assert_eq!(contents[entry.index], content);
+            assert_eq!(entry.recovered, content.len() as u64);
+        }
+    }
+}
+
+#[test]
+fn salvages_edgezlib_entry_up_to_last_good_chunk() {
+    let large = large_content();
+    let data = create_sharc(&entries(&large), Endianness::Little);
+
+    let archive = open_sharc(data.clone());
+    let index = archive
+        .find_by_hash(AfsHash::new_from_str("large.bin"))
+        .unwrap()
+        .unwrap();
+    let metadata = archive.entry_metadata(index).unwrap();
+    let start = archive.data_start() + metadata.offset;
+
+    // Cut the file halfway through the EdgeZLib entry
+    let cut = (start + u64::from(metadata.compressed_size) / 2) as usize;
+    let mut archive =
+        SharcReader::open_salvage(Cursor::new(data[..cut].to_vec()), TEST_SHARC_KEY).unwrap();
+    let (report, contents) = salvage(&mut archive);
+
+    let entry = entry(&report, "large.bin");
+    assert_eq!(entry.status, EntryStatus::Truncated);
+    assert_eq!(u64::from(entry.available), cut as u64 - start);
+    assert!(entry.recovered > 0 && entry.recovered < large.len() as u64);
+    assert!(large.starts_with(&contents[index]));
+
+    // Entries stored wholly before the cut are intact, those after it are empty
+    for entry in &report.entries {
+        if entry.index == index {
+            continue;
+        }
+
+        let other_start =
+            archive.data_start() + archive.entry_metadata(entry.index).unwrap().offset;
+        if other_start < start {
+            assert_eq!(entry.status, EntryStatus::Intact);
+        } else {
+            assert_eq!(entry.status, EntryStatus::Truncated);
+            assert_eq!((entry.available, entry.recovered), (0, 0));
+        }
+    }
+    assert!(!report.is_complete());
+}
+
+#[test]
+fn salvages_bar_with_truncated_toc() {
+    let data = create_bar(&entries(&large_content()));
+
+    // Keep the header and one and a half ToC records
+    let truncated = data[..20 + 16 + 8].to_vec();
+    assert!(
+        BarReader::open(
+            Cursor::new(truncated.clone()),
+            TEST_DEFAULT_KEY,
+            TEST_SIGNATURE_KEY
+        )
+        .is_err()
+    );
+
+    let mut archive =
+        BarReader::open_salvage(Cursor::new(truncated), TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY)
+            .unwrap();
+    let (report, contents) = salvage(&mut archive);
+
+    assert_eq!(report.declared, 3);
+    assert_eq!(report.entries.len(), 1);
+    assert_eq!(report.unlisted(), 2);
+    assert_eq!(report.entries[0].status, EntryStatus::Truncated);
+    assert_eq!(report.entries[0].available, 0);
+    assert!(contents[0].is_empty());
+}
+
+#[test]
+fn reports_corrupt_entries() {
+    let mut data = create_bar(&entries(&large_content()));
+
+    let archive = open_bar(data.clone());
+    let index = archive
+        .find_by_hash(AfsHash::new_from_str("secret.lua"))
+        .unwrap()
+        .unwrap();
+    let start = archive.data_start() + u64::from(archive.entry_metadata(index).unwrap().offset);
+    data[start as usize + 30] ^= 0xFF;
+
+    let mut archive =
+        BarReader::open_salvage(Cursor::new(data), TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY).unwrap();
+    let (report, _) = salvage(&mut archive);
+
+    assert!(matches!(
+        entry(&report, "secret.lua").status,
+        EntryStatus::Corrupt(_)
+    ));
+    assert_eq!(report.intact().count(), 2);
+    assert!(!report.is_complete());
+}
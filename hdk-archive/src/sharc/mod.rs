@@ -1,4 +1,5 @@
 pub mod reader;
+pub mod stream;
 pub mod structs;
 pub mod writer;
 
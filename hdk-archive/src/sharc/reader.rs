@@ -8,14 +8,18 @@ use flate2::read::ZlibDecoder;
 use std::convert::TryFrom;
 use std::io::{self, Cursor, Read, Seek, SeekFrom};
 
-use hdk_comp::zlib::reader::SegmentedZlibReader;
-use hdk_secure::{reader::CryptoReader, xtea::modes::XteaPS3};
+use hdk_comp::zlib::reader::{SeekableSegmentedZlibReader, SegmentedZlibReader};
+use hdk_secure::hash::AfsHash;
+use hdk_secure::reader::{CryptoReader, SeekableCryptoReader};
+use hdk_secure::xtea::modes::XteaPS3;
 
 use super::structs::{
     SharcEntry, SharcEntryMetadata, SharcHeader, SharcInnerHeader, SharcPreamble,
 };
-use crate::archive::ArchiveReader;
+use crate::archive::{ArchiveReader, HashIndex, RawEntry, ReadSeek};
+use crate::error::ArchiveError;
 use crate::structs::{ARCHIVE_MAGIC, ArchiveFlags, CompressionType, Endianness};
+use crate::utils::{Window, read_table};
 
 pub struct SharcReader<R: Read + Seek> {
     /// The underlying reader.
@@ -26,9 +30,15 @@ pub struct SharcReader<R: Read + Seek> {
     /// The parsed SHARC header.
     header: SharcHeader,
 
+    /// The header flags as stored, including bits unknown to [`ArchiveFlags`].
+    raw_flags: u16,
+
     /// Every entry in the archive's table of contents.
     entries: Vec<SharcEntry>,
 
+    /// Entry indices by name hash, built when the archive is opened.
+    index: HashIndex,
+
     /// The detected endianness of the archive.
     pub endianness: Endianness,
 
@@ -39,7 +49,20 @@ pub struct SharcReader<R: Read + Seek> {
 }
 
 impl<R: Read + Seek> SharcReader<R> {
-    pub fn open(mut reader: R, key: [u8; 32]) -> io::Result<Self> {
+    pub fn open(reader: R, key: [u8; 32]) -> io::Result<Self> {
+        Self::open_with(reader, key, false)
+    }
+
+    /// Open a possibly truncated SHARC archive, keeping the entries whose ToC record
+    /// is complete.
+    ///
+    /// Only the preamble and inner header must be intact. Entries may still point
+    /// past the end of the file; see [`crate::salvage`] to recover what they hold.
+    pub fn open_salvage(reader: R, key: [u8; 32]) -> io::Result<Self> {
+        Self::open_with(reader, key, true)
+    }
+
+    fn open_with(mut reader: R, key: [u8; 32], salvage: bool) -> io::Result<Self> {
         // 1. Detect Endianness via Magic
         let magic_val = reader.read_le::<u32>().map_err(|e| {
             io::Error::new(
@@ -87,10 +110,12 @@ impl<R: Read + Seek> SharcReader<R> {
         })?;
 
         // 5. Read & Decrypt ToC
-        let count = inner.file_count as usize;
-        let toc_size = count * 24;
-        let mut toc_buf = vec![0u8; toc_size];
-        reader.read_exact(&mut toc_buf)?;
+        let toc_start = reader.stream_position()?;
+        let toc_size = u64::from(inner.file_count) * 24;
+        let mut toc_buf = read_table(&mut reader, toc_size, salvage)?;
+
+        // A salvaged ToC may be cut short
+        let count = toc_buf.len() / 24;
 
         // Previous cipher is now misaligned, so we have to create a new one.
         // Update IV (Increment by 1 for ToC)
@@ -111,7 +136,7 @@ impl<R: Read + Seek> SharcReader<R> {
                 )
             })?;
 
-        let data_start_offset = reader.stream_position()?;
+        let data_start_offset = toc_start + toc_size;
 
         // 7. Assemble Public Header
         let header = SharcHeader {
@@ -124,10 +149,14 @@ impl<R: Read + Seek> SharcReader<R> {
             files_key: inner.files_key.try_into().unwrap(),
         };
 
+        let index = HashIndex::new(entries.iter().map(SharcEntry::name_hash));
+
         Ok(Self {
             inner: reader,
             header,
+            raw_flags: preamble.version_and_flags.1,
             entries,
+            index,
             endianness: if endian == Endian::Little {
                 Endianness::Little
             } else {
@@ -140,10 +169,24 @@ impl<R: Read + Seek> SharcReader<R> {
     pub fn header(&self) -> SharcHeader {
         self.header.clone()
     }
-    
+
     pub const fn is_empty(&self) -> bool {
         self.entries.is_empty()
     }
+
+    /// Where entry data starts; entry offsets are relative to it.
+    pub(crate) const fn data_start(&self) -> u64 {
+        self.data_start_offset
+    }
+
+    pub(crate) const fn raw_flags(&self) -> u16 {
+        self.raw_flags
+    }
+
+    /// The size of the whole archive.
+    pub(crate) fn archive_len(&mut self) -> io::Result<u64> {
+        self.inner.seek(SeekFrom::End(0))
+    }
 }
 
 impl<R: Read + Seek> ArchiveReader for SharcReader<R> {
@@ -153,6 +196,10 @@ impl<R: Read + Seek> ArchiveReader for SharcReader<R> {
         self.entries.len()
     }
 
+    fn timestamp(&self) -> i32 {
+        self.header.timestamp
+    }
+
     /// Return a copyable metadata view for an entry.
     fn entry_metadata(&self, index: usize) -> io::Result<SharcEntryMetadata> {
         let entry = self
@@ -168,20 +215,74 @@ impl<R: Read + Seek> ArchiveReader for SharcReader<R> {
         })
     }
 
+    fn find_by_hash(&self, name_hash: AfsHash) -> Result<Option<usize>, ArchiveError> {
+        self.index.find(name_hash)
+    }
+
     /// Returns a Reader that streams the file content, automatically handling
     /// decryption and decompression based on the entry type.
     fn entry_reader<'a>(&'a mut self, index: usize) -> io::Result<Box<dyn Read + 'a>> {
+        let (entry, comp_type, payload) = self.open_payload(index)?;
+
+        match comp_type {
+            // Encrypted: first compressed with ZLib, then encrypted with XTEA-CTR
+            CompressionType::Encrypted => Ok(Box::new(SegmentedZlibReader::new(payload))),
+
+            // Standard ZLib compression
+            CompressionType::ZLib => Ok(Box::new(ZlibDecoder::new(payload))),
+
+            // Encrypted nested archive, already decrypted by `open_payload`
+            CompressionType::EdgeZLib if entry.compressed_size == entry.uncompressed_size => {
+                Ok(payload)
+            }
+
+            // EdgeZLib regular segmented streaming
+            CompressionType::EdgeZLib => Ok(Box::new(SegmentedZlibReader::new(payload))),
+
+            // No compression
+            CompressionType::None => Ok(payload),
+        }
+    }
+
+    /// Open an entry's stored payload, decrypted but not decompressed.
+    ///
+    /// Encrypted nested archives hold plain data once decrypted, so they are
+    /// reported as uncompressed, with their IV.
+    fn raw_entry_reader<'a>(&'a mut self, index: usize) -> io::Result<RawEntry<'a>> {
+        let (entry, comp_type, reader) = self.open_payload(index)?;
+
+        let nested = comp_type == CompressionType::EdgeZLib
+            && entry.compressed_size == entry.uncompressed_size;
+
+        let (compression, iv) = match comp_type {
+            CompressionType::EdgeZLib if nested => (CompressionType::None, entry.iv_bytes()),
+            CompressionType::Encrypted => (comp_type, entry.iv_bytes()),
+            other => (other, None),
+        };
+
+        Ok(RawEntry {
+            name_hash: entry.name_hash(),
+            compression,
+            uncompressed_size: entry.uncompressed_size,
+            iv,
+            checksum: None,
+            reader,
+        })
+    }
+
+    /// Open an entry for random access.
+    ///
+    /// Uncompressed entries are read through a window over the archive, and EdgeZLib
+    /// and encrypted entries through an index of their chunks with the XTEA keystream
+    /// seeked to match, so only the chunk containing the current position is decrypted
+    /// and decompressed. ZLib entries cannot be seeked into and are buffered in memory.
+    fn entry_seekable_reader<'a>(&'a mut self, index: usize) -> io::Result<Box<dyn ReadSeek + 'a>> {
         let entry = self
             .entries
             .get(index)
             .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Invalid entry index"))?
             .clone();
 
-        let start_offset = self.data_start_offset + entry.offset();
-        self.inner.seek(SeekFrom::Start(start_offset))?;
-
-        let raw_stream = (&mut self.inner).take(u64::from(entry.compressed_size));
-
         let comp_type = CompressionType::try_from(entry.compression()).map_err(|_| {
             io::Error::new(
                 io::ErrorKind::InvalidData,
@@ -189,39 +290,78 @@ impl<R: Read + Seek> ArchiveReader for SharcReader<R> {
             )
         })?;
 
+        let start_offset = self.data_start_offset + entry.offset();
+        let size = u64::from(entry.compressed_size);
+
         match comp_type {
-            // Encrypted: first compressed with ZLib, then encrypted with XTEA-CTR
             CompressionType::Encrypted => {
-                // Decrypt with XTEA-CTR then stream-decompress with SegmentedZlibReader
                 let key = self.header.files_key.into();
                 let cipher = XteaPS3::new(&key, entry.iv.as_slice().into());
-                let crypto = CryptoReader::new(raw_stream, cipher);
-                let seg = SegmentedZlibReader::new(crypto);
-                Ok(Box::new(seg))
+                let window = Window::new(&mut self.inner, start_offset, size)?;
+                let crypto = SeekableCryptoReader::new(window, cipher)?;
+                Ok(Box::new(SeekableSegmentedZlibReader::new(crypto)?))
             }
-
-            // Standard ZLib compression
             CompressionType::ZLib => {
-                let zlib = ZlibDecoder::new(raw_stream);
-                Ok(Box::new(zlib))
+                let mut data = Vec::with_capacity(entry.uncompressed_size as usize);
+                self.entry_reader(index)?.read_to_end(&mut data)?;
+                Ok(Box::new(Cursor::new(data)))
             }
-
-            // EdgeZLib (segmented zlib) -> if sizes match, it's actually an encrypted nested archive
+            // Encrypted nested archive, see `entry_reader`
             CompressionType::EdgeZLib if entry.compressed_size == entry.uncompressed_size => {
                 let key = self.header.files_key.into();
                 let cipher = XteaPS3::new(&key, entry.iv.as_slice().into());
-                let crypto = CryptoReader::new(raw_stream, cipher);
-                Ok(Box::new(crypto))
+                let window = Window::new(&mut self.inner, start_offset, size)?;
+                Ok(Box::new(SeekableCryptoReader::new(window, cipher)?))
             }
-
-            // EdgeZLib regular segmented streaming
             CompressionType::EdgeZLib => {
-                let seg = SegmentedZlibReader::new(raw_stream);
-                Ok(Box::new(seg))
+                let window = Window::new(&mut self.inner, start_offset, size)?;
+                Ok(Box::new(SeekableSegmentedZlibReader::new(window)?))
+            }
+            CompressionType::None => {
+                Ok(Box::new(Window::new(&mut self.inner, start_offset, size)?))
             }
+        }
+    }
+}
 
-            // No compression
-            CompressionType::None => Ok(Box::new(raw_stream)),
+// Private helpers for opening entry streams
+impl<R: Read + Seek> SharcReader<R> {
+    /// Open an entry's stored payload, decrypting it if needed but not decompressing it.
+    fn open_payload(
+        &mut self,
+        index: usize,
+    ) -> io::Result<(SharcEntry, CompressionType, Box<dyn Read + '_>)> {
+        let entry = self
+            .entries
+            .get(index)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Invalid entry index"))?
+            .clone();
+
+        let start_offset = self.data_start_offset + entry.offset();
+        self.inner.seek(SeekFrom::Start(start_offset))?;
+
+        let raw_stream = (&mut self.inner).take(u64::from(entry.compressed_size));
+
+        let comp_type = CompressionType::try_from(entry.compression()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Unknown compression type in SharcEntry",
+            )
+        })?;
+
+        // Encrypted entries, and EdgeZLib entries whose sizes match (encrypted nested
+        // archives), are encrypted with XTEA-CTR
+        let encrypted = comp_type == CompressionType::Encrypted
+            || (comp_type == CompressionType::EdgeZLib
+                && entry.compressed_size == entry.uncompressed_size);
+
+        if !encrypted {
+            return Ok((entry, comp_type, Box::new(raw_stream)));
         }
+
+        let key = self.header.files_key.into();
+        let cipher = XteaPS3::new(&key, entry.iv.as_slice().into());
+        let crypto = CryptoReader::new(raw_stream, cipher);
+        Ok((entry, comp_type, Box::new(crypto)))
     }
 }
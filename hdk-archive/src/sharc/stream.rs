@@ -0,0 +1,367 @@
+use aes::cipher::KeyIvInit;
+use enumflags2::{BitFlag, BitFlags};
+use flate2::{Compression, write::ZlibEncoder};
+use rand::RngCore;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use hdk_comp::zlib::writer::SegmentedZlibWriter;
+use hdk_secure::{hash::AfsHash, writer::CryptoWriter, xtea::modes::XteaPS3};
+
+use super::structs::{SharcEntryMetadata, SharcHeader};
+use super::writer::{raw_entry_encryption, write_header, write_toc};
+use crate::archive::{ArchiveWriter, RawEntry};
+use crate::structs::{ArchiveFlags, ArchiveVersion, CompressionType, Endianness};
+use crate::utils::{CountingWriter, padding, toc_u32};
+
+/// A SHARC writer that streams entry data straight to the output.
+///
+/// Unlike [`super::writer::SharcWriter`], which keeps every compressed entry in
+/// memory until `finish()`, this writer reserves space for the header and ToC up
+/// front, writes each entry's payload as soon as it is added, then seeks back to
+/// patch the (encrypted) ToC.
+///
+/// Because the ToC size depends on the number of entries, that number must be
+/// known when the writer is created.
+pub struct SharcStreamWriter<W: Write + Seek> {
+    inner: W,
+
+    /// Where the archive starts in the underlying writer.
+    start: u64,
+
+    /// The number of entries reserved in the ToC.
+    file_count: u32,
+
+    /// SHARC header and ToC encryption key.
+    ///
+    /// See [`super::writer::SharcWriter::with_key`].
+    key: [u8; 32],
+
+    /// Home archives can be either big-endian or little-endian.
+    endianness: Endianness,
+
+    /// This should always be `512` for SHARC archives.
+    pub version: u16,
+
+    /// This holds Home archives bitflags.
+    pub flags: BitFlags<ArchiveFlags>,
+
+    /// This can be any random 16 bytes.
+    pub iv: [u8; 16],
+
+    /// Priority field in the inner header.
+    ///
+    /// Set this to `0` for standard archives.
+    pub priority: i32,
+
+    /// Timestamp field in the inner header.
+    pub timestamp: i32,
+
+    /// The XTEA key used to encrypt file entries.
+    ///
+    /// Entries are encrypted as they are added, so this can only be set before
+    /// adding any, via [`SharcStreamWriter::with_files_key`].
+    files_key: [u8; 16],
+
+    /// ToC entries written so far.
+    entries: Vec<SharcEntryMetadata>,
+
+    /// Offset of the next entry, relative to the end of the ToC.
+    next_offset: u64,
+}
+
+impl<W: Write + Seek> SharcStreamWriter<W> {
+    /// Create a new streaming SHARC archive writer.
+    ///
+    /// This immediately reserves space for the header and a ToC of `file_count` entries.
+    pub fn new(
+        mut inner: W,
+        key: [u8; 32],
+        endianness: Endianness,
+        file_count: u32,
+    ) -> io::Result<Self> {
+        let mut rng = rand::rng();
+        let mut iv = [0u8; 16];
+        rng.fill_bytes(&mut iv);
+
+        let mut files_key = [0u8; 16];
+        rng.fill_bytes(&mut files_key);
+
+        let start = inner.stream_position()?;
+
+        // Reserve preamble (8) + IV (16) + inner header (28) + ToC, patched in `finish()`
+        let reserved = 8 + 16 + 28 + u64::from(file_count) * 24;
+        io::copy(&mut io::repeat(0).take(reserved), &mut inner)?;
+
+        Ok(Self {
+            inner,
+            start,
+            file_count,
+            key,
+            endianness,
+            version: ArchiveVersion::SHARC.into(),
+            flags: ArchiveFlags::empty(),
+            iv,
+            priority: 0,
+            timestamp: 0,
+            files_key,
+            entries: Vec::with_capacity(file_count as usize),
+            next_offset: 0,
+        })
+    }
+
+    /// Set the XTEA key used to encrypt file entries.
+    ///
+    /// This must be called before adding any entry.
+    pub fn with_files_key(mut self, files_key: [u8; 16]) -> io::Result<Self> {
+        if !self.entries.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The files key cannot change after entries were added",
+            ));
+        }
+
+        self.files_key = files_key;
+        Ok(self)
+    }
+
+    /// Add an entry from a byte slice.
+    pub fn add_entry_from_bytes(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        bytes: &[u8],
+    ) -> io::Result<()> {
+        let mut cur = io::Cursor::new(bytes);
+        self.add_entry_from_reader(name_hash, compression, &mut cur)
+    }
+
+    /// Read data from `reader`, compress/encrypt as needed and write it to the output.
+    ///
+    /// If this fails, the writer is left as it was, and more entries can be added.
+    pub fn add_entry_from_reader<Rd: Read + ?Sized>(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        reader: &mut Rd,
+    ) -> io::Result<()> {
+        self.rollback_on_error(|this| this.write_entry(name_hash, compression, reader))
+    }
+
+    /// Add an entry from a payload read with [`ArchiveReader::raw_entry_reader`], without
+    /// recompressing it, and write it to the output.
+    ///
+    /// See [`super::writer::SharcWriter::add_raw_entry`]. If this fails, the writer is
+    /// left as it was, and more entries can be added.
+    ///
+    /// [`ArchiveReader::raw_entry_reader`]: crate::archive::ArchiveReader::raw_entry_reader
+    pub fn add_raw_entry(&mut self, entry: RawEntry<'_>) -> io::Result<()> {
+        self.rollback_on_error(|this| this.write_raw_entry(entry))
+    }
+
+    /// Add an entry with `write`, undoing what it wrote if it fails, so that the next
+    /// entry is written where the ToC expects it.
+    fn rollback_on_error(
+        &mut self,
+        write: impl FnOnce(&mut Self) -> io::Result<()>,
+    ) -> io::Result<()> {
+        self.check_capacity()?;
+
+        let result = write(self);
+
+        if result.is_err() {
+            // Whatever the failed entry wrote is overwritten by the next one
+            let data_start = self.start + 8 + 16 + 28 + u64::from(self.file_count) * 24;
+            self.inner
+                .seek(SeekFrom::Start(data_start + self.next_offset))?;
+        }
+
+        result
+    }
+
+    fn write_entry<Rd: Read + ?Sized>(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        reader: &mut Rd,
+    ) -> io::Result<()> {
+        let mut iv = [0u8; 8];
+        let mut out = CountingWriter::new(&mut self.inner);
+
+        let uncompressed_size = match compression {
+            CompressionType::None => io::copy(reader, &mut out)?,
+            CompressionType::ZLib => {
+                let mut enc = ZlibEncoder::new(&mut out, Compression::best());
+                let n = io::copy(reader, &mut enc)?;
+                enc.finish()?;
+                n
+            }
+            CompressionType::EdgeZLib => {
+                let mut seg = SegmentedZlibWriter::new(&mut out);
+                let n = io::copy(reader, &mut seg)?;
+                seg.finish()?;
+                n
+            }
+            // For Encrypted, encrypt compressed stream using XTEA-CTR with files_key
+            CompressionType::Encrypted => {
+                rand::rng().fill_bytes(&mut iv);
+
+                let cipher = XteaPS3::new(&self.files_key.into(), iv.as_slice().into());
+                let mut seg = SegmentedZlibWriter::new(CryptoWriter::new(&mut out, cipher));
+                let n = io::copy(reader, &mut seg)?;
+                seg.finish()?;
+                n
+            }
+        };
+
+        let compressed_size = out.count();
+
+        self.push_entry(
+            name_hash,
+            compression,
+            uncompressed_size,
+            compressed_size,
+            iv,
+        )
+    }
+
+    fn write_raw_entry(&mut self, mut entry: RawEntry<'_>) -> io::Result<()> {
+        let (compression, iv) = raw_entry_encryption(&entry);
+        let mut out = CountingWriter::new(&mut self.inner);
+
+        if let Some(iv) = iv {
+            let cipher = XteaPS3::new(&self.files_key.into(), iv.as_slice().into());
+            io::copy(&mut entry.reader, &mut CryptoWriter::new(&mut out, cipher))?;
+        } else {
+            io::copy(&mut entry.reader, &mut out)?;
+        }
+
+        let compressed_size = out.count();
+
+        self.push_entry(
+            entry.name_hash,
+            compression,
+            u64::from(entry.uncompressed_size),
+            compressed_size,
+            iv.unwrap_or_default(),
+        )
+    }
+
+    fn check_capacity(&self) -> io::Result<()> {
+        if self.entries.len() >= self.file_count as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "More entries added than reserved in the ToC",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Pad the entry just written and record it in the ToC.
+    fn push_entry(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        uncompressed_size: u64,
+        compressed_size: u64,
+        iv: [u8; 8],
+    ) -> io::Result<()> {
+        let offset = self.next_offset;
+        let metadata = SharcEntryMetadata {
+            name_hash,
+            offset,
+            compression_raw: compression.into(),
+            uncompressed_size: toc_u32(uncompressed_size)?,
+            compressed_size: toc_u32(compressed_size)?,
+            iv,
+        };
+
+        // 4-byte alignment padding
+        let mut pad = vec![0u8; padding(compressed_size) as usize];
+        rand::rng().fill_bytes(&mut pad);
+        self.inner.write_all(&pad)?;
+
+        self.next_offset = offset + compressed_size + pad.len() as u64;
+        self.entries.push(metadata);
+
+        Ok(())
+    }
+
+    /// Patch the header and ToC, and return the underlying writer positioned at the end
+    /// of the archive.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.entries.len() != self.file_count as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Expected {} entries, but {} were added",
+                    self.file_count,
+                    self.entries.len()
+                ),
+            ));
+        }
+
+        let header = SharcHeader {
+            version: self.version,
+            flags: self.flags,
+            iv: self.iv,
+            priority: self.priority,
+            timestamp: self.timestamp,
+            file_count: self.file_count,
+            files_key: self.files_key,
+        };
+
+        let end = self.inner.stream_position()?;
+        self.inner.seek(SeekFrom::Start(self.start))?;
+
+        write_header(&mut self.inner, &self.key, &header, self.endianness)?;
+        write_toc(
+            &mut self.inner,
+            &self.key,
+            self.iv,
+            self.endianness,
+            self.entries.iter().copied(),
+        )?;
+
+        self.inner.seek(SeekFrom::Start(end))?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write + Seek> ArchiveWriter for SharcStreamWriter<W> {
+    type Output = W;
+
+    fn add_entry_from_reader<R: Read + ?Sized>(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        reader: &mut R,
+    ) -> io::Result<()> {
+        Self::add_entry_from_reader(self, name_hash, compression, reader)
+    }
+
+    fn add_raw_entry(&mut self, entry: RawEntry<'_>) -> io::Result<()> {
+        Self::add_raw_entry(self, entry)
+    }
+
+    fn flags(&self) -> BitFlags<ArchiveFlags> {
+        self.flags
+    }
+
+    fn set_flags(&mut self, flags: BitFlags<ArchiveFlags>) -> io::Result<()> {
+        self.flags = flags;
+        Ok(())
+    }
+
+    fn set_priority(&mut self, priority: i32) {
+        self.priority = priority;
+    }
+
+    fn set_timestamp(&mut self, timestamp: i32) {
+        self.timestamp = timestamp;
+    }
+
+    fn finish(self) -> io::Result<W> {
+        Self::finish(self)
+    }
+}
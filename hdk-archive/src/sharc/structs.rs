@@ -3,7 +3,8 @@ use enumflags2::BitFlags;
 
 use std::convert::TryInto;
 
-use crate::structs::ArchiveFlags;
+use crate::archive::EntryMetadata;
+use crate::structs::{ArchiveFlags, CompressionType};
 use hdk_secure::hash::AfsHash;
 
 // 1. The Raw Entry (as it appears in the decrypted ToC)
@@ -73,6 +74,24 @@ impl TryFrom<&SharcEntry> for SharcEntryMetadata {
     }
 }
 
+impl EntryMetadata for SharcEntryMetadata {
+    fn name_hash(&self) -> AfsHash {
+        self.name_hash
+    }
+
+    fn compression(&self) -> CompressionType {
+        CompressionType::try_from(self.compression_raw).unwrap_or(CompressionType::None)
+    }
+
+    fn uncompressed_size(&self) -> u32 {
+        self.uncompressed_size
+    }
+
+    fn compressed_size(&self) -> u32 {
+        self.compressed_size
+    }
+}
+
 // 2. The Unencrypted Preamble (File Start)
 #[derive(BinRead, Debug, Clone)]
 pub struct SharcPreamble {
@@ -106,6 +125,7 @@ pub struct SharcHeader {
     pub flags: BitFlags<ArchiveFlags>,
     pub iv: [u8; 16],
     pub priority: i32,
+    /// Kept in the `.time` file of extracted archives, see [`crate::time`].
     pub timestamp: i32,
     pub file_count: u32,
     pub files_key: [u8; 16],
@@ -38,3 +38,315 @@ fn roundtrip_write_read() {
 
     assert_eq!(archive.header().file_count, 3);
 }
+
+#[test]
+fn stream_writer_roundtrip() {
+    use std::io::{Cursor, Read};
+
+    use hdk_secure::hash::AfsHash;
+
+    use crate::archive::ArchiveReader;
+    use crate::sharc::reader::SharcReader;
+    use crate::sharc::stream::SharcStreamWriter;
+    use crate::structs::{CompressionType, Endianness};
+
+    let test_key: [u8; 32] = [7; 32];
+    let big: Vec<u8> = (0..150_000u32).map(|i| (i % 13) as u8).collect();
+    let files: [(&str, CompressionType, &[u8]); 4] = [
+        ("plain.txt", CompressionType::None, b"abcde"),
+        ("zlib.xml", CompressionType::ZLib, b"<xml>zlib</xml>"),
+        ("edge.dds", CompressionType::EdgeZLib, &big),
+        ("secret.lua", CompressionType::Encrypted, &big[..70_000]),
+    ];
+
+    for endianness in [Endianness::Little, Endianness::Big] {
+        // Leading bytes check that the archive may start anywhere in the output
+        let mut out = Cursor::new(vec![0xEE; 3]);
+        out.set_position(3);
+
+        let mut w = SharcStreamWriter::new(out, test_key, endianness, files.len() as u32).unwrap();
+        for (name, compression, data) in files {
+            w.add_entry_from_bytes(AfsHash::new_from_str(name), compression, data)
+                .unwrap();
+        }
+        let out = w.finish().unwrap().into_inner();
+
+        let mut archive = SharcReader::open(Cursor::new(out[3..].to_vec()), test_key).unwrap();
+        assert_eq!(archive.entry_count(), files.len());
+
+        for (i, (name, _, data)) in files.iter().enumerate() {
+            assert_eq!(
+                archive.entry_metadata(i).unwrap().name_hash,
+                AfsHash::new_from_str(name)
+            );
+
+            let mut content = Vec::new();
+            archive
+                .entry_reader(i)
+                .unwrap()
+                .read_to_end(&mut content)
+                .unwrap();
+            assert_eq!(&content, data);
+        }
+    }
+}
+
+#[test]
+fn stream_writer_recovers_from_failed_entry() {
+    use std::io::{Cursor, Read};
+
+    use hdk_secure::hash::AfsHash;
+
+    use crate::archive::ArchiveReader;
+    use crate::sharc::reader::SharcReader;
+    use crate::sharc::stream::SharcStreamWriter;
+    use crate::structs::{CompressionType, Endianness};
+
+    /// A reader that fails once `data` is read.
+    struct FailingReader<'a> {
+        data: &'a [u8],
+    }
+
+    impl Read for FailingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.data.is_empty() {
+                return Err(std::io::Error::other("source failed"));
+            }
+
+            self.data.read(buf)
+        }
+    }
+
+    let test_key: [u8; 32] = [7; 32];
+
+    for compression in [
+        CompressionType::None,
+        CompressionType::ZLib,
+        CompressionType::EdgeZLib,
+        CompressionType::Encrypted,
+    ] {
+        let mut w =
+            SharcStreamWriter::new(Cursor::new(Vec::new()), test_key, Endianness::Little, 2)
+                .unwrap();
+        w.add_entry_from_bytes(AfsHash::new_from_str("a"), compression, b"first")
+            .unwrap();
+
+        let mut broken = FailingReader {
+            data: &[0x55; 1000],
+        };
+        assert!(
+            w.add_entry_from_reader(AfsHash::new_from_str("b"), compression, &mut broken)
+                .is_err()
+        );
+
+        w.add_entry_from_bytes(AfsHash::new_from_str("c"), compression, b"second")
+            .unwrap();
+        let out = w.finish().unwrap().into_inner();
+
+        let mut archive = SharcReader::open(Cursor::new(out), test_key).unwrap();
+        assert_eq!(archive.entry_count(), 2);
+
+        for (i, expected) in [&b"first"[..], b"second"].into_iter().enumerate() {
+            let mut content = Vec::new();
+            archive
+                .entry_reader(i)
+                .unwrap()
+                .read_to_end(&mut content)
+                .unwrap();
+            assert_eq!(content, expected, "{compression:?}");
+        }
+    }
+}
+
+#[test]
+fn seekable_entry_readers() {
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    use hdk_secure::hash::AfsHash;
+
+    use crate::archive::ArchiveReader;
+    use crate::sharc::reader::SharcReader;
+    use crate::sharc::writer::SharcWriter;
+    use crate::structs::{CompressionType, Endianness};
+
+    let test_key: [u8; 32] = [3; 32];
+    let big: Vec<u8> = (0..300_000u32).map(|i| (i * 7 % 251) as u8).collect();
+    let compressions = [
+        CompressionType::None,
+        CompressionType::ZLib,
+        CompressionType::EdgeZLib,
+        CompressionType::Encrypted,
+    ];
+
+    let mut w = SharcWriter::new(Vec::new(), test_key, Endianness::Big).unwrap();
+    for (i, compression) in compressions.iter().enumerate() {
+        w.add_entry_from_bytes(
+            AfsHash::new_from_str(&format!("big{i}")),
+            *compression,
+            &big,
+        )
+        .unwrap();
+    }
+    let out = w.finish().unwrap();
+
+    let mut archive = SharcReader::open(Cursor::new(out), test_key).unwrap();
+    for i in 0..compressions.len() {
+        let mut reader = archive.entry_seekable_reader(i).unwrap();
+
+        for offset in [250_000u64, 65_530, 3] {
+            let mut buf = [0u8; 40];
+            reader.seek(SeekFrom::Start(offset)).unwrap();
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, big[offset as usize..offset as usize + 40]);
+        }
+
+        let mut rest = Vec::new();
+        reader.seek(SeekFrom::Current(-43)).unwrap();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, big);
+    }
+}
+
+#[test]
+fn raw_repack_keeps_payloads_and_ivs() {
+    use std::io::{Cursor, Read};
+
+    use hdk_secure::hash::AfsHash;
+
+    use crate::archive::{ArchiveReader, RawEntry};
+    use crate::sharc::reader::SharcReader;
+    use crate::sharc::stream::SharcStreamWriter;
+    use crate::sharc::writer::SharcWriter;
+    use crate::structs::{CompressionType, Endianness};
+
+    let test_key: [u8; 32] = [9; 32];
+    let big: Vec<u8> = (0..90_000u32).map(|i| (i % 97) as u8).collect();
+    let nested = b"nested archive, stored encrypted";
+
+    let mut w = SharcWriter::new(Vec::new(), test_key, Endianness::Big).unwrap();
+    for (i, compression) in [
+        CompressionType::None,
+        CompressionType::ZLib,
+        CompressionType::EdgeZLib,
+        CompressionType::Encrypted,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        w.add_entry_from_bytes(AfsHash::new_from_str(&format!("f{i}")), compression, &big)
+            .unwrap();
+    }
+    // Uncompressed entries with an IV are written as encrypted nested archives
+    w.add_raw_entry(RawEntry {
+        name_hash: AfsHash::new_from_str("nested.sharc"),
+        compression: CompressionType::None,
+        uncompressed_size: nested.len() as u32,
+        iv: Some([5; 8]),
+        checksum: None,
+        reader: Box::new(&nested[..]),
+    })
+    .unwrap();
+    let files_key = w.files_key;
+    let data = w.finish().unwrap();
+
+    let mut archive = SharcReader::open(Cursor::new(data), test_key).unwrap();
+    let nested_meta = archive.entry_metadata(4).unwrap();
+    assert_eq!(nested_meta.compression_raw, CompressionType::EdgeZLib as u8);
+    assert_eq!(nested_meta.compressed_size, nested_meta.uncompressed_size);
+    assert_eq!(nested_meta.iv, [5; 8]);
+
+    let mut memory = SharcWriter::new(Vec::new(), test_key, Endianness::Little).unwrap();
+    memory.files_key = files_key;
+    let stream = SharcStreamWriter::new(
+        Cursor::new(Vec::new()),
+        test_key,
+        Endianness::Little,
+        archive.entry_count() as u32,
+    )
+    .unwrap();
+    let mut stream = stream.with_files_key(files_key).unwrap();
+
+    for i in archive.entry_indices() {
+        memory
+            .add_raw_entry(archive.raw_entry_reader(i).unwrap())
+            .unwrap();
+        stream
+            .add_raw_entry(archive.raw_entry_reader(i).unwrap())
+            .unwrap();
+    }
+
+    let outputs = [
+        memory.finish().unwrap(),
+        stream.finish().unwrap().into_inner(),
+    ];
+    for out in outputs {
+        let mut repacked = SharcReader::open(Cursor::new(out), test_key).unwrap();
+        assert_eq!(repacked.entry_count(), archive.entry_count());
+
+        for i in archive.entry_indices() {
+            // Same payloads and IVs, so same sizes and offsets too
+            assert_eq!(
+                repacked.entry_metadata(i).unwrap(),
+                archive.entry_metadata(i).unwrap()
+            );
+
+            let mut content = Vec::new();
+            repacked
+                .entry_reader(i)
+                .unwrap()
+                .read_to_end(&mut content)
+                .unwrap();
+            if i == 4 {
+                assert_eq!(content, nested);
+            } else {
+                assert_eq!(content, big);
+            }
+        }
+    }
+}
+
+#[test]
+fn lookup_by_path() {
+    use std::io::{Cursor, Read};
+
+    use hdk_secure::hash::AfsHash;
+
+    use crate::archive::ArchiveReader;
+    use crate::error::ArchiveError;
+    use crate::sharc::reader::SharcReader;
+    use crate::sharc::writer::SharcWriter;
+    use crate::structs::{CompressionType, Endianness};
+
+    let test_key: [u8; 32] = [1; 32];
+    let mut w = SharcWriter::new(Vec::new(), test_key, Endianness::Big).unwrap();
+    for (path, content) in [
+        ("a.txt", &b"first"[..]),
+        ("textures/b.dds", b"second"),
+        ("a.txt", b"again"),
+    ] {
+        w.add_entry_from_bytes(
+            AfsHash::new_from_str(path),
+            CompressionType::Encrypted,
+            content,
+        )
+        .unwrap();
+    }
+    let data = w.finish().unwrap();
+
+    let mut archive = SharcReader::open(Cursor::new(data), test_key).unwrap();
+
+    let mut out = Vec::new();
+    archive
+        .open_path("Textures\\B.dds")
+        .unwrap()
+        .reader
+        .read_to_end(&mut out)
+        .unwrap();
+    assert_eq!(out, b"second");
+
+    assert_eq!(archive.find_by_path("c.txt").unwrap(), None);
+    assert!(matches!(
+        archive.find_by_path("a.txt"),
+        Err(ArchiveError::DuplicateHash { indices, .. }) if indices == [0, 2]
+    ));
+}
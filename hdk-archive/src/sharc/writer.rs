@@ -10,7 +10,10 @@ use std::io::{self, Read, Write};
 use hdk_comp::zlib::writer::SegmentedZlibWriter;
 use hdk_secure::{hash::AfsHash, xtea::modes::XteaPS3};
 
-use crate::structs::{ARCHIVE_MAGIC, ArchiveFlags, CompressionType, Endianness};
+use super::structs::{SharcEntryMetadata, SharcHeader};
+use crate::archive::{ArchiveWriter, RawEntry};
+use crate::structs::{ARCHIVE_MAGIC, ArchiveFlags, ArchiveVersion, CompressionType, Endianness};
+use crate::utils::toc_u32;
 
 /// Helper small struct to hold a queued entry for writing
 struct EntryToWrite {
@@ -109,7 +112,7 @@ impl<W: Write> SharcWriter<W> {
             inner,
             key,
             endianness,
-            version: 1,
+            version: ArchiveVersion::SHARC.into(),
             flags: ArchiveFlags::empty(),
             iv,
             priority: 0,
@@ -195,12 +198,42 @@ impl<W: Write> SharcWriter<W> {
         Ok(())
     }
 
-    pub fn finish(mut self) -> io::Result<W> {
-        // Header sizes
-        const INNER_SIZE: usize = 4 + 4 + 4 + 16; // priority + timestamp + file_count + files_key
+    /// Add an entry from a payload read with [`ArchiveReader::raw_entry_reader`], without
+    /// recompressing it.
+    ///
+    /// The payload is stored as-is, except for encrypted entries (and encrypted nested
+    /// archives), which are encrypted with `files_key` and the entry's original IV, or
+    /// a random one if it came from a BAR.
+    ///
+    /// [`ArchiveReader::raw_entry_reader`]: crate::archive::ArchiveReader::raw_entry_reader
+    pub fn add_raw_entry(&mut self, mut entry: RawEntry<'_>) -> io::Result<()> {
+        let (compression, iv) = raw_entry_encryption(&entry);
+        let mut data = Vec::new();
+
+        if let Some(iv) = iv {
+            let cipher = XteaPS3::new(&self.files_key.into(), iv.as_slice().into());
+            let mut cw = hdk_secure::writer::CryptoWriter::new(data, cipher);
+            io::copy(&mut entry.reader, &mut cw)?;
+            data = cw.into_inner();
+        } else {
+            entry.reader.read_to_end(&mut data)?;
+        }
+
+        self.entries.push(EntryToWrite {
+            name_hash: entry.name_hash,
+            compression,
+            uncompressed_size: entry.uncompressed_size,
+            compressed_size: toc_u32(data.len() as u64)?,
+            iv: iv.unwrap_or_default(),
+            data,
+            offset: 0,
+        });
 
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
         let file_count = self.entries.len() as u32;
-        let toc_size = (file_count as usize) * 24;
 
         // Compute offsets (relative to the start of the data section).
         // The reader interprets entry offsets as `data_start_offset + entry.offset()`.
@@ -218,81 +251,27 @@ impl<W: Write> SharcWriter<W> {
                 .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Size overflow"))?;
         }
 
-        // 1) Write Preamble (plain)
-        match self.endianness {
-            Endianness::Little => {
-                self.inner.write_u32::<LittleEndian>(ARCHIVE_MAGIC)?;
-                let flags_and_version =
-                    (u32::from(self.version) << 16) | u32::from(self.flags.bits());
-                self.inner.write_u32::<LittleEndian>(flags_and_version)?;
-            }
-            Endianness::Big => {
-                self.inner.write_u32::<BigEndian>(ARCHIVE_MAGIC)?;
-                let flags_and_version =
-                    (u32::from(self.version) << 16) | u32::from(self.flags.bits());
-                self.inner.write_u32::<BigEndian>(flags_and_version)?;
-            }
-        }
-        self.inner.write_all(&self.iv)?;
+        let header = SharcHeader {
+            version: self.version,
+            flags: self.flags,
+            iv: self.iv,
+            priority: self.priority,
+            timestamp: self.timestamp,
+            file_count,
+            files_key: self.files_key,
+        };
 
-        // 2) Inner header (encrypt with AES-CTR using `key` and `iv`)
-        let mut inner_buf = Vec::with_capacity(INNER_SIZE);
-        match self.endianness {
-            Endianness::Little => {
-                inner_buf.write_i32::<LittleEndian>(self.priority)?;
-                inner_buf.write_i32::<LittleEndian>(self.timestamp)?;
-                inner_buf.write_u32::<LittleEndian>(file_count)?;
-            }
-            Endianness::Big => {
-                inner_buf.write_i32::<BigEndian>(self.priority)?;
-                inner_buf.write_i32::<BigEndian>(self.timestamp)?;
-                inner_buf.write_u32::<BigEndian>(file_count)?;
-            }
-        }
-        inner_buf.extend_from_slice(&self.files_key);
-
-        // Encrypt inner_buf with AES-256 CTR using iv using CryptoWriter
-        let mut cw = hdk_secure::writer::CryptoWriter::new(
-            Vec::new(),
-            Ctr128BE::<Aes256>::new(&self.key.into(), self.iv.as_slice().into()),
-        );
-        cw.write_all(&inner_buf)?;
-        let enc_inner = cw.into_inner();
-        self.inner.write_all(&enc_inner)?;
-
-        // 3) Build ToC (plain) then encrypt with iv+1
-        let mut toc_buf: Vec<u8> = Vec::with_capacity(toc_size);
-        for e in &self.entries {
-            match self.endianness {
-                Endianness::Little => {
-                    toc_buf.write_i32::<LittleEndian>(e.name_hash.0)?;
-                    let offset_and_comp = (e.offset & 0xFFFFFFFC) | (e.compression as u32);
-                    toc_buf.write_u32::<LittleEndian>(offset_and_comp)?;
-                    toc_buf.write_u32::<LittleEndian>(e.uncompressed_size)?;
-                    toc_buf.write_u32::<LittleEndian>(e.compressed_size)?;
-                }
-                Endianness::Big => {
-                    toc_buf.write_i32::<BigEndian>(e.name_hash.0)?;
-                    let offset_and_comp = (e.offset & 0xFFFFFFFC) | (e.compression as u32);
-                    toc_buf.write_u32::<BigEndian>(offset_and_comp)?;
-                    toc_buf.write_u32::<BigEndian>(e.uncompressed_size)?;
-                    toc_buf.write_u32::<BigEndian>(e.compressed_size)?;
-                }
-            }
-            toc_buf.extend_from_slice(&e.iv);
-        }
+        // 1) + 2) Preamble and encrypted inner header
+        write_header(&mut self.inner, &self.key, &header, self.endianness)?;
 
-        // Encrypt ToC with iv + 1 using CryptoWriter
-        let mut iv_int = u128::from_be_bytes(self.iv);
-        iv_int = iv_int.wrapping_add(1);
-        let iv_inc = iv_int.to_be_bytes();
-        let mut toc_cw = hdk_secure::writer::CryptoWriter::new(
-            Vec::new(),
-            Ctr128BE::<Aes256>::new(&self.key.into(), &iv_inc.into()),
-        );
-        toc_cw.write_all(&toc_buf)?;
-        let enc_toc = toc_cw.into_inner();
-        self.inner.write_all(&enc_toc)?;
+        // 3) Encrypted ToC
+        write_toc(
+            &mut self.inner,
+            &self.key,
+            self.iv,
+            self.endianness,
+            self.entries.iter().map(EntryToWrite::metadata),
+        )?;
 
         // 4) Entries data + padding
         let mut rng = rand::rng();
@@ -310,3 +289,172 @@ impl<W: Write> SharcWriter<W> {
         Ok(self.inner)
     }
 }
+
+impl EntryToWrite {
+    const fn metadata(&self) -> SharcEntryMetadata {
+        SharcEntryMetadata {
+            name_hash: self.name_hash,
+            offset: self.offset as u64,
+            compression_raw: self.compression as u8,
+            uncompressed_size: self.uncompressed_size,
+            compressed_size: self.compressed_size,
+            iv: self.iv,
+        }
+    }
+}
+
+/// Decide how a raw entry is stored in a SHARC: its ToC compression type, and the IV
+/// to encrypt it with, if it must be encrypted.
+///
+/// Uncompressed entries with an IV are encrypted nested archives, which SHARC marks
+/// as `EdgeZLib` with matching sizes.
+pub(super) fn raw_entry_encryption(entry: &RawEntry<'_>) -> (CompressionType, Option<[u8; 8]>) {
+    match (entry.compression, entry.iv) {
+        (CompressionType::Encrypted, iv) => (
+            CompressionType::Encrypted,
+            Some(iv.unwrap_or_else(|| {
+                let mut iv = [0u8; 8];
+                rand::rng().fill_bytes(&mut iv);
+                iv
+            })),
+        ),
+        (CompressionType::None, Some(iv)) => (CompressionType::EdgeZLib, Some(iv)),
+        (compression, _) => (compression, None),
+    }
+}
+
+/// Write the plain preamble followed by the inner header, encrypted with AES-CTR
+/// using `key` and the header's IV.
+pub(super) fn write_header<W: Write>(
+    out: &mut W,
+    key: &[u8; 32],
+    header: &SharcHeader,
+    endianness: Endianness,
+) -> io::Result<()> {
+    // Header sizes
+    const INNER_SIZE: usize = 4 + 4 + 4 + 16; // priority + timestamp + file_count + files_key
+
+    let flags_and_version = (u32::from(header.version) << 16) | u32::from(header.flags.bits());
+
+    // 1) Write Preamble (plain)
+    match endianness {
+        Endianness::Little => {
+            out.write_u32::<LittleEndian>(ARCHIVE_MAGIC)?;
+            out.write_u32::<LittleEndian>(flags_and_version)?;
+        }
+        Endianness::Big => {
+            out.write_u32::<BigEndian>(ARCHIVE_MAGIC)?;
+            out.write_u32::<BigEndian>(flags_and_version)?;
+        }
+    }
+    out.write_all(&header.iv)?;
+
+    // 2) Inner header (encrypt with AES-CTR using `key` and `iv`)
+    let mut inner_buf = Vec::with_capacity(INNER_SIZE);
+    match endianness {
+        Endianness::Little => {
+            inner_buf.write_i32::<LittleEndian>(header.priority)?;
+            inner_buf.write_i32::<LittleEndian>(header.timestamp)?;
+            inner_buf.write_u32::<LittleEndian>(header.file_count)?;
+        }
+        Endianness::Big => {
+            inner_buf.write_i32::<BigEndian>(header.priority)?;
+            inner_buf.write_i32::<BigEndian>(header.timestamp)?;
+            inner_buf.write_u32::<BigEndian>(header.file_count)?;
+        }
+    }
+    inner_buf.extend_from_slice(&header.files_key);
+
+    // Encrypt inner_buf with AES-256 CTR using iv using CryptoWriter
+    let mut cw = hdk_secure::writer::CryptoWriter::new(
+        &mut *out,
+        Ctr128BE::<Aes256>::new(key.into(), header.iv.as_slice().into()),
+    );
+    cw.write_all(&inner_buf)
+}
+
+/// Build the plain ToC, then write it encrypted with AES-CTR using `key` and `iv + 1`.
+pub(super) fn write_toc<W: Write>(
+    out: &mut W,
+    key: &[u8; 32],
+    iv: [u8; 16],
+    endianness: Endianness,
+    entries: impl IntoIterator<Item = SharcEntryMetadata>,
+) -> io::Result<()> {
+    let mut toc_buf: Vec<u8> = Vec::new();
+    for e in entries {
+        let offset = toc_u32(e.offset)?;
+        let offset_and_comp = (offset & 0xFFFFFFFC) | u32::from(e.compression_raw);
+
+        match endianness {
+            Endianness::Little => {
+                toc_buf.write_i32::<LittleEndian>(e.name_hash.0)?;
+                toc_buf.write_u32::<LittleEndian>(offset_and_comp)?;
+                toc_buf.write_u32::<LittleEndian>(e.uncompressed_size)?;
+                toc_buf.write_u32::<LittleEndian>(e.compressed_size)?;
+            }
+            Endianness::Big => {
+                toc_buf.write_i32::<BigEndian>(e.name_hash.0)?;
+                toc_buf.write_u32::<BigEndian>(offset_and_comp)?;
+                toc_buf.write_u32::<BigEndian>(e.uncompressed_size)?;
+                toc_buf.write_u32::<BigEndian>(e.compressed_size)?;
+            }
+        }
+        toc_buf.extend_from_slice(&e.iv);
+    }
+
+    // Encrypt ToC with iv + 1 using CryptoWriter
+    let iv_inc = u128::from_be_bytes(iv).wrapping_add(1).to_be_bytes();
+    let mut toc_cw = hdk_secure::writer::CryptoWriter::new(
+        out,
+        Ctr128BE::<Aes256>::new(key.into(), &iv_inc.into()),
+    );
+    toc_cw.write_all(&toc_buf)
+}
+
+impl<W: Write> ArchiveWriter for SharcWriter<W> {
+    type Output = W;
+
+    fn add_entry_from_reader<R: Read + ?Sized>(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        reader: &mut R,
+    ) -> io::Result<()> {
+        Self::add_entry_from_reader(self, name_hash, compression, reader)
+    }
+
+    fn add_entry_from_bytes(
+        &mut self,
+        name_hash: AfsHash,
+        compression: CompressionType,
+        bytes: &[u8],
+    ) -> io::Result<()> {
+        Self::add_entry_from_bytes(self, name_hash, compression, bytes)
+    }
+
+    fn add_raw_entry(&mut self, entry: RawEntry<'_>) -> io::Result<()> {
+        Self::add_raw_entry(self, entry)
+    }
+
+    fn flags(&self) -> BitFlags<ArchiveFlags> {
+        self.flags
+    }
+
+    fn set_flags(&mut self, flags: BitFlags<ArchiveFlags>) -> io::Result<()> {
+        self.flags = flags;
+        Ok(())
+    }
+
+    fn set_priority(&mut self, priority: i32) {
+        self.priority = priority;
+    }
+
+    fn set_timestamp(&mut self, timestamp: i32) {
+        self.timestamp = timestamp;
+    }
+
+    fn finish(self) -> io::Result<W> {
+        Self::finish(self)
+    }
+}
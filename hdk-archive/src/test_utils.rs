@@ -0,0 +1,67 @@
+//! Keys and archive builders shared by the test modules.
+
+use std::io::Cursor;
+
+use hdk_secure::hash::AfsHash;
+
+use crate::any::ArchiveKeys;
+use crate::archive::ArchiveWriter;
+use crate::bar::{BarReader, BarWriter};
+use crate::sharc::reader::SharcReader;
+use crate::sharc::writer::SharcWriter;
+use crate::structs::{CompressionType, Endianness};
+
+/// Test default Blowfish key (32 bytes) for encrypted file bodies.
+pub const TEST_DEFAULT_KEY: [u8; 32] = [0xAA; 32];
+
+/// Test signature Blowfish key (32 bytes) for encrypted file headers.
+pub const TEST_SIGNATURE_KEY: [u8; 32] = [0xBB; 32];
+
+/// Test SHARC header and ToC key.
+pub const TEST_SHARC_KEY: [u8; 32] = [0xCC; 32];
+
+/// Keys for every archive built with the test keys.
+pub const TEST_KEYS: ArchiveKeys = ArchiveKeys::new()
+    .with_bar_keys(TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY)
+    .with_sharc_key(TEST_SHARC_KEY);
+
+/// Header timestamp of the archives built by [`create_bar`] and [`create_sharc`].
+pub const TEST_TIMESTAMP: i32 = 0x4F2A_1B3C;
+
+/// An entry's path, compression and content.
+pub type TestEntry<'a> = (&'a str, CompressionType, &'a [u8]);
+
+/// A BAR holding `entries`, in order.
+pub fn create_bar(entries: &[TestEntry<'_>]) -> Vec<u8> {
+    let mut writer = BarWriter::new(Vec::new(), TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY);
+    writer.set_timestamp(TEST_TIMESTAMP);
+
+    write_entries(writer, entries)
+}
+
+/// A SHARC holding `entries`, in order.
+pub fn create_sharc(entries: &[TestEntry<'_>], endianness: Endianness) -> Vec<u8> {
+    let mut writer = SharcWriter::new(Vec::new(), TEST_SHARC_KEY, endianness).unwrap();
+    writer.set_timestamp(TEST_TIMESTAMP);
+
+    write_entries(writer, entries)
+}
+
+/// Add `entries` to `writer`, in order, and finish it.
+pub fn write_entries<W: ArchiveWriter>(mut writer: W, entries: &[TestEntry<'_>]) -> W::Output {
+    for &(path, compression, data) in entries {
+        writer
+            .add_entry_from_bytes(AfsHash::new_from_str(path), compression, data)
+            .unwrap();
+    }
+
+    writer.finish().unwrap()
+}
+
+pub fn open_bar(data: Vec<u8>) -> BarReader<Cursor<Vec<u8>>> {
+    BarReader::open(Cursor::new(data), TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY).unwrap()
+}
+
+pub fn open_sharc(data: Vec<u8>) -> SharcReader<Cursor<Vec<u8>>> {
+    SharcReader::open(Cursor::new(data), TEST_SHARC_KEY).unwrap()
+}
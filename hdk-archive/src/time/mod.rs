@@ -0,0 +1,150 @@
+//! The `.time` file that sits next to extracted archives.
+//!
+//! Archive paths are not stored in BAR and SHARC archives, but their header
+//! `timestamp` is, and the game refuses to mount an archive whose timestamp does not
+//! match the one it expects (SEC error -6 in the logs). Tools that extract an
+//! archive to a folder therefore keep the timestamp in a `.time` file, as 8
+//! uppercase hexadecimal digits, so it can be restored when repacking.
+//!
+//! [`unpack_to_directory`](crate::unpack::unpack_to_directory) writes this file and
+//! [`pack_directory`](crate::pack::pack_directory) reads it back.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use hdk_archive::bar::BarReader;
+//! use hdk_archive::time::{TimeFileStatus, validate};
+//!
+//! let file = std::fs::File::open("path/to/archive.bar").unwrap();
+//! let archive = BarReader::open(file, [0u8; 32], [0u8; 32]).unwrap();
+//!
+//! match validate(&archive, "path/to/extracted".as_ref()).unwrap() {
+//!     TimeFileStatus::Consistent => {}
+//!     TimeFileStatus::Missing => println!("No .time file!"),
+//!     TimeFileStatus::Mismatch { header, time_file } => {
+//!         println!("Header has {header:08X}, .time has {time_file:08X}")
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::archive::ArchiveReader;
+
+#[cfg(test)]
+mod tests;
+
+/// Name of the file holding the header timestamp, in an extracted archive's folder.
+pub const TIME_FILE_NAME: &str = ".time";
+
+/// The content of a `.time` file: an archive header's timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimeFile {
+    pub timestamp: i32,
+}
+
+impl TimeFile {
+    pub const fn new(timestamp: i32) -> Self {
+        Self { timestamp }
+    }
+
+    /// The `.time` file matching an archive's header.
+    pub fn from_archive<A: ArchiveReader>(archive: &A) -> Self {
+        Self::new(archive.timestamp())
+    }
+
+    /// Parse the content of a `.time` file.
+    ///
+    /// Surrounding whitespace and a `0x` prefix are tolerated.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let text = text.trim();
+        let digits = text
+            .strip_prefix("0x")
+            .or_else(|| text.strip_prefix("0X"))
+            .unwrap_or(text);
+
+        u32::from_str_radix(digits, 16)
+            .map(|value| Self::new(value as i32))
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid .time file content {text:?}: {e}"),
+                )
+            })
+    }
+
+    /// Read the `.time` file in `dir`, if there is one.
+    pub fn read(dir: &Path) -> io::Result<Option<Self>> {
+        match std::fs::read_to_string(dir.join(TIME_FILE_NAME)) {
+            Ok(text) => Self::parse(&text).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write the `.time` file in `dir`, replacing any existing one.
+    pub fn write(&self, dir: &Path) -> io::Result<()> {
+        std::fs::write(dir.join(TIME_FILE_NAME), self.to_string())
+    }
+}
+
+impl From<i32> for TimeFile {
+    fn from(timestamp: i32) -> Self {
+        Self::new(timestamp)
+    }
+}
+
+impl From<TimeFile> for i32 {
+    fn from(time_file: TimeFile) -> Self {
+        time_file.timestamp
+    }
+}
+
+impl fmt::Display for TimeFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08X}", self.timestamp as u32)
+    }
+}
+
+/// How an archive's header timestamp compares to a folder's `.time` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeFileStatus {
+    /// The `.time` file holds the header timestamp.
+    Consistent,
+
+    /// There is no `.time` file.
+    Missing,
+
+    /// The `.time` file holds a different timestamp.
+    Mismatch { header: i32, time_file: i32 },
+}
+
+impl TimeFileStatus {
+    /// Compare a header timestamp with the content of a `.time` file, if any.
+    pub const fn compare(header: i32, time_file: Option<TimeFile>) -> Self {
+        match time_file {
+            None => Self::Missing,
+            Some(time_file) if time_file.timestamp == header => Self::Consistent,
+            Some(time_file) => Self::Mismatch {
+                header,
+                time_file: time_file.timestamp,
+            },
+        }
+    }
+
+    pub const fn is_consistent(&self) -> bool {
+        matches!(self, Self::Consistent)
+    }
+}
+
+/// Check that the `.time` file in `dir` matches the header timestamp of `archive`.
+///
+/// Fails if the `.time` file exists but cannot be read or parsed.
+pub fn validate<A: ArchiveReader>(archive: &A, dir: &Path) -> io::Result<TimeFileStatus> {
+    Ok(TimeFileStatus::compare(
+        archive.timestamp(),
+        TimeFile::read(dir)?,
+    ))
+}
@@ -0,0 +1,62 @@
+use crate::structs::Endianness;
+use crate::test_utils::{TEST_TIMESTAMP, create_sharc, open_sharc};
+use crate::time::{TIME_FILE_NAME, TimeFile, TimeFileStatus, validate};
+
+#[test]
+fn parse_and_format() {
+    assert_eq!(TimeFile::parse("4F2A1B3C").unwrap().timestamp, 0x4F2A_1B3C);
+    assert_eq!(
+        TimeFile::parse(" 0x4f2a1b3c\r\n").unwrap().timestamp,
+        0x4F2A_1B3C
+    );
+    assert_eq!(TimeFile::parse("FFFFFFFF").unwrap().timestamp, -1);
+
+    assert_eq!(TimeFile::new(0x1B3C).to_string(), "00001B3C");
+    assert_eq!(TimeFile::new(-1).to_string(), "FFFFFFFF");
+
+    assert!(TimeFile::parse("").is_err());
+    assert!(TimeFile::parse("not a timestamp").is_err());
+    assert!(TimeFile::parse("123456789").is_err());
+}
+
+#[test]
+fn read_and_write() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(TimeFile::read(dir.path()).unwrap(), None);
+
+    TimeFile::new(0x4F2A_1B3C).write(dir.path()).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join(TIME_FILE_NAME)).unwrap(),
+        "4F2A1B3C"
+    );
+    assert_eq!(
+        TimeFile::read(dir.path()).unwrap(),
+        Some(TimeFile::new(0x4F2A_1B3C))
+    );
+}
+
+#[test]
+fn validate_against_header() {
+    let archive = open_sharc(create_sharc(&[], Endianness::Big));
+    let dir = tempfile::tempdir().unwrap();
+
+    assert_eq!(
+        validate(&archive, dir.path()).unwrap(),
+        TimeFileStatus::Missing
+    );
+
+    TimeFile::from_archive(&archive).write(dir.path()).unwrap();
+    assert!(validate(&archive, dir.path()).unwrap().is_consistent());
+
+    TimeFile::new(1234).write(dir.path()).unwrap();
+    assert_eq!(
+        validate(&archive, dir.path()).unwrap(),
+        TimeFileStatus::Mismatch {
+            header: TEST_TIMESTAMP,
+            time_file: 1234
+        }
+    );
+
+    std::fs::write(dir.path().join(TIME_FILE_NAME), "garbage").unwrap();
+    assert!(validate(&archive, dir.path()).is_err());
+}
@@ -0,0 +1,141 @@
+//! Unpacking an archive into a directory tree.
+//!
+//! Every entry is written to a file named by the uppercase-hex [`AfsHash`] of its
+//! path, since archives only store hashes. If a hash→path dictionary is given,
+//! entries whose hash it knows are written to their real path instead.
+//!
+//! The header timestamp is written to a `.time` file next to the entries, so the
+//! folder can be fed to [`Mapper`](crate::mapper::Mapper) and repacked with
+//! [`pack_directory`](crate::pack::pack_directory) without losing it.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use hdk_archive::bar::BarReader;
+//! use hdk_archive::unpack::unpack_to_directory;
+//!
+//! let file = std::fs::File::open("path/to/archive.bar").unwrap();
+//! let mut archive = BarReader::open(file, [0u8; 32], [0u8; 32]).unwrap();
+//!
+//! let entries = unpack_to_directory("path/to/output")
+//!     .with_path("objects/00000000-00000000-00000000-00000000/object.xml")
+//!     .unpack(&mut archive)
+//!     .unwrap();
+//!
+//! println!("Unpacked {} entries", entries.len());
+//! ```
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+
+use hdk_secure::hash::AfsHash;
+
+use crate::archive::{ArchiveReader, EntryMetadata, HashIndex};
+use crate::error::ArchiveError;
+use crate::time::TimeFile;
+use crate::utils::relative_path;
+
+#[cfg(test)]
+mod tests;
+
+/// An entry written by [`DirectoryUnpacker::unpack`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnpackedEntry {
+    pub name_hash: AfsHash,
+
+    /// Where the entry was written, relative to the output folder.
+    pub path: PathBuf,
+
+    /// Whether the entry was written to its real path rather than its hash.
+    pub mapped: bool,
+}
+
+/// Builder for unpacking an archive into a directory tree.
+///
+/// See the [module documentation](self) for how files are named.
+#[derive(Debug, Clone)]
+pub struct DirectoryUnpacker {
+    output: PathBuf,
+    dictionary: HashMap<AfsHash, String>,
+}
+
+/// Start unpacking an archive into the `output` folder.
+pub fn unpack_to_directory(output: impl Into<PathBuf>) -> DirectoryUnpacker {
+    DirectoryUnpacker::new(output)
+}
+
+impl DirectoryUnpacker {
+    /// Create a new `DirectoryUnpacker` writing into the given folder.
+    pub fn new(output: impl Into<PathBuf>) -> Self {
+        Self {
+            output: output.into(),
+            dictionary: HashMap::new(),
+        }
+    }
+
+    /// Add a known path to the dictionary, keyed by its hash.
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        let path = path.into();
+        self.dictionary.insert(AfsHash::new_from_str(&path), path);
+        self
+    }
+
+    /// Add known paths to the dictionary, keyed by their hash.
+    ///
+    /// Paths are written as given, so they should come from the same source as the
+    /// hashes (e.g. a previous [`Mapper`](crate::mapper::Mapper) run).
+    pub fn with_dictionary<I: IntoIterator<Item = (AfsHash, String)>>(mut self, paths: I) -> Self {
+        self.dictionary.extend(paths);
+        self
+    }
+
+    /// Write every entry of `archive` and the `.time` file to the output folder.
+    ///
+    /// Fails with [`ArchiveError::DuplicateHash`] if two entries share a name hash,
+    /// since they would overwrite each other. Duplicates and invalid paths are
+    /// found before anything is written.
+    pub fn unpack<A: ArchiveReader>(
+        &self,
+        archive: &mut A,
+    ) -> Result<Vec<UnpackedEntry>, ArchiveError> {
+        let hashes = (0..archive.entry_count())
+            .map(|index| Ok(archive.entry_metadata(index)?.name_hash()))
+            .collect::<io::Result<Vec<_>>>()?;
+        HashIndex::new(hashes.iter().copied()).check_unique()?;
+
+        let entries = hashes
+            .into_iter()
+            .map(|name_hash| {
+                let (path, mapped) = match self.dictionary.get(&name_hash) {
+                    Some(path) => (relative_path(path)?, true),
+                    None => (PathBuf::from(name_hash.to_string()), false),
+                };
+
+                Ok(UnpackedEntry {
+                    name_hash,
+                    path,
+                    mapped,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        std::fs::create_dir_all(&self.output)?;
+        TimeFile::from_archive(archive).write(&self.output)?;
+
+        for (index, entry) in entries.iter().enumerate() {
+            let target = self.output.join(&entry.path);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            let mut reader = archive.entry_reader(index)?;
+            let mut writer = BufWriter::new(File::create(&target)?);
+            io::copy(&mut reader, &mut writer)?;
+            writer.into_inner().map_err(|e| e.into_error())?;
+        }
+
+        Ok(entries)
+    }
+}
@@ -0,0 +1,173 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use hdk_secure::hash::AfsHash;
+
+use crate::archive::ArchiveReader;
+use crate::bar::BarWriter;
+use crate::error::ArchiveError;
+use crate::mapper::Mapper;
+use crate::pack::pack_directory;
+use crate::structs::{CompressionType, Endianness};
+use crate::test_utils::{
+    TEST_DEFAULT_KEY, TEST_SIGNATURE_KEY, TestEntry, create_bar, create_sharc, open_bar, open_sharc,
+};
+use crate::unpack::unpack_to_directory;
+
+const OBJECT_XML: &[u8] = b"<object><script file=\"scripts/main.lua\"/></object>";
+const MAIN_LUA: &[u8] = b"print('hello')";
+
+const ENTRIES: [TestEntry; 2] = [
+    ("object.xml", CompressionType::Encrypted, OBJECT_XML),
+    ("scripts/main.lua", CompressionType::ZLib, MAIN_LUA),
+];
+
+fn hash_path(path: &str) -> PathBuf {
+    PathBuf::from(AfsHash::new_from_str(path).to_string())
+}
+
+#[test]
+fn unpack_to_hash_names() {
+    let mut archive = open_bar(create_bar(&ENTRIES));
+    let dir = tempfile::tempdir().unwrap();
+
+    let entries = unpack_to_directory(dir.path())
+        .unpack(&mut archive)
+        .unwrap();
+
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(|entry| !entry.mapped));
+    assert_eq!(entries[0].path, hash_path("object.xml"));
+
+    assert_eq!(
+        std::fs::read(dir.path().join(hash_path("object.xml"))).unwrap(),
+        OBJECT_XML
+    );
+    assert_eq!(
+        std::fs::read(dir.path().join(hash_path("scripts/main.lua"))).unwrap(),
+        MAIN_LUA
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join(".time")).unwrap(),
+        "4F2A1B3C"
+    );
+}
+
+#[test]
+fn unpack_with_dictionary() {
+    let mut archive = open_bar(create_bar(&ENTRIES));
+    let dir = tempfile::tempdir().unwrap();
+
+    let entries = unpack_to_directory(dir.path())
+        .with_path("scripts/main.lua")
+        .unpack(&mut archive)
+        .unwrap();
+
+    assert!(!entries[0].mapped);
+    assert!(entries[1].mapped);
+    assert_eq!(entries[1].path, Path::new("scripts").join("main.lua"));
+
+    assert_eq!(
+        std::fs::read(dir.path().join("scripts/main.lua")).unwrap(),
+        MAIN_LUA
+    );
+    assert!(dir.path().join(hash_path("object.xml")).is_file());
+    assert!(!dir.path().join(hash_path("scripts/main.lua")).exists());
+}
+
+#[test]
+fn unpack_rejects_escaping_paths() {
+    let mut archive = open_bar(create_bar(&ENTRIES));
+    let dir = tempfile::tempdir().unwrap();
+
+    let hash = AfsHash::new_from_str("object.xml");
+    let result = unpack_to_directory(dir.path().join("out"))
+        .with_dictionary([(hash, "../object.xml".to_string())])
+        .unpack(&mut archive);
+
+    assert!(matches!(result, Err(ArchiveError::Io(_))));
+    assert!(!dir.path().join("object.xml").exists());
+}
+
+#[test]
+fn unpack_rejects_duplicate_hashes() {
+    let mut archive = open_sharc(create_sharc(
+        &[
+            ("object.xml", CompressionType::None, b"one"),
+            ("object.xml", CompressionType::None, b"two"),
+        ],
+        Endianness::Big,
+    ));
+    let hash = AfsHash::new_from_str("object.xml");
+
+    let dir = tempfile::tempdir().unwrap();
+    let result = unpack_to_directory(dir.path()).unpack(&mut archive);
+
+    assert!(matches!(
+        result,
+        Err(ArchiveError::DuplicateHash { name_hash, ref indices })
+            if name_hash == hash && indices == &[0, 1]
+    ));
+
+    // Nothing is written, not even the `.time` file
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn unpack_then_repack_keeps_entries_and_timestamp() {
+    let mut archive = open_bar(create_bar(&ENTRIES));
+    let dir = tempfile::tempdir().unwrap();
+
+    unpack_to_directory(dir.path())
+        .with_path("object.xml")
+        .unpack(&mut archive)
+        .unwrap();
+
+    let writer = BarWriter::new(
+        Cursor::new(Vec::new()),
+        TEST_DEFAULT_KEY,
+        TEST_SIGNATURE_KEY,
+    );
+    let out = pack_directory(dir.path())
+        .pack(writer)
+        .unwrap()
+        .into_inner();
+    let repacked = open_bar(out);
+
+    assert_eq!(repacked.timestamp(), archive.timestamp());
+    assert_eq!(repacked.entry_count(), 2);
+    for path in ["object.xml", "scripts/main.lua"] {
+        let hash = AfsHash::new_from_str(path);
+        assert!(repacked.find_by_hash(hash).unwrap().is_some());
+    }
+}
+
+#[test]
+fn unpacked_folder_can_be_mapped() {
+    let mut archive = open_bar(create_bar(&ENTRIES));
+    let dir = tempfile::tempdir().unwrap();
+    let hashed = dir.path().join("hashed");
+    let mapped = dir.path().join("mapped");
+
+    unpack_to_directory(&hashed).unpack(&mut archive).unwrap();
+
+    let result = Mapper::new(hashed)
+        .with_output_folder(mapped.clone())
+        .run()
+        .unwrap();
+
+    assert_eq!(result.mapped, 2);
+    assert!(result.not_found.is_empty());
+    assert_eq!(
+        std::fs::read(mapped.join("object.xml")).unwrap(),
+        OBJECT_XML
+    );
+    assert_eq!(
+        std::fs::read(mapped.join("scripts/main.lua")).unwrap(),
+        MAIN_LUA
+    );
+    assert_eq!(
+        std::fs::read_to_string(mapped.join(".time")).unwrap(),
+        "4F2A1B3C"
+    );
+}
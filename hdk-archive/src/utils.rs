@@ -0,0 +1,300 @@
+//! Small I/O helpers shared by the archive readers and writers.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// A writer that counts how many bytes were written through it.
+pub struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> CountingWriter<W> {
+    pub const fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+
+    pub const fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Number of zero bytes needed to pad `len` to a 4-byte boundary.
+pub const fn padding(len: u64) -> u64 {
+    (4 - (len % 4)) % 4
+}
+
+/// Convert a size to the `u32` stored in archive ToCs, failing instead of truncating.
+pub fn toc_u32(value: u64) -> io::Result<u32> {
+    u32::try_from(value).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Size overflow"))
+}
+
+/// Read a table of `len` bytes, failing if it is cut short unless `allow_short`.
+///
+/// The buffer grows as data arrives, so a garbage length from a corrupted header or a
+/// wrong key fails instead of allocating it up front.
+pub fn read_table(reader: &mut impl Read, len: u64, allow_short: bool) -> io::Result<Vec<u8>> {
+    let mut table = Vec::new();
+    reader.take(len).read_to_end(&mut table)?;
+
+    if !allow_short && (table.len() as u64) < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Table runs past the end of the archive",
+        ));
+    }
+
+    Ok(table)
+}
+
+/// Read to the end of `reader`, keeping everything read before an error.
+pub fn read_until_error(reader: &mut impl Read, buf: &mut Vec<u8>) -> io::Result<()> {
+    let mut chunk = [0u8; 0x4000];
+    loop {
+        match reader.read(&mut chunk) {
+            Ok(0) => return Ok(()),
+            Ok(read) => buf.extend_from_slice(&chunk[..read]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// A reader that computes the SHA-1 of everything read through it.
+pub struct Sha1Reader<R> {
+    inner: R,
+    hasher: sha1_smol::Sha1,
+}
+
+impl<R: Read> Sha1Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: sha1_smol::Sha1::new(),
+        }
+    }
+
+    pub fn digest(&self) -> [u8; 20] {
+        self.hasher.digest().bytes()
+    }
+}
+
+impl<R: Read> Read for Sha1Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// A seekable view over `len` bytes of `inner`, starting at `start`.
+///
+/// Positions are relative to `start`, and reads stop at the end of the window.
+pub struct Window<R> {
+    inner: R,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl<R: Read + Seek> Window<R> {
+    pub fn new(mut inner: R, start: u64, len: u64) -> io::Result<Self> {
+        inner.seek(SeekFrom::Start(start))?;
+
+        Ok(Self {
+            inner,
+            start,
+            len,
+            pos: 0,
+        })
+    }
+}
+
+impl<R: Read + Seek> Read for Window<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos);
+        let max = buf
+            .len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+
+        let n = self.inner.read(&mut buf[..max])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for Window<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        self.inner.seek(SeekFrom::Start(self.start + target))?;
+        self.pos = target;
+        Ok(target)
+    }
+}
+
+/// A reader that wraps a raw deflate stream into a zlib stream, without recompressing it.
+///
+/// The deflate data is decompressed on the fly only to compute the Adler-32 trailer.
+pub struct ZlibWrapReader<R> {
+    inner: R,
+    decompress: flate2::Decompress,
+    adler: Adler32,
+
+    /// The zlib header, then the Adler-32 trailer once the deflate data is exhausted.
+    pending: Vec<u8>,
+    pending_pos: usize,
+    finished: bool,
+}
+
+impl<R: Read> ZlibWrapReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            decompress: flate2::Decompress::new(false),
+            adler: Adler32::new(),
+            // Deflate with a 32K window and maximum compression, as written by `Compression::best()`
+            pending: vec![0x78, 0xDA],
+            pending_pos: 0,
+            finished: false,
+        }
+    }
+
+    /// Decompress `input`, only to feed the output to the checksum.
+    fn digest(&mut self, mut input: &[u8], flush: flate2::FlushDecompress) -> io::Result<()> {
+        let mut scratch = [0u8; 8192];
+
+        loop {
+            let before_in = self.decompress.total_in();
+            let before_out = self.decompress.total_out();
+
+            let status = self
+                .decompress
+                .decompress(input, &mut scratch, flush)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+            let consumed = (self.decompress.total_in() - before_in) as usize;
+            let produced = (self.decompress.total_out() - before_out) as usize;
+
+            self.adler.update(&scratch[..produced]);
+            input = &input[consumed..];
+
+            if status == flate2::Status::StreamEnd
+                || (consumed == 0 && produced == 0)
+                || (input.is_empty() && produced < scratch.len())
+            {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for ZlibWrapReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending_pos < self.pending.len() {
+            let n = buf.len().min(self.pending.len() - self.pending_pos);
+            buf[..n].copy_from_slice(&self.pending[self.pending_pos..self.pending_pos + n]);
+            self.pending_pos += n;
+            return Ok(n);
+        }
+
+        if self.finished || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.digest(&buf[..n], flate2::FlushDecompress::None)?;
+            return Ok(n);
+        }
+
+        // End of the deflate data: flush the decompressor and queue the trailer
+        self.digest(&[], flate2::FlushDecompress::Finish)?;
+        self.finished = true;
+        self.pending = self.adler.finish().to_be_bytes().to_vec();
+        self.pending_pos = 0;
+
+        self.read(buf)
+    }
+}
+
+/// The Adler-32 checksum used in zlib trailers.
+struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    const MOD: u32 = 65521;
+
+    const fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        // 5552 is the largest block for which `b` cannot overflow before the modulo
+        for block in data.chunks(5552) {
+            for &byte in block {
+                self.a += u32::from(byte);
+                self.b += self.a;
+            }
+
+            self.a %= Self::MOD;
+            self.b %= Self::MOD;
+        }
+    }
+
+    const fn finish(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+/// Turn an archive path into a path relative to an output folder, refusing any
+/// that would escape it.
+pub fn relative_path(path: &str) -> io::Result<PathBuf> {
+    let mut relative = PathBuf::new();
+
+    for component in Path::new(&path.replace('\\', "/")).components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Refusing to write to {path:?}, it is not a relative path"),
+                ));
+            }
+        }
+    }
+
+    if relative.as_os_str().is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Refusing to write to {path:?}, it is empty"),
+        ));
+    }
+
+    Ok(relative)
+}
@@ -0,0 +1,480 @@
+//! Integrity checks for BAR and SHARC archives.
+//!
+//! The game silently refuses damaged archives, and most tools only find out when
+//! they try to read the broken entry. [`Validate::validate`] checks an opened
+//! archive's header and table of contents, and optionally reads every entry, and
+//! reports each problem as a [`Finding`] tagged with a [`Severity`]: archives with
+//! only warnings still load, so CI can gate on [`ValidationReport::has_errors`].
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use hdk_archive::bar::BarReader;
+//! use hdk_archive::validate::{Depth, Validate};
+//!
+//! let file = std::fs::File::open("path/to/archive.bar").unwrap();
+//! let mut archive = BarReader::open(file, [0u8; 32], [0u8; 32]).unwrap();
+//!
+//! let report = archive.validate(Depth::Content).unwrap();
+//! for finding in &report.findings {
+//!     println!("{finding}");
+//! }
+//!
+//! assert!(!report.has_errors());
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Seek};
+
+use enumflags2::BitFlags;
+use hdk_secure::hash::AfsHash;
+use thiserror::Error;
+
+use crate::any::AnyArchive;
+use crate::archive::{ArchiveReader, EntryMetadata};
+use crate::bar::BarReader;
+use crate::error::ArchiveError;
+use crate::sharc::reader::SharcReader;
+use crate::structs::{ArchiveFlags, ArchiveVersion, CompressionType};
+
+#[cfg(test)]
+mod tests;
+
+/// Size of the encrypted head (fourcc and SHA-1) and raw body fourcc of an
+/// encrypted BAR entry.
+const BAR_ENCRYPTED_HEAD_SIZE: u32 = 28;
+
+/// How bad a finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Readable, but not as written, or not by every tool.
+    Warning,
+
+    /// Unreadable, or refused by the game.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// A problem found in an archive.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Issue {
+    #[error("unexpected archive version {0:#06X}")]
+    UnexpectedVersion(u16),
+
+    #[error("unknown header flags {0:#06X}")]
+    UnknownFlags(u16),
+
+    #[error("{0:?} flag has no effect on {1:?} archives")]
+    UnusedFlag(ArchiveFlags, ArchiveVersion),
+
+    #[error("data at {offset:#X} is not 4-byte aligned")]
+    Misaligned { offset: u64 },
+
+    #[error("data at {start:#X}..{end:#X} ends past the end of the archive ({len:#X})")]
+    OutOfBounds { start: u64, end: u64, len: u64 },
+
+    #[error("data overlaps entry {other}")]
+    Overlap { other: usize },
+
+    #[error("invalid compression type {0}")]
+    InvalidCompression(u8),
+
+    #[error("{name_hash} is shared by entries {indices:?}")]
+    DuplicateHash {
+        name_hash: AfsHash,
+        indices: Vec<usize>,
+    },
+
+    #[error("stored compression {stored:?} contradicts the sizes, read as {read:?}")]
+    CompressionFixup {
+        stored: CompressionType,
+        read: CompressionType,
+    },
+
+    #[error(
+        "{compression:?} entry stores {compressed_size} bytes for {uncompressed_size} bytes of content"
+    )]
+    SizeMismatch {
+        compression: CompressionType,
+        compressed_size: u32,
+        uncompressed_size: u32,
+    },
+
+    #[error("content is {actual} bytes, expected {expected}")]
+    LengthMismatch { expected: u32, actual: u64 },
+
+    #[error("SHA-1 signature mismatch")]
+    SignatureMismatch,
+
+    #[error("content cannot be read: {0}")]
+    Unreadable(String),
+}
+
+impl Issue {
+    pub const fn severity(&self) -> Severity {
+        match self {
+            Self::UnknownFlags(_)
+            | Self::UnusedFlag(..)
+            | Self::Misaligned { .. }
+            | Self::CompressionFixup { .. } => Severity::Warning,
+            Self::UnexpectedVersion(_)
+            | Self::OutOfBounds { .. }
+            | Self::Overlap { .. }
+            | Self::InvalidCompression(_)
+            | Self::DuplicateHash { .. }
+            | Self::SizeMismatch { .. }
+            | Self::LengthMismatch { .. }
+            | Self::SignatureMismatch
+            | Self::Unreadable(_) => Severity::Error,
+        }
+    }
+}
+
+/// An issue, and the entry it was found in, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub entry: Option<usize>,
+    pub issue: Issue,
+}
+
+impl Finding {
+    pub const fn severity(&self) -> Severity {
+        self.issue.severity()
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.entry {
+            Some(entry) => write!(f, "{}: entry {entry}: {}", self.severity(), self.issue),
+            None => write!(f, "{}: {}", self.severity(), self.issue),
+        }
+    }
+}
+
+/// The findings of a validation, in the order they were found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    pub const fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// The severity of the worst finding, if any.
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(Finding::severity).max()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.max_severity() == Some(Severity::Error)
+    }
+
+    /// The findings of at least `severity`.
+    pub fn at_least(&self, severity: Severity) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(move |finding| finding.severity() >= severity)
+    }
+
+    fn push(&mut self, entry: Option<usize>, issue: Issue) {
+        self.findings.push(Finding { entry, issue });
+    }
+}
+
+/// How much of an archive to check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    /// The header and table of contents only.
+    Structure,
+
+    /// Also read every entry, checking that it decrypts and decompresses to its size
+    /// (and, for encrypted BAR entries, its signature).
+    Content,
+}
+
+/// Archives that can be checked for integrity.
+pub trait Validate {
+    /// Check the archive, reporting every problem found.
+    ///
+    /// Only I/O errors on the archive itself fail; damaged entries are reported.
+    fn validate(&mut self, depth: Depth) -> io::Result<ValidationReport>;
+}
+
+impl<R: Read + Seek> Validate for BarReader<R> {
+    fn validate(&mut self, depth: Depth) -> io::Result<ValidationReport> {
+        let mut report = ValidationReport::default();
+
+        let flags = self.header().version_and_flags.1;
+        check_flags(&mut report, ArchiveVersion::BAR, flags);
+
+        for &(index, stored) in self.compression_fixups() {
+            let read = self.entry_metadata(index)?.compression;
+            report.push(Some(index), Issue::CompressionFixup { stored, read });
+        }
+
+        let archive_len = self.archive_len()?;
+        let data_start = self.data_start();
+        let entries = (0..self.entry_count())
+            .map(|index| {
+                let metadata = self.entry_metadata(index)?;
+                Ok(EntryLayout::new(
+                    &metadata,
+                    self.raw_compression(index),
+                    data_start + u64::from(metadata.offset),
+                ))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        for (index, entry) in entries.iter().enumerate() {
+            if entry.compression == CompressionType::Encrypted
+                && entry.compressed_size < BAR_ENCRYPTED_HEAD_SIZE
+            {
+                report.push(Some(index), entry.size_mismatch());
+            }
+        }
+
+        let readable = check_layout(&mut report, &entries, archive_len);
+
+        if depth == Depth::Content {
+            for index in readable {
+                let result = self
+                    .open_entry(index, true)
+                    .and_then(|mut reader| io::copy(&mut reader, &mut io::sink()));
+                check_content(&mut report, index, &entries[index], result);
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+impl<R: Read + Seek> Validate for SharcReader<R> {
+    fn validate(&mut self, depth: Depth) -> io::Result<ValidationReport> {
+        let mut report = ValidationReport::default();
+
+        let version = self.header().version;
+        if version != u16::from(ArchiveVersion::SHARC) {
+            report.push(None, Issue::UnexpectedVersion(version));
+        }
+        check_flags(&mut report, ArchiveVersion::SHARC, self.raw_flags());
+
+        let archive_len = self.archive_len()?;
+        let data_start = self.data_start();
+        let entries = self
+            .entries()
+            .map(|metadata| {
+                let metadata = metadata?;
+                Ok(EntryLayout::new(
+                    &metadata,
+                    metadata.compression_raw,
+                    data_start + metadata.offset,
+                ))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let readable = check_layout(&mut report, &entries, archive_len);
+
+        if depth == Depth::Content {
+            for index in readable {
+                let result = self
+                    .entry_reader(index)
+                    .and_then(|mut reader| io::copy(&mut reader, &mut io::sink()));
+                check_content(&mut report, index, &entries[index], result);
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+impl<R: Read + Seek> Validate for AnyArchive<R> {
+    fn validate(&mut self, depth: Depth) -> io::Result<ValidationReport> {
+        match self {
+            Self::Bar(bar) => bar.validate(depth),
+            Self::Sharc(sharc) => sharc.validate(depth),
+        }
+    }
+}
+
+/// Where an entry's data is, and what it holds.
+#[derive(Debug, Clone, Copy)]
+struct EntryLayout {
+    name_hash: AfsHash,
+    compression: CompressionType,
+
+    /// The compression bits as stored in the ToC, which `compression` reads as
+    /// `None` if they are not a known type.
+    compression_raw: u8,
+
+    compressed_size: u32,
+    uncompressed_size: u32,
+
+    /// Absolute offset of the data in the archive.
+    start: u64,
+}
+
+impl EntryLayout {
+    fn new<M: EntryMetadata>(metadata: &M, compression_raw: u8, start: u64) -> Self {
+        Self {
+            name_hash: metadata.name_hash(),
+            compression: metadata.compression(),
+            compression_raw,
+            compressed_size: metadata.compressed_size(),
+            uncompressed_size: metadata.uncompressed_size(),
+            start,
+        }
+    }
+
+    fn end(&self) -> u64 {
+        self.start + u64::from(self.compressed_size)
+    }
+
+    const fn size_mismatch(&self) -> Issue {
+        Issue::SizeMismatch {
+            compression: self.compression,
+            compressed_size: self.compressed_size,
+            uncompressed_size: self.uncompressed_size,
+        }
+    }
+}
+
+/// Report unknown header flag bits, and flags the format does not use.
+fn check_flags(report: &mut ValidationReport, version: ArchiveVersion, raw: u16) {
+    let unknown = raw & !BitFlags::<ArchiveFlags>::all().bits();
+    if unknown != 0 {
+        report.push(None, Issue::UnknownFlags(unknown));
+    }
+
+    if version == ArchiveVersion::SHARC {
+        for flag in BitFlags::<ArchiveFlags>::from_bits_truncate(raw) {
+            if matches!(flag, ArchiveFlags::ZTOC | ArchiveFlags::LeanZLib) {
+                report.push(None, Issue::UnusedFlag(flag, version));
+            }
+        }
+    }
+}
+
+/// Check the entries' hashes, sizes and placement.
+///
+/// Returns the indices of the entries that can be read: of a known compression type,
+/// with their data within the archive.
+fn check_layout(
+    report: &mut ValidationReport,
+    entries: &[EntryLayout],
+    archive_len: u64,
+) -> Vec<usize> {
+    let mut by_hash: HashMap<AfsHash, Vec<usize>> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        by_hash.entry(entry.name_hash).or_default().push(index);
+    }
+
+    let mut duplicates: Vec<_> = by_hash
+        .into_iter()
+        .filter(|(_, indices)| indices.len() > 1)
+        .collect();
+    duplicates.sort_unstable_by_key(|(_, indices)| indices[0]);
+
+    for (name_hash, indices) in duplicates {
+        report.push(None, Issue::DuplicateHash { name_hash, indices });
+    }
+
+    let mut readable = Vec::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let known_compression = CompressionType::try_from(entry.compression_raw).is_ok();
+        if !known_compression {
+            report.push(
+                Some(index),
+                Issue::InvalidCompression(entry.compression_raw),
+            );
+        }
+
+        let sizes_match = match entry.compression {
+            CompressionType::None => entry.compressed_size == entry.uncompressed_size,
+            _ => entry.compressed_size > 0 || entry.uncompressed_size == 0,
+        };
+        if known_compression && !sizes_match {
+            report.push(Some(index), entry.size_mismatch());
+        }
+
+        if entry.start % 4 != 0 {
+            report.push(
+                Some(index),
+                Issue::Misaligned {
+                    offset: entry.start,
+                },
+            );
+        }
+
+        if entry.end() > archive_len {
+            report.push(
+                Some(index),
+                Issue::OutOfBounds {
+                    start: entry.start,
+                    end: entry.end(),
+                    len: archive_len,
+                },
+            );
+        } else if known_compression {
+            readable.push(index);
+        }
+    }
+
+    // Sweep the entries by offset, remembering the one reaching furthest
+    let mut order: Vec<usize> = (0..entries.len())
+        .filter(|&index| entries[index].compressed_size > 0)
+        .collect();
+    order.sort_unstable_by_key(|&index| (entries[index].start, index));
+
+    let mut furthest: Option<usize> = None;
+    for index in order {
+        if let Some(other) = furthest {
+            if entries[other].end() > entries[index].start {
+                report.push(Some(index), Issue::Overlap { other });
+            }
+
+            if entries[other].end() >= entries[index].end() {
+                continue;
+            }
+        }
+
+        furthest = Some(index);
+    }
+
+    readable
+}
+
+/// Report what went wrong reading an entry's content, if anything.
+fn check_content(
+    report: &mut ValidationReport,
+    index: usize,
+    entry: &EntryLayout,
+    result: io::Result<u64>,
+) {
+    match result.map_err(ArchiveError::from) {
+        Ok(actual) if actual != u64::from(entry.uncompressed_size) => report.push(
+            Some(index),
+            Issue::LengthMismatch {
+                expected: entry.uncompressed_size,
+                actual,
+            },
+        ),
+        Ok(_) => {}
+        Err(ArchiveError::SignatureMismatch { .. }) => {
+            report.push(Some(index), Issue::SignatureMismatch)
+        }
+        Err(e) => report.push(Some(index), Issue::Unreadable(e.to_string())),
+    }
+}
@@ -0,0 +1,254 @@
+use std::io::Cursor;
+
+use hdk_secure::hash::AfsHash;
+
+use super::{
+    Depth, EntryLayout, Finding, Issue, Severity, Validate, ValidationReport, check_layout,
+};
+use crate::any::open_any;
+use crate::archive::ArchiveReader;
+use crate::structs::{ArchiveFlags, ArchiveVersion, CompressionType, Endianness};
+use crate::test_utils::{TEST_KEYS, TestEntry, create_bar, create_sharc, open_bar, open_sharc};
+
+const ENTRIES: [TestEntry; 3] = [
+    ("plain.txt", CompressionType::None, b"plain text"),
+    (
+        "zlib.xml",
+        CompressionType::ZLib,
+        b"<xml><xml><xml><xml><xml></xml></xml></xml></xml></xml>",
+    ),
+    ("secret.lua", CompressionType::Encrypted, b"print('secret')"),
+];
+
+/// Offset of a field of the `index`th entry in a BAR's ToC.
+const fn bar_toc(index: usize, field: usize) -> usize {
+    20 + index * 16 + field * 4
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn issues(findings: &[Finding]) -> Vec<(Option<usize>, &Issue)> {
+    findings
+        .iter()
+        .map(|finding| (finding.entry, &finding.issue))
+        .collect()
+}
+
+#[test]
+fn clean_bar_passes() {
+    let report = open_bar(create_bar(&ENTRIES))
+        .validate(Depth::Content)
+        .unwrap();
+
+    assert!(report.is_clean(), "{:?}", report.findings);
+    assert_eq!(report.max_severity(), None);
+}
+
+#[test]
+fn reports_bar_layout_issues() {
+    let mut data = create_bar(&ENTRIES);
+
+    // Entry 1 shares entry 0's hash and data, and entry 2 runs past the end
+    let hash = read_u32(&data, bar_toc(0, 0));
+    write_u32(&mut data, bar_toc(1, 0), hash);
+    let offset = read_u32(&data, bar_toc(0, 1));
+    let compression = read_u32(&data, bar_toc(1, 1)) & 0x3;
+    write_u32(&mut data, bar_toc(1, 1), offset | compression);
+    write_u32(&mut data, bar_toc(2, 3), 0x10000);
+
+    let archive_len = data.len() as u64;
+    let mut archive = open_bar(data);
+    let start = 20 + 3 * 16 + u64::from(archive.entry_metadata(2).unwrap().offset);
+    let report = archive.validate(Depth::Structure).unwrap();
+
+    assert_eq!(
+        issues(&report.findings),
+        [
+            (
+                None,
+                &Issue::DuplicateHash {
+                    name_hash: AfsHash(hash as i32),
+                    indices: vec![0, 1],
+                }
+            ),
+            (
+                Some(2),
+                &Issue::OutOfBounds {
+                    start,
+                    end: start + 0x10000,
+                    len: archive_len,
+                }
+            ),
+            (Some(1), &Issue::Overlap { other: 0 }),
+        ]
+    );
+    assert!(report.has_errors());
+    assert_eq!(
+        report.findings[2].to_string(),
+        "error: entry 1: data overlaps entry 0"
+    );
+}
+
+#[test]
+fn reports_bar_compression_fixups_as_warnings() {
+    let mut data = create_bar(&ENTRIES);
+
+    // Store the ZLib entry as uncompressed, which readers correct from its sizes
+    let offset_and_comp = read_u32(&data, bar_toc(1, 1));
+    write_u32(&mut data, bar_toc(1, 1), offset_and_comp & !0x3);
+
+    let report = open_bar(data).validate(Depth::Content).unwrap();
+
+    assert_eq!(
+        issues(&report.findings),
+        [(
+            Some(1),
+            &Issue::CompressionFixup {
+                stored: CompressionType::None,
+                read: CompressionType::ZLib,
+            }
+        )]
+    );
+    assert_eq!(report.max_severity(), Some(Severity::Warning));
+    assert!(!report.has_errors());
+    assert_eq!(report.at_least(Severity::Error).count(), 0);
+}
+
+#[test]
+fn reports_bar_sizes_and_content() {
+    let mut data = create_bar(&ENTRIES);
+
+    // The uncompressed entry says it holds less than it stores, the ZLib entry
+    // decompresses to less than it says, and the encrypted entry is corrupted
+    write_u32(&mut data, bar_toc(0, 2), 9);
+    let zlib_size = read_u32(&data, bar_toc(1, 2));
+    write_u32(&mut data, bar_toc(1, 2), zlib_size + 1);
+
+    let archive = open_bar(data.clone());
+    let start = 20 + 3 * 16 + archive.entry_metadata(2).unwrap().offset as usize;
+    data[start + 30] ^= 0xFF;
+
+    let report = open_bar(data).validate(Depth::Content).unwrap();
+    let findings = issues(&report.findings);
+
+    assert_eq!(
+        findings[..2],
+        [
+            (
+                Some(0),
+                &Issue::SizeMismatch {
+                    compression: CompressionType::None,
+                    compressed_size: 10,
+                    uncompressed_size: 9,
+                }
+            ),
+            (
+                Some(0),
+                &Issue::LengthMismatch {
+                    expected: 9,
+                    actual: 10,
+                }
+            ),
+        ]
+    );
+    assert_eq!(
+        findings[2],
+        (
+            Some(1),
+            &Issue::LengthMismatch {
+                expected: zlib_size + 1,
+                actual: u64::from(zlib_size),
+            }
+        )
+    );
+    assert!(matches!(
+        findings[3],
+        (Some(2), Issue::SignatureMismatch | Issue::Unreadable(_))
+    ));
+    assert_eq!(findings.len(), 4);
+
+    // Content is only read when asked for
+    let mut data = create_bar(&ENTRIES);
+    data[start + 30] ^= 0xFF;
+    assert!(
+        open_bar(data)
+            .validate(Depth::Structure)
+            .unwrap()
+            .is_clean()
+    );
+}
+
+#[test]
+fn reports_invalid_compression() {
+    // The ToC stores compression in two bits, which both formats define all values
+    // of, so an unknown type is only possible in the layout checks themselves
+    let layout = |compression_raw: u8| EntryLayout {
+        name_hash: AfsHash(compression_raw.into()),
+        compression: CompressionType::None,
+        compression_raw,
+        compressed_size: 4,
+        uncompressed_size: 4,
+        start: u64::from(compression_raw) * 4,
+    };
+
+    let mut report = ValidationReport::default();
+    let readable = check_layout(&mut report, &[layout(0), layout(4)], 32);
+
+    assert_eq!(
+        issues(&report.findings),
+        [(Some(1), &Issue::InvalidCompression(4))]
+    );
+    assert_eq!(report.max_severity(), Some(Severity::Error));
+    assert_eq!(readable, [0]);
+}
+
+#[test]
+fn validates_sharc() {
+    let data = create_sharc(&ENTRIES, Endianness::Little);
+    let report = open_sharc(data.clone()).validate(Depth::Content).unwrap();
+    assert!(report.is_clean(), "{:?}", report.findings);
+
+    // Unknown version and flags, then a corrupted ZLib header
+    let mut damaged = data;
+    let flags = 0x0010 | ArchiveFlags::ZTOC as u16;
+    damaged[4..6].copy_from_slice(&flags.to_le_bytes());
+    damaged[6..8].copy_from_slice(&0x0201u16.to_le_bytes());
+
+    let archive = open_sharc(damaged.clone());
+    let start = 52 + 3 * 24 + archive.entry_metadata(1).unwrap().offset as usize;
+    damaged[start] ^= 0xFF;
+
+    let report = open_sharc(damaged).validate(Depth::Content).unwrap();
+    let findings = issues(&report.findings);
+
+    assert_eq!(
+        findings[..3],
+        [
+            (None, &Issue::UnexpectedVersion(0x0201)),
+            (None, &Issue::UnknownFlags(0x0010)),
+            (
+                None,
+                &Issue::UnusedFlag(ArchiveFlags::ZTOC, ArchiveVersion::SHARC)
+            ),
+        ]
+    );
+    assert!(matches!(findings[3], (Some(1), Issue::Unreadable(_))));
+    assert_eq!(findings.len(), 4);
+}
+
+#[test]
+fn validates_any_archive() {
+    for data in [
+        create_bar(&ENTRIES),
+        create_sharc(&ENTRIES, Endianness::Little),
+    ] {
+        let mut archive = open_any(Cursor::new(data), &TEST_KEYS).unwrap();
+        assert!(archive.validate(Depth::Content).unwrap().is_clean());
+    }
+}
@@ -0,0 +1,230 @@
+//! Recursive listing of archives nested inside other archives.
+//!
+//! CDN objects are often SHARCs holding further BAR or SHARC archives, stored either
+//! as plain entries or as SHARC's encrypted nested archives. [`Walker::walk`] lists
+//! every entry of an archive and descends into each entry that is itself an
+//! archive, opening it with keys from a [`KeyProvider`]. Entries are visited in
+//! ToC order, each nested archive's entries right after the entry holding it, and
+//! carry the name hashes of the entries leading to them.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use hdk_archive::any::ArchiveKeys;
+//! use hdk_archive::walk::Walker;
+//!
+//! let file = std::fs::File::open("path/to/object.sharc").unwrap();
+//! let keys = ArchiveKeys::new()
+//!     .with_bar_keys([0u8; 32], [0u8; 32])
+//!     .with_sharc_key([0u8; 32]);
+//!
+//! Walker::new(keys)
+//!     .walk(file, |entry| {
+//!         println!("{:?} {}", entry.parents, entry.name_hash);
+//!         Ok(())
+//!     })
+//!     .unwrap();
+//! ```
+
+use std::io::{self, Cursor, Read, Seek};
+
+use hdk_secure::hash::AfsHash;
+
+use crate::any::{AnyArchive, ArchiveKeys, detect_format, open_any};
+use crate::archive::{ArchiveReader, EntryMetadata};
+use crate::error::ArchiveError;
+use crate::structs::{ArchiveVersion, CompressionType};
+
+#[cfg(test)]
+mod tests;
+
+/// Deepest nesting descended into by default.
+pub const DEFAULT_MAX_DEPTH: usize = 8;
+
+/// Supplies the keys for each archive a walk opens.
+pub trait KeyProvider {
+    /// Keys for the archive held by the entries `parents`, outermost first, or for
+    /// the archive being walked if `parents` is empty.
+    fn keys(&self, parents: &[AfsHash]) -> ArchiveKeys;
+}
+
+/// The same keys for every archive.
+impl KeyProvider for ArchiveKeys {
+    fn keys(&self, _parents: &[AfsHash]) -> ArchiveKeys {
+        *self
+    }
+}
+
+impl<F: Fn(&[AfsHash]) -> ArchiveKeys> KeyProvider for F {
+    fn keys(&self, parents: &[AfsHash]) -> ArchiveKeys {
+        self(parents)
+    }
+}
+
+/// An entry that is itself an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Nested {
+    /// The archive was opened, and its entries follow this one.
+    Opened(ArchiveVersion),
+
+    /// The archive could not be opened, e.g. for lack of keys or because it is
+    /// nested too deeply.
+    Unopened(ArchiveVersion, String),
+}
+
+/// An entry found by a walk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkedEntry {
+    /// Name hashes of the entries holding the archive this entry is in, outermost
+    /// first. Empty for entries of the archive being walked.
+    pub parents: Vec<AfsHash>,
+
+    /// Index of the entry in its archive.
+    pub index: usize,
+    pub name_hash: AfsHash,
+    pub compression: CompressionType,
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+
+    /// Whether the entry is itself an archive.
+    pub nested: Option<Nested>,
+}
+
+impl WalkedEntry {
+    /// How many archives deep the entry is, 0 for the archive being walked.
+    pub const fn depth(&self) -> usize {
+        self.parents.len()
+    }
+
+    /// The entry's path of name hashes, from the outermost archive's entry.
+    pub fn path(&self) -> impl Iterator<Item = AfsHash> + '_ {
+        self.parents.iter().copied().chain([self.name_hash])
+    }
+}
+
+/// Lists the entries of an archive and of every archive nested inside it.
+pub struct Walker<K> {
+    keys: K,
+    max_depth: usize,
+}
+
+impl<K: KeyProvider> Walker<K> {
+    pub const fn new(keys: K) -> Self {
+        Self {
+            keys,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// Set how many archives deep to descend; nested archives past it are reported
+    /// as [`Nested::Unopened`].
+    pub const fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Open an archive with the keys for an empty path and walk it.
+    pub fn walk<R, F>(&self, reader: R, visit: F) -> Result<(), ArchiveError>
+    where
+        R: Read + Seek,
+        F: FnMut(&WalkedEntry) -> Result<(), ArchiveError>,
+    {
+        let mut archive = open_any(reader, &self.keys.keys(&[]))?;
+        self.walk_archive(&mut archive, visit)
+    }
+
+    /// Walk an opened archive, calling `visit` for every entry.
+    ///
+    /// Nested archives are read into memory to be opened, and reported as
+    /// [`Nested::Unopened`] if that fails. Entries whose first bytes cannot be read
+    /// are listed without being checked for a nested archive.
+    pub fn walk_archive<R, F>(
+        &self,
+        archive: &mut AnyArchive<R>,
+        mut visit: F,
+    ) -> Result<(), ArchiveError>
+    where
+        R: Read + Seek,
+        F: FnMut(&WalkedEntry) -> Result<(), ArchiveError>,
+    {
+        self.walk_inner(archive, &mut Vec::new(), &mut visit)
+    }
+
+    fn walk_inner<R, F>(
+        &self,
+        archive: &mut AnyArchive<R>,
+        parents: &mut Vec<AfsHash>,
+        visit: &mut F,
+    ) -> Result<(), ArchiveError>
+    where
+        R: Read + Seek,
+        F: FnMut(&WalkedEntry) -> Result<(), ArchiveError>,
+    {
+        for index in 0..archive.entry_count() {
+            let metadata = archive.entry_metadata(index)?;
+            let mut entry = WalkedEntry {
+                parents: parents.clone(),
+                index,
+                name_hash: metadata.name_hash(),
+                compression: metadata.compression(),
+                compressed_size: metadata.compressed_size(),
+                uncompressed_size: metadata.uncompressed_size(),
+                nested: None,
+            };
+
+            let Some((version, data)) = read_nested(archive, index) else {
+                visit(&entry)?;
+                continue;
+            };
+
+            parents.push(entry.name_hash);
+            let opened = if parents.len() > self.max_depth {
+                Err("nested too deeply".to_string())
+            } else {
+                data.map_err(ArchiveError::from)
+                    .and_then(|data| open_any(Cursor::new(data), &self.keys.keys(parents)))
+                    .map_err(|e| e.to_string())
+            };
+
+            let result = match opened {
+                Ok(mut nested) => {
+                    entry.nested = Some(Nested::Opened(version));
+                    visit(&entry).and_then(|()| self.walk_inner(&mut nested, parents, visit))
+                }
+                Err(e) => {
+                    entry.nested = Some(Nested::Unopened(version, e));
+                    visit(&entry)
+                }
+            };
+            parents.pop();
+            result?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The content of an entry, if it starts like an archive.
+///
+/// Once the entry is known to be an archive, failing to read the rest of it is
+/// returned rather than treating the entry as a plain one.
+fn read_nested<R: Read + Seek>(
+    archive: &mut AnyArchive<R>,
+    index: usize,
+) -> Option<(ArchiveVersion, io::Result<Vec<u8>>)> {
+    // Sniff the magic and version before reading the whole entry
+    let mut head = Vec::with_capacity(8);
+    archive
+        .entry_reader(index)
+        .and_then(|reader| reader.take(8).read_to_end(&mut head))
+        .ok()?;
+    let version = detect_format(&mut Cursor::new(head)).ok()?.version;
+
+    let mut data = Vec::new();
+    let result = archive
+        .entry_reader(index)
+        .and_then(|mut reader| reader.read_to_end(&mut data))
+        .map(|_| data);
+
+    Some((version, result))
+}
@@ -0,0 +1,186 @@
+use std::io::Cursor;
+
+use hdk_secure::hash::AfsHash;
+
+use super::{Nested, WalkedEntry, Walker};
+use crate::any::ArchiveKeys;
+use crate::archive::RawEntry;
+use crate::sharc::writer::SharcWriter;
+use crate::structs::{ArchiveVersion, CompressionType, Endianness};
+use crate::test_utils::{TEST_KEYS, create_bar, create_sharc, write_entries};
+
+/// Key of the archive being walked, unlike [`TEST_KEYS`] for the ones inside it.
+const OUTER_SHARC_KEY: [u8; 32] = [0xDD; 32];
+
+fn hash(path: &str) -> AfsHash {
+    AfsHash::new_from_str(path)
+}
+
+/// A SHARC holding a plain entry and a BAR.
+fn create_inner_sharc() -> Vec<u8> {
+    let bar = create_bar(&[("inner.txt", CompressionType::ZLib, b"innermost")]);
+
+    create_sharc(
+        &[
+            ("readme.txt", CompressionType::None, b"readme"),
+            ("scene.bar", CompressionType::ZLib, &bar),
+        ],
+        Endianness::Big,
+    )
+}
+
+/// A SHARC holding the inner SHARC as an encrypted nested archive.
+fn create_outer_sharc() -> Vec<u8> {
+    let inner = create_inner_sharc();
+
+    let mut writer = SharcWriter::new(Vec::new(), OUTER_SHARC_KEY, Endianness::Little).unwrap();
+    writer
+        .add_raw_entry(RawEntry {
+            name_hash: hash("nested.sharc"),
+            compression: CompressionType::None,
+            uncompressed_size: inner.len() as u32,
+            iv: Some([0x11; 8]),
+            checksum: None,
+            reader: Box::new(Cursor::new(inner)),
+        })
+        .unwrap();
+    writer
+        .add_entry_from_bytes(hash("object.xml"), CompressionType::ZLib, b"<object/>")
+        .unwrap();
+
+    writer.finish().unwrap()
+}
+
+/// Keys for the outer SHARC, and only inside it for the inner archives.
+fn keys(parents: &[AfsHash]) -> ArchiveKeys {
+    if parents.is_empty() {
+        ArchiveKeys::new().with_sharc_key(OUTER_SHARC_KEY)
+    } else {
+        TEST_KEYS
+    }
+}
+
+fn walk(walker: &Walker<impl super::KeyProvider>, data: Vec<u8>) -> Vec<WalkedEntry> {
+    let mut entries = Vec::new();
+    walker
+        .walk(Cursor::new(data), |entry| {
+            entries.push(entry.clone());
+            Ok(())
+        })
+        .unwrap();
+
+    entries
+}
+
+/// Entries by path, with whether they are nested archives, sorted by path.
+fn paths(entries: &[WalkedEntry]) -> Vec<(Vec<AfsHash>, Option<Nested>)> {
+    let mut paths: Vec<_> = entries
+        .iter()
+        .map(|entry| (entry.path().collect(), entry.nested.clone()))
+        .collect();
+    sort_paths(&mut paths);
+
+    paths
+}
+
+fn sort_paths(paths: &mut [(Vec<AfsHash>, Option<Nested>)]) {
+    paths.sort_by_key(|(path, _)| path.iter().map(|hash| hash.0).collect::<Vec<_>>());
+}
+
+#[test]
+fn walks_nested_archives() {
+    let entries = walk(&Walker::new(keys), create_outer_sharc());
+
+    let nested = hash("nested.sharc");
+    let scene = hash("scene.bar");
+    let mut expected = vec![
+        (vec![nested], Some(Nested::Opened(ArchiveVersion::SHARC))),
+        (vec![hash("object.xml")], None),
+        (vec![nested, hash("readme.txt")], None),
+        (
+            vec![nested, scene],
+            Some(Nested::Opened(ArchiveVersion::BAR)),
+        ),
+        (vec![nested, scene, hash("inner.txt")], None),
+    ];
+    sort_paths(&mut expected);
+    assert_eq!(paths(&entries), expected);
+
+    // Each nested archive's entries directly follow the entry holding it
+    for (position, entry) in entries.iter().enumerate() {
+        if let Some(&parent) = entry.parents.last() {
+            let holder = entries[..position]
+                .iter()
+                .rposition(|other| other.depth() < entry.depth())
+                .unwrap();
+            assert_eq!(entries[holder].name_hash, parent);
+        }
+    }
+
+    let inner = entries
+        .iter()
+        .find(|entry| entry.name_hash == hash("inner.txt"))
+        .unwrap();
+    assert_eq!(inner.depth(), 2);
+    assert_eq!(inner.uncompressed_size, 9);
+}
+
+#[test]
+fn reports_unopened_nested_archives() {
+    // Without the inner keys, the nested SHARC is listed but not descended into
+    let outer_only = ArchiveKeys::new().with_sharc_key(OUTER_SHARC_KEY);
+    let entries = walk(&Walker::new(outer_only), create_outer_sharc());
+
+    assert_eq!(entries.len(), 2);
+    let nested = entries
+        .iter()
+        .find(|entry| entry.name_hash == hash("nested.sharc"))
+        .unwrap();
+    assert!(matches!(
+        nested.nested,
+        Some(Nested::Unopened(ArchiveVersion::SHARC, _))
+    ));
+
+    // Past the maximum depth, nested archives are not opened either
+    let entries = walk(&Walker::new(keys).with_max_depth(1), create_outer_sharc());
+
+    assert_eq!(entries.len(), 4);
+    let scene = entries
+        .iter()
+        .find(|entry| entry.name_hash == hash("scene.bar"))
+        .unwrap();
+    assert_eq!(
+        scene.nested,
+        Some(Nested::Unopened(
+            ArchiveVersion::BAR,
+            "nested too deeply".to_string()
+        ))
+    );
+}
+
+#[test]
+fn reports_truncated_nested_archives() {
+    // A nested BAR large enough to span several EdgeZLib chunks, so that its header
+    // still reads once the outer archive is cut short
+    let mut state = 0x1234_5678u32;
+    let large: Vec<u8> = (0..300_000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 24) as u8
+        })
+        .collect();
+
+    let bar = create_bar(&[("large.bin", CompressionType::None, &large)]);
+
+    let writer = SharcWriter::new(Vec::new(), OUTER_SHARC_KEY, Endianness::Little).unwrap();
+    let mut outer = write_entries(writer, &[("scene.bar", CompressionType::EdgeZLib, &bar)]);
+    outer.truncate(outer.len() / 2);
+
+    let entries = walk(&Walker::new(keys), outer);
+
+    assert_eq!(entries.len(), 1);
+    assert!(matches!(
+        entries[0].nested,
+        Some(Nested::Unopened(ArchiveVersion::BAR, _))
+    ));
+}
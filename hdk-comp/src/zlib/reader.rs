@@ -1,6 +1,6 @@
 use byteorder::{BigEndian, ReadBytesExt};
 use flate2::{Decompress, read::ZlibDecoder};
-use std::io::{self, Read};
+use std::io::{self, Read, Seek, SeekFrom};
 
 use super::EDGE_ZLIB_CHUNK_HEADER_SIZE;
 
@@ -73,3 +73,164 @@ impl<R: Read> Read for SegmentedZlibReader<R> {
         Ok(to_read)
     }
 }
+
+/// A chunk of a segmented zlib stream, as located by [`SeekableSegmentedZlibReader`].
+#[derive(Debug, Clone, Copy)]
+struct ChunkIndex {
+    /// Offset of the chunk's body in the compressed stream.
+    offset: u64,
+
+    /// Offset of the chunk's first byte in the decompressed data.
+    start: u64,
+
+    src_size: u16,
+    comp_size: u16,
+}
+
+/// A random-access variant of [`SegmentedZlibReader`].
+///
+/// On creation, the chunk headers are scanned (seeking past the chunk bodies) to
+/// build an index of where each chunk starts in both the compressed and
+/// decompressed data. Seeking then only needs to decompress the chunk containing
+/// the target position.
+///
+/// Offset `0` of `inner` must be the start of the segmented stream.
+pub struct SeekableSegmentedZlibReader<R: Read + Seek> {
+    inner: R,
+    chunks: Vec<ChunkIndex>,
+    len: u64,
+    pos: u64,
+
+    /// The index of the chunk held in `current_chunk`, if any.
+    current: Option<usize>,
+    current_chunk: Vec<u8>,
+}
+
+impl<R: Read + Seek> SeekableSegmentedZlibReader<R> {
+    /// Create a new reader, indexing the chunks of the whole stream.
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut chunks = Vec::new();
+        let mut offset = inner.seek(SeekFrom::Start(0))?;
+        let mut start = 0u64;
+
+        loop {
+            let mut header = [0u8; EDGE_ZLIB_CHUNK_HEADER_SIZE];
+
+            match inner.read_exact(&mut header) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+
+            let mut header_slice = &header[..];
+            let src_size = header_slice.read_u16::<BigEndian>()?;
+            let comp_size = header_slice.read_u16::<BigEndian>()?;
+
+            offset += EDGE_ZLIB_CHUNK_HEADER_SIZE as u64;
+            chunks.push(ChunkIndex {
+                offset,
+                start,
+                src_size,
+                comp_size,
+            });
+
+            offset = inner.seek(SeekFrom::Current(i64::from(comp_size)))?;
+            start += u64::from(src_size);
+        }
+
+        Ok(Self {
+            inner,
+            chunks,
+            len: start,
+            pos: 0,
+            current: None,
+            current_chunk: Vec::new(),
+        })
+    }
+
+    /// The total decompressed size of the stream.
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Decompress the chunk at `index` into `current_chunk`, unless it is already there.
+    fn load_chunk(&mut self, index: usize) -> io::Result<()> {
+        if self.current == Some(index) {
+            return Ok(());
+        }
+
+        let chunk = self.chunks[index];
+        self.inner.seek(SeekFrom::Start(chunk.offset))?;
+
+        let mut chunk_data = vec![0u8; chunk.comp_size as usize];
+        self.inner.read_exact(&mut chunk_data)?;
+
+        self.current = None;
+        self.current_chunk.clear();
+
+        if chunk.src_size == chunk.comp_size {
+            // Uncompressed chunk
+            self.current_chunk = chunk_data;
+        } else {
+            // Compressed chunk
+            self.current_chunk.reserve(chunk.src_size as usize);
+            let no_header = Decompress::new(false);
+            let mut decoder = ZlibDecoder::new_with_decompress(&chunk_data[..], no_header);
+            decoder.read_to_end(&mut self.current_chunk)?;
+        }
+
+        if self.current_chunk.len() != chunk.src_size as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Chunk decompressed to an unexpected size",
+            ));
+        }
+
+        self.current = Some(index);
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableSegmentedZlibReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0); // EOF
+        }
+
+        // Last chunk starting at or before the current position
+        let index = self.chunks.partition_point(|chunk| chunk.start <= self.pos) - 1;
+        self.load_chunk(index)?;
+
+        let cursor = (self.pos - self.chunks[index].start) as usize;
+        let available = self.current_chunk.len() - cursor;
+        let to_read = std::cmp::min(available, buf.len());
+
+        buf[..to_read].copy_from_slice(&self.current_chunk[cursor..cursor + to_read]);
+        self.pos += to_read as u64;
+
+        Ok(to_read)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableSegmentedZlibReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        Ok(self.pos)
+    }
+}
@@ -1,5 +1,8 @@
-use crate::zlib::{reader::SegmentedZlibReader, writer::SegmentedZlibWriter};
-use std::io::{Read, Write};
+use crate::zlib::{
+    reader::{SeekableSegmentedZlibReader, SegmentedZlibReader},
+    writer::SegmentedZlibWriter,
+};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 
 #[test]
 fn test_roundtrip_simple() {
@@ -58,3 +61,50 @@ fn test_empty() {
 
     assert_eq!(output.len(), 0);
 }
+
+#[test]
+fn test_seekable_random_access() {
+    // Mix of compressible and incompressible data to get both chunk kinds
+    let mut data: Vec<u8> = (0..150_000).map(|i| (i % 256) as u8).collect();
+    let mut state = 0x1234_5678u32;
+    data.extend((0..70_000).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as u8
+    }));
+
+    let mut buffer = Vec::new();
+
+    {
+        let mut writer = SegmentedZlibWriter::new(&mut buffer);
+        writer.write_all(&data).unwrap();
+        writer.finish().unwrap();
+    }
+
+    let mut reader = SeekableSegmentedZlibReader::new(Cursor::new(&buffer)).unwrap();
+    assert_eq!(reader.len(), data.len() as u64);
+
+    // Reads spanning a chunk boundary, backwards seeks and the incompressible tail
+    for offset in [200_000u64, 65_530, 12, 131_000, 0] {
+        reader.seek(SeekFrom::Start(offset)).unwrap();
+        let mut out = [0u8; 32];
+        reader.read_exact(&mut out).unwrap();
+        assert_eq!(out, data[offset as usize..offset as usize + 32]);
+    }
+
+    reader.seek(SeekFrom::End(-10)).unwrap();
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).unwrap();
+    assert_eq!(out, data[data.len() - 10..]);
+
+    // Reading past the end yields nothing
+    reader.seek(SeekFrom::Start(data.len() as u64 + 5)).unwrap();
+    assert_eq!(reader.read(&mut [0u8; 4]).unwrap(), 0);
+
+    assert!(
+        reader
+            .seek(SeekFrom::Current(-(data.len() as i64) - 10))
+            .is_err()
+    );
+}
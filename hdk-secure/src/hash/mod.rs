@@ -1,5 +1,7 @@
 use std::path::Path;
 
+pub mod preimage;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct AfsHash(pub i32);
 
@@ -8,7 +10,22 @@ impl AfsHash {
     ///
     /// This does not verify that the string is an actual AfsHash: only that it matches the expected format.
     pub fn is_valid_hash_str(s: &str) -> bool {
-        s.len() == 8 && s.chars().all(|c| c.is_ascii_hexdigit() && c.is_uppercase())
+        s.len() == 8
+            && s.chars()
+                .all(|c| c.is_ascii_digit() || ('A'..='F').contains(&c))
+    }
+
+    /// Parse an uppercase hex AfsHash, as formatted by `Display`.
+    ///
+    /// Returns `None` if the string is not in that format (see [`AfsHash::is_valid_hash_str`]).
+    pub fn from_hash_str(s: &str) -> Option<Self> {
+        if !Self::is_valid_hash_str(s) {
+            return None;
+        }
+
+        u32::from_str_radix(s, 16)
+            .ok()
+            .map(|hash| Self(hash as i32))
     }
 
     pub fn new_from_str(s: &str) -> Self {
@@ -25,16 +42,13 @@ impl AfsHash {
         // Check if file name is already a hash in uppercase hex
         //
         // WARN: This might shadow actual file names that just so happen to be valid hashes
-        if let Some(file_name) = path.file_name().and_then(|n| n.to_str())
-            && file_name.len() == 8
-            && file_name
-                .chars()
-                .all(|c| c.is_ascii_hexdigit() && c.is_uppercase())
+        if let Some(hash) = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(Self::from_hash_str)
         {
-            // If it is, parse it directly as a hash
-            if let Ok(hash) = i32::from_str_radix(file_name, 16) {
-                return Self(hash);
-            }
+            // If it is, use it directly as a hash
+            return hash;
         }
 
         // Otherwise, compute the hash from the path string
@@ -60,7 +74,7 @@ pub fn afs_hash(data: std::str::Chars) -> i32 {
         c = c.to_lowercase().next().unwrap();
 
         hash = hash.overflowing_mul(0x25).0; // 37
-        hash += c as i32;
+        hash = hash.wrapping_add(c as i32);
     }
 
     hash
@@ -0,0 +1,251 @@
+//! Preimage search for [`AfsHash`] values.
+//!
+//! The hash is a polynomial over the lowercased characters: each character multiplies
+//! the state by 37 and adds itself, modulo 2^32. Since 37 is odd it is invertible
+//! modulo 2^32, so the hash can be run backwards from a target as well as forwards
+//! from a known prefix.
+//!
+//! Names are searched as a head followed by a tail, meeting in the middle: the state
+//! after `prefix + head` is computed once for every head, and each target is unwound
+//! through every extension and tail and looked up among those states. That costs
+//! `heads + targets × extensions × tails` steps instead of their product.
+//!
+//! # Example
+//!
+//! ```rust
+//! use hdk_secure::hash::AfsHash;
+//! use hdk_secure::hash::preimage::PreimageSearch;
+//!
+//! let target = AfsHash::new_from_str("textures/wood_floor.dds");
+//!
+//! let found = PreimageSearch::new("textures/")
+//!     .with_words(["floor", "wall", "wood"], &["_"])
+//!     .with_extensions(["dds", "png"])
+//!     .search(&[target]);
+//!
+//! assert_eq!(found, [(target, "textures/wood_floor.dds".to_string())]);
+//! ```
+
+use std::collections::{BTreeSet, HashMap};
+
+use super::{AfsHash, afs_hash};
+
+/// Multiplier applied to the state for each character.
+const MULTIPLIER: u32 = 0x25;
+
+/// Inverse of [`MULTIPLIER`] modulo 2^32.
+const INVERSE: u32 = inverse(MULTIPLIER);
+
+/// Inverse of an odd number modulo 2^32, by Newton's iteration.
+const fn inverse(value: u32) -> u32 {
+    // Correct to 3 bits, doubling with each step
+    let mut inverse = value;
+    let mut step = 0;
+
+    while step < 4 {
+        inverse = inverse.wrapping_mul(2u32.wrapping_sub(value.wrapping_mul(inverse)));
+        step += 1;
+    }
+
+    inverse
+}
+
+/// A piece of a path, with what it does to the hash state.
+struct Part<'a> {
+    text: &'a str,
+    hash: u32,
+    power: u32,
+    inverse_power: u32,
+}
+
+impl<'a> Part<'a> {
+    fn new(text: &'a str) -> Self {
+        let len = text.chars().count() as u32;
+
+        Self {
+            text,
+            hash: afs_hash(text.chars()) as u32,
+            power: MULTIPLIER.wrapping_pow(len),
+            inverse_power: INVERSE.wrapping_pow(len),
+        }
+    }
+
+    /// The state after hashing this part from `state`.
+    const fn forward(&self, state: u32) -> u32 {
+        state.wrapping_mul(self.power).wrapping_add(self.hash)
+    }
+
+    /// The state that hashing this part turns into `state`.
+    const fn backward(&self, state: u32) -> u32 {
+        state
+            .wrapping_sub(self.hash)
+            .wrapping_mul(self.inverse_power)
+    }
+}
+
+/// Names made of any head followed by any tail.
+#[derive(Debug, Clone)]
+struct Space {
+    heads: Vec<String>,
+    tails: Vec<String>,
+}
+
+/// A search for the paths under a known prefix that hash to given values.
+///
+/// Candidate names are added with [`with_words`](Self::with_words),
+/// [`with_charset`](Self::with_charset) or [`with_parts`](Self::with_parts); each call
+/// adds names to try, and searching without any finds nothing.
+#[derive(Debug, Clone)]
+pub struct PreimageSearch {
+    prefix: String,
+    spaces: Vec<Space>,
+    suffixes: Vec<String>,
+}
+
+impl PreimageSearch {
+    /// A search for names directly under `prefix`, e.g. `"Objects/{uuid}/textures/"`.
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            spaces: Vec::new(),
+            suffixes: vec![String::new()],
+        }
+    }
+
+    /// Try names made of one word, or of two words joined by one of `separators`.
+    pub fn with_words<I, S>(self, words: I, separators: &[&str]) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let words: Vec<String> = words.into_iter().map(Into::into).collect();
+
+        let mut tails = vec![String::new()];
+        for separator in separators {
+            tails.extend(words.iter().map(|word| format!("{separator}{word}")));
+        }
+
+        self.with_parts(words, tails)
+    }
+
+    /// Try every name of 1 to `max_len` characters from `charset`.
+    ///
+    /// The search stores about `charset.len() ^ ceil(max_len / 2)` states, so
+    /// `max_len` should stay small: 8 lowercase alphanumeric characters take a couple
+    /// of million.
+    pub fn with_charset(self, charset: &str, max_len: usize) -> Self {
+        // The hash is case-insensitive, so only one case needs trying
+        let mut chars: Vec<char> = charset.chars().flat_map(char::to_lowercase).collect();
+        chars.sort_unstable();
+        chars.dedup();
+
+        let head_len = max_len.div_ceil(2);
+        self.with_parts(
+            strings(&chars, head_len),
+            strings(&chars, max_len - head_len),
+        )
+    }
+
+    /// Try names made of any of `heads` followed by any of `tails`.
+    pub fn with_parts<H, T, S>(mut self, heads: H, tails: T) -> Self
+    where
+        H: IntoIterator<Item = S>,
+        T: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.spaces.push(Space {
+            heads: heads.into_iter().map(Into::into).collect(),
+            tails: tails.into_iter().map(Into::into).collect(),
+        });
+        self
+    }
+
+    /// Try each extension (without its dot) after the names, instead of bare names.
+    pub fn with_extensions<I, S>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.suffixes = extensions
+            .into_iter()
+            .map(|extension| format!(".{}", extension.as_ref()))
+            .collect();
+        self
+    }
+
+    /// Find the candidate paths that hash to one of `targets`.
+    ///
+    /// Returns every match, sorted by hash (as unsigned) then path: a target may have
+    /// several, especially with long charset names.
+    pub fn search(&self, targets: &[AfsHash]) -> Vec<(AfsHash, String)> {
+        let start = afs_hash(self.prefix.chars()) as u32;
+        let suffixes: Vec<Part> = self
+            .suffixes
+            .iter()
+            .map(|suffix| Part::new(suffix))
+            .collect();
+
+        let mut targets: Vec<u32> = targets.iter().map(|hash| hash.0 as u32).collect();
+        targets.sort_unstable();
+        targets.dedup();
+
+        let mut found = BTreeSet::new();
+
+        for space in &self.spaces {
+            let mut states: HashMap<u32, Vec<&str>> = HashMap::new();
+            for head in &space.heads {
+                let state = Part::new(head).forward(start);
+                states.entry(state).or_default().push(head);
+            }
+
+            let tails: Vec<Part> = space.tails.iter().map(|tail| Part::new(tail)).collect();
+
+            for &target in &targets {
+                for suffix in &suffixes {
+                    let before_suffix = suffix.backward(target);
+
+                    for tail in &tails {
+                        let Some(heads) = states.get(&tail.backward(before_suffix)) else {
+                            continue;
+                        };
+
+                        for head in heads
+                            .iter()
+                            .filter(|head| !(head.is_empty() && tail.text.is_empty()))
+                        {
+                            let path = format!("{}{head}{}{}", self.prefix, tail.text, suffix.text);
+                            found.insert((target, path));
+                        }
+                    }
+                }
+            }
+        }
+
+        found
+            .into_iter()
+            .map(|(hash, path)| (AfsHash(hash as i32), path))
+            .collect()
+    }
+}
+
+/// Every string of up to `max_len` of `chars`, including the empty one.
+fn strings(chars: &[char], max_len: usize) -> Vec<String> {
+    let mut strings = vec![String::new()];
+    let mut last = 0;
+
+    for _ in 0..max_len {
+        let end = strings.len();
+
+        for index in last..end {
+            for &c in chars {
+                let mut string = strings[index].clone();
+                string.push(c);
+                strings.push(string);
+            }
+        }
+
+        last = end;
+    }
+
+    strings
+}
@@ -1,6 +1,6 @@
-use cipher::StreamCipher;
+use cipher::{StreamCipher, StreamCipherSeek};
 use std::cmp;
-use std::io::{self, Read};
+use std::io::{self, Read, Seek, SeekFrom};
 
 // 8KB buffer (Aligns with 64-bit XTEA and 128-bit AES blocks perfectly)
 const BUF_SIZE: usize = 8192;
@@ -59,3 +59,55 @@ impl<R: Read, C: StreamCipher> Read for CryptoReader<R, C> {
         Ok(to_copy)
     }
 }
+
+/// A random-access variant of [`CryptoReader`] for seekable stream ciphers (e.g. CTR modes).
+///
+/// The keystream position follows the inner reader's position: offset `0` of `inner`
+/// must be the start of the encrypted data (e.g. a window over the encrypted region).
+/// Seeking moves both the inner reader and the keystream, so any byte can be
+/// decrypted without processing the ones before it.
+pub struct SeekableCryptoReader<R, C> {
+    inner: R,
+    cipher: C,
+    pos: u64,
+}
+
+impl<R: Read + Seek, C: StreamCipher + StreamCipherSeek> SeekableCryptoReader<R, C> {
+    /// Create a new `SeekableCryptoReader`, rewinding `inner` to the start of the keystream.
+    pub fn new(mut inner: R, mut cipher: C) -> io::Result<Self> {
+        inner.rewind()?;
+        cipher.seek(0u64);
+
+        Ok(Self {
+            inner,
+            cipher,
+            pos: 0,
+        })
+    }
+
+    /// Consume this reader and return the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read + Seek, C: StreamCipher + StreamCipherSeek> Read for SeekableCryptoReader<R, C> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+
+        self.cipher
+            .try_seek(self.pos)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        self.cipher.apply_keystream(&mut buf[..n]);
+        self.pos += n as u64;
+
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek, C: StreamCipher + StreamCipherSeek> Seek for SeekableCryptoReader<R, C> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
+    }
+}
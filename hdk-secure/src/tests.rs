@@ -455,4 +455,141 @@ mod blowfish_tests {
 
         assert_eq!(out, plaintext);
     }
+
+    #[test]
+    fn seekable_crypto_reader_random_access() {
+        use crate::reader::SeekableCryptoReader;
+        use crate::writer::CryptoWriter;
+        use crate::xtea::modes::XteaPS3;
+        use cipher::generic_array::GenericArray;
+        use ctr::cipher::KeyIvInit;
+        use std::io::{Read, Seek, SeekFrom, Write};
+
+        let key = GenericArray::from([0x77u8; 16]);
+        let iv = GenericArray::from([0x88u8; 8]);
+        let plaintext: Vec<u8> = (0..10_000u32).map(|i| (i % 253) as u8).collect();
+
+        let mut writer = CryptoWriter::new(Vec::new(), XteaPS3::new(&key, &iv));
+        writer.write_all(&plaintext).unwrap();
+        let buf = writer.into_inner();
+
+        let cursor = std::io::Cursor::new(buf);
+        let mut reader = SeekableCryptoReader::new(cursor, XteaPS3::new(&key, &iv)).unwrap();
+
+        // Unaligned offsets, backwards and relative seeks
+        for offset in [5003u64, 17, 9999, 0, 4096] {
+            reader.seek(SeekFrom::Start(offset)).unwrap();
+            let mut out = [0u8; 1];
+            reader.read_exact(&mut out).unwrap();
+            assert_eq!(out[0], plaintext[offset as usize]);
+        }
+
+        reader.seek(SeekFrom::Current(-100)).unwrap();
+        let mut out = [0u8; 50];
+        reader.read_exact(&mut out).unwrap();
+        assert_eq!(out, plaintext[3997..4047]);
+
+        reader.seek(SeekFrom::End(-3)).unwrap();
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, plaintext[9997..]);
+    }
+}
+
+mod hash_tests {
+    use std::path::Path;
+
+    use crate::hash::AfsHash;
+
+    #[test]
+    fn hash_str_roundtrip() {
+        for hash in [
+            AfsHash(0),
+            AfsHash(0x1234_ABCD),
+            AfsHash(-1),
+            AfsHash::new_from_str("object.xml"),
+        ] {
+            let s = hash.to_string();
+            assert!(AfsHash::is_valid_hash_str(&s));
+            assert_eq!(AfsHash::from_hash_str(&s), Some(hash));
+        }
+
+        for invalid in ["1234abcd", "1234ABC", "1234ABCDE", "GHIJKLMN", "object.x"] {
+            assert!(!AfsHash::is_valid_hash_str(invalid));
+            assert_eq!(AfsHash::from_hash_str(invalid), None);
+        }
+    }
+
+    #[test]
+    fn new_from_path_uses_hash_names() {
+        assert_eq!(
+            AfsHash::new_from_path(Path::new("extracted/F00DCAFE")),
+            AfsHash(0xF00D_CAFEu32 as i32)
+        );
+        assert_eq!(
+            AfsHash::new_from_path(Path::new("Objects\\Object.XML")),
+            AfsHash::new_from_str("objects/object.xml")
+        );
+    }
+}
+
+mod preimage_tests {
+    use crate::hash::AfsHash;
+    use crate::hash::preimage::PreimageSearch;
+
+    #[test]
+    fn finds_charset_names() {
+        let targets = [
+            AfsHash::new_from_str("Objects/ABC/textures/k9x.dds"),
+            AfsHash::new_from_str("objects/abc/textures/zz0q1.png"),
+        ];
+
+        let found = PreimageSearch::new("Objects/ABC/textures/")
+            .with_charset("abcdefghijklmnopqrstuvwxyz0123456789", 5)
+            .with_extensions(["dds", "png"])
+            .search(&targets);
+
+        for target in targets {
+            assert!(
+                found.iter().any(|(hash, _)| *hash == target),
+                "no preimage of {target}"
+            );
+        }
+
+        assert!(found.contains(&(targets[0], "Objects/ABC/textures/k9x.dds".to_string())));
+        assert!(found.contains(&(targets[1], "Objects/ABC/textures/zz0q1.png".to_string())));
+        assert!(
+            found
+                .iter()
+                .all(|(hash, path)| AfsHash::new_from_str(path) == *hash)
+        );
+    }
+
+    #[test]
+    fn finds_word_names() {
+        let target = AfsHash::new_from_str("scripts/door_open.lua");
+
+        let search =
+            PreimageSearch::new("scripts/").with_words(["door", "open", "close"], &["", "_"]);
+
+        assert_eq!(
+            search.clone().with_extensions(["lua"]).search(&[target]),
+            [(target, "scripts/door_open.lua".to_string())]
+        );
+        // Without extensions, names are tried bare
+        assert!(search.search(&[target]).is_empty());
+    }
+
+    #[test]
+    fn finds_nothing_without_names() {
+        let target = AfsHash::new_from_str("object.xml");
+
+        assert!(PreimageSearch::new("").search(&[target]).is_empty());
+        assert!(
+            PreimageSearch::new("object.xml")
+                .with_parts([""], [""])
+                .search(&[target])
+                .is_empty()
+        );
+    }
 }